@@ -1,11 +1,14 @@
 use pyo3::prelude::*;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::process::Stdio; // For TokioCommand setup
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::process::{Child, Command as TokioCommand}; // Ensure Child is imported
-use log::{info, warn, error};
+use encoding_rs::Encoding;
+use log::{info, warn};
 use thiserror::Error;
 
+mod logging;
+
 #[derive(Error, Debug)]
 pub enum CommandExecutorError {
     #[error("Failed to parse command: {0}")]
@@ -18,10 +21,10 @@ pub enum CommandExecutorError {
         source: std::io::Error,
     },
 
-    #[error("Command '{command}' timed out after {duration_secs} seconds")]
+    #[error("Command '{command}' timed out after {} seconds", duration.as_secs_f64())]
     TimeoutError {
         command: String,
-        duration_secs: u64,
+        duration: std::time::Duration,
     },
 
     #[error("I/O error during command execution: {source}")]
@@ -41,6 +44,13 @@ pub enum CommandExecutorError {
 
     #[error("Empty command string provided")]
     EmptyCommandError,
+
+    #[error("Failed to open output file '{path}': {source}")]
+    OutputFileError {
+        path: String,
+        #[source]
+        source: std::io::Error,
+    },
 }
 
 impl From<CommandExecutorError> for PyErr {
@@ -55,7 +65,9 @@ impl From<CommandExecutorError> for PyErr {
             CommandExecutorError::TimeoutError { .. } => {
                 pyo3::exceptions::PyTimeoutError::new_err(err.to_string())
             }
-            CommandExecutorError::IoError { .. } | CommandExecutorError::StdinWriteError(_) => {
+            CommandExecutorError::IoError { .. }
+            | CommandExecutorError::StdinWriteError(_)
+            | CommandExecutorError::OutputFileError { .. } => {
                 pyo3::exceptions::PyIOError::new_err(err.to_string())
             }
             CommandExecutorError::JoinError { .. } => {
@@ -69,19 +81,463 @@ impl From<CommandExecutorError> for PyErr {
 #[pyclass]
 #[derive(Debug, Clone)]
 struct CommandOutput {
+    // Command line that was run, after env expansion
+    #[pyo3(get)]
+    command: String,
     #[pyo3(get)]
     stdout: String,
     #[pyo3(get)]
     stderr: String,
+    // Output exactly as the child wrote it, exposed as `bytes` through the getters below
+    stdout_raw: Vec<u8>,
+    stderr_raw: Vec<u8>,
+    #[pyo3(get)]
+    exit_code: Option<i32>,
+    // Exit codes `success()` accepts, `[0]` unless the caller declared others
+    #[pyo3(get)]
+    success_exit_codes: Vec<i32>,
+    // Resource usage of the finished child, only collected on Unix
+    #[pyo3(get)]
+    max_rss_bytes: Option<u64>,
+    #[pyo3(get)]
+    user_cpu_ms: Option<u64>,
+    #[pyo3(get)]
+    sys_cpu_ms: Option<u64>,
+    // Environment the command ran with, only attached when requested
+    #[pyo3(get)]
+    resolved_env: Option<HashMap<String, String>>,
+}
+
+#[pymethods]
+impl CommandOutput {
+    #[getter]
+    fn stdout_bytes(&self) -> &[u8] {
+        &self.stdout_raw
+    }
+
+    #[getter]
+    fn stderr_bytes(&self) -> &[u8] {
+        &self.stderr_raw
+    }
+
+    // Whether the command exited with one of `success_exit_codes`, never true when it was
+    // terminated by a signal
+    fn success(&self) -> bool {
+        self.exit_code
+            .is_some_and(|code| self.success_exit_codes.contains(&code))
+    }
+    // Invocation, output streams and exit status in one block, ready to be logged or shared
+    fn transcript(&self) -> String {
+        let exit_status = self
+            .exit_code
+            .map_or("unknown (terminated by signal)".to_string(), |code| code.to_string());
+        let mut transcript = format!("$ {}\n", self.command);
+        for (name, content) in [("stdout", &self.stdout), ("stderr", &self.stderr)] {
+            transcript.push_str(&format!("---------- {} ----------\n", name));
+            transcript.push_str(content);
+            if !content.is_empty() && !content.ends_with('\n') {
+                transcript.push('\n');
+            }
+        }
+        transcript.push_str(&format!("---------- exit status: {} ----------\n", exit_status));
+        transcript
+    }
+}
+
+impl CommandOutput {
+    // Re-decodes the captured output with the given charsets, streams without one stay UTF-8
+    fn decoded(mut self, stdout_encoding: Option<&'static Encoding>, stderr_encoding: Option<&'static Encoding>) -> Self {
+        if let Some(encoding) = stdout_encoding {
+            self.stdout = encoding.decode(&self.stdout_raw).0.into_owned();
+        }
+        if let Some(encoding) = stderr_encoding {
+            self.stderr = encoding.decode(&self.stderr_raw).0.into_owned();
+        }
+        self
+    }
+}
+
+// Looks up a charset by its WHATWG label, e.g. "latin1" or "shift_jis". Unknown labels fall
+// back to UTF-8 with a warning rather than failing the command.
+fn resolve_encoding(label: Option<&str>) -> Option<&'static Encoding> {
+    let label = label?;
+    let encoding = Encoding::for_label(label.trim().as_bytes());
+    if encoding.is_none() {
+        warn!("Unknown encoding '{}', decoding output as UTF-8", label);
+    }
+    encoding
+}
+
+#[pyclass]
+#[derive(Debug, Clone)]
+struct PipelineOutput {
+    // Output of the final stage
+    #[pyo3(get)]
+    output: CommandOutput,
+    // Exit code of the pipeline, honoring pipefail
     #[pyo3(get)]
     exit_code: Option<i32>,
+    #[pyo3(get)]
+    stage_exit_codes: Vec<Option<i32>>,
+    #[pyo3(get)]
+    stage_stderr: Vec<String>,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct ResourceUsage {
+    max_rss_bytes: Option<u64>,
+    user_cpu_ms: Option<u64>,
+    sys_cpu_ms: Option<u64>,
 }
 
-// Helper async function to manage the actual execution and I/O
+// Reaps the child with wait4 so its rusage can be collected alongside the exit status. A child
+// still running at `deadline` is killed and reaped, and None is returned for it.
+#[cfg(unix)]
+async fn wait_with_rusage(child: &mut Child, deadline: Option<tokio::time::Instant>) -> Result<Option<(std::process::ExitStatus, ResourceUsage)>, std::io::Error> {
+    use std::os::unix::process::ExitStatusExt;
+
+    let pid = match child.id() {
+        Some(pid) => pid as libc::pid_t,
+        // Already reaped, the status is cached by tokio
+        None => return Ok(Some((child.wait().await?, ResourceUsage::default()))),
+    };
+
+    let mut reaper = tokio::task::spawn_blocking(move || {
+        let mut status: libc::c_int = 0;
+        let mut rusage: libc::rusage = unsafe { std::mem::zeroed() };
+        loop {
+            let ret = unsafe { libc::wait4(pid, &mut status, 0, &mut rusage) };
+            if ret == pid {
+                break;
+            }
+            let err = std::io::Error::last_os_error();
+            if err.kind() != std::io::ErrorKind::Interrupted {
+                return Err(err);
+            }
+        }
+
+        let to_ms = |tv: libc::timeval| (tv.tv_sec as u64) * 1000 + (tv.tv_usec as u64) / 1000;
+        // ru_maxrss is reported in bytes on macOS and in kilobytes elsewhere
+        let max_rss_bytes = if cfg!(target_os = "macos") {
+            rusage.ru_maxrss as u64
+        } else {
+            rusage.ru_maxrss as u64 * 1024
+        };
+
+        Ok((
+            std::process::ExitStatus::from_raw(status),
+            ResourceUsage {
+                max_rss_bytes: Some(max_rss_bytes),
+                user_cpu_ms: Some(to_ms(rusage.ru_utime)),
+                sys_cpu_ms: Some(to_ms(rusage.ru_stime)),
+            },
+        ))
+    });
+
+    let Some(deadline) = deadline else {
+        return reaper.await?.map(Some);
+    };
+    match tokio::time::timeout_at(deadline, &mut reaper).await {
+        Ok(waited) => waited?.map(Some),
+        Err(_) => {
+            // Until the reaper returns the pid still belongs to the child, so it can't hit a
+            // reused pid. Killing it also ends the blocking wait4, which then reaps it.
+            if !reaper.is_finished() {
+                unsafe { libc::kill(pid, libc::SIGKILL) };
+            }
+            reaper.await??;
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(not(unix))]
+async fn wait_with_rusage(child: &mut Child, deadline: Option<tokio::time::Instant>) -> Result<Option<(std::process::ExitStatus, ResourceUsage)>, std::io::Error> {
+    let Some(deadline) = deadline else {
+        return Ok(Some((child.wait().await?, ResourceUsage::default())));
+    };
+    match tokio::time::timeout_at(deadline, child.wait()).await {
+        Ok(status) => Ok(Some((status?, ResourceUsage::default()))),
+        Err(_) => {
+            // Kills and waits for the child
+            child.kill().await?;
+            Ok(None)
+        }
+    }
+}
+
+const REDACTED_ENV_VALUE: &str = "<redacted>";
+const SECRET_ENV_KEY_MARKERS: [&str; 8] = [
+    "SECRET", "TOKEN", "PASSWORD", "PASSWD", "KEY", "CREDENTIAL", "AUTH", "PRIVATE",
+];
+
+// Inherited process environment with the provided env_vars taking precedence
+fn merged_env(env_vars: Option<&HashMap<String, String>>) -> HashMap<String, String> {
+    let mut merged: HashMap<String, String> = std::env::vars().collect();
+    if let Some(env_map) = env_vars {
+        merged.extend(env_map.clone());
+    }
+    merged
+}
+
+fn is_secret_env_key(key: &str) -> bool {
+    let key = key.to_ascii_uppercase();
+    SECRET_ENV_KEY_MARKERS.iter().any(|marker| key.contains(marker))
+}
+
+fn redact_env(env: HashMap<String, String>) -> HashMap<String, String> {
+    env.into_iter()
+        .map(|(key, value)| {
+            if is_secret_env_key(&key) {
+                (key, REDACTED_ENV_VALUE.to_string())
+            } else {
+                (key, value)
+            }
+        })
+        .collect()
+}
+
+// Expands `$NAME` and `${NAME}` references using `env`. `$$` yields a literal `$`,
+// unknown variables expand to an empty string like in a POSIX shell.
+fn expand_env_vars(input: &str, env: &HashMap<String, String>) -> Result<String, CommandExecutorError> {
+    let mut expanded = String::with_capacity(input.len());
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            expanded.push(c);
+            continue;
+        }
+
+        match chars.peek() {
+            Some('$') => {
+                chars.next();
+                expanded.push('$');
+            }
+            Some('{') => {
+                chars.next();
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(ch) => name.push(ch),
+                        None => {
+                            return Err(CommandExecutorError::ParseError(format!(
+                                "unterminated variable reference '${{{}' in '{}'",
+                                name, input
+                            )))
+                        }
+                    }
+                }
+                expanded.push_str(env.get(&name).map(String::as_str).unwrap_or(""));
+            }
+            Some(ch) if ch.is_ascii_alphabetic() || *ch == '_' => {
+                let mut name = String::new();
+                while let Some(ch) = chars.peek() {
+                    if !(ch.is_ascii_alphanumeric() || *ch == '_') {
+                        break;
+                    }
+                    name.push(*ch);
+                    chars.next();
+                }
+                expanded.push_str(env.get(&name).map(String::as_str).unwrap_or(""));
+            }
+            // Not a variable reference, keep the dollar sign as is
+            _ => expanded.push('$'),
+        }
+    }
+
+    Ok(expanded)
+}
+
+// Splits the command string and applies the working directory and extra env vars
+fn build_command(
+    command_str: &str,
+    cwd: Option<&str>,
+    env_vars: Option<&HashMap<String, String>>,
+) -> Result<TokioCommand, CommandExecutorError> {
+    let parts = shlex::split(command_str)
+        .ok_or_else(|| CommandExecutorError::ParseError(command_str.to_string()))?;
+
+    if parts.is_empty() {
+        return Err(CommandExecutorError::EmptyCommandError);
+    }
+
+    let mut cmd_builder = TokioCommand::new(&parts[0]);
+    if parts.len() > 1 {
+        cmd_builder.args(&parts[1..]);
+    }
+    if let Some(current_dir) = cwd {
+        cmd_builder.current_dir(current_dir);
+    }
+    if let Some(env_map) = env_vars {
+        cmd_builder.envs(env_map);
+    }
+
+    Ok(cmd_builder)
+}
+
+// On Linux the child gets SIGTERM once the process that spawned it dies. This is tied to the
+// spawning thread, which is one of the long-lived runtime workers here. macOS and Windows have
+// no equivalent, so children can still outlive a crashed host there (best effort: none).
+#[cfg(target_os = "linux")]
+fn set_kill_on_parent_exit(cmd_builder: &mut TokioCommand) {
+    let parent_pid = std::process::id() as libc::pid_t;
+    // Safety: only async-signal-safe libc calls run between fork and exec
+    unsafe {
+        cmd_builder.pre_exec(move || {
+            if libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGTERM) == -1 {
+                return Err(std::io::Error::last_os_error());
+            }
+            // The parent may have died before prctl took effect
+            if libc::getppid() != parent_pid {
+                libc::raise(libc::SIGTERM);
+            }
+            Ok(())
+        });
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_kill_on_parent_exit(_cmd_builder: &mut TokioCommand) {}
+
+// On Unix `nice` is applied to the child with setpriority before exec, using the usual -20
+// (highest) to 19 (lowest) range. Raising the priority above the current one needs privileges
+// and makes the spawn fail otherwise. Windows has no niceness, the value is mapped onto the
+// closest process priority class instead.
+#[cfg(unix)]
+fn set_priority(cmd_builder: &mut TokioCommand, nice: i32) {
+    // Safety: setpriority is async-signal-safe
+    unsafe {
+        cmd_builder.pre_exec(move || {
+            if libc::setpriority(libc::PRIO_PROCESS, 0, nice) == -1 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+}
+
+#[cfg(windows)]
+fn set_priority(cmd_builder: &mut TokioCommand, nice: i32) {
+    const IDLE_PRIORITY_CLASS: u32 = 0x0000_0040;
+    const BELOW_NORMAL_PRIORITY_CLASS: u32 = 0x0000_4000;
+    const NORMAL_PRIORITY_CLASS: u32 = 0x0000_0020;
+    const ABOVE_NORMAL_PRIORITY_CLASS: u32 = 0x0000_8000;
+    const HIGH_PRIORITY_CLASS: u32 = 0x0000_0080;
+
+    let priority_class = match nice {
+        15.. => IDLE_PRIORITY_CLASS,
+        1..=14 => BELOW_NORMAL_PRIORITY_CLASS,
+        0 => NORMAL_PRIORITY_CLASS,
+        -14..=-1 => ABOVE_NORMAL_PRIORITY_CLASS,
+        _ => HIGH_PRIORITY_CLASS,
+    };
+    cmd_builder.creation_flags(priority_class);
+}
+
+// On Unix the child's umask is set before exec, so files it creates get the same permissions
+// whatever the host's umask is. Windows has no umask, the value is ignored there.
+#[cfg(unix)]
+fn set_umask(cmd_builder: &mut TokioCommand, umask: u32) {
+    // Safety: umask is async-signal-safe
+    unsafe {
+        cmd_builder.pre_exec(move || {
+            libc::umask(umask as libc::mode_t);
+            Ok(())
+        });
+    }
+}
+
+#[cfg(windows)]
+fn set_umask(_cmd_builder: &mut TokioCommand, _umask: u32) {}
+
+fn spawn_command(cmd_builder: &mut TokioCommand) -> Result<Child, CommandExecutorError> {
+    cmd_builder.spawn().map_err(|e| CommandExecutorError::SpawnError {
+        command: cmd_builder.as_std().get_program().to_string_lossy().into_owned(),
+        source: e,
+    })
+}
+
+// timeout_ms takes precedence over timeout_seconds, a zero value of either means no timeout
+fn resolve_timeout(timeout_ms: Option<u64>, timeout_seconds: Option<u64>) -> Option<std::time::Duration> {
+    match (timeout_ms, timeout_seconds) {
+        (Some(ms), _) => Some(std::time::Duration::from_millis(ms)),
+        (None, Some(secs)) => Some(std::time::Duration::from_secs(secs)),
+        (None, None) => None,
+    }
+    .filter(|duration| !duration.is_zero())
+}
+
+// Creates (or truncates) the file the child's output is redirected to
+fn open_output_file(path: &str) -> Result<Stdio, CommandExecutorError> {
+    std::fs::File::create(path)
+        .map(Stdio::from)
+        .map_err(|e| CommandExecutorError::OutputFileError {
+            path: path.to_string(),
+            source: e,
+        })
+}
+
+// Reads a stream to the end. With `tail_lines` only the last N lines are kept while reading, so
+// chatty commands don't pile up output in memory, and a marker with the number of dropped lines
+// is put in front of them.
+async fn read_output<R: tokio::io::AsyncRead + Unpin>(
+    reader: Option<R>,
+    tail_lines: Option<usize>,
+) -> Result<Vec<u8>, std::io::Error> {
+    let mut buffer = Vec::new();
+    let Some(mut reader) = reader else {
+        return Ok(buffer);
+    };
+    let Some(tail_lines) = tail_lines else {
+        reader.read_to_end(&mut buffer).await?;
+        return Ok(buffer);
+    };
+
+    let mut lines: VecDeque<Vec<u8>> = VecDeque::new();
+    let mut total_lines = 0usize;
+    let mut line_open = false;
+    let mut chunk = [0u8; 8192];
+    loop {
+        let read = reader.read(&mut chunk).await?;
+        if read == 0 {
+            break;
+        }
+        for piece in chunk[..read].split_inclusive(|b| *b == b'\n') {
+            // A piece continues the previous line unless that one was complete
+            if line_open {
+                if let Some(line) = lines.back_mut() {
+                    line.extend_from_slice(piece);
+                }
+            } else {
+                lines.push_back(piece.to_vec());
+                total_lines += 1;
+            }
+            line_open = !piece.ends_with(b"\n");
+            while lines.len() > tail_lines {
+                lines.pop_front();
+            }
+        }
+    }
+
+    let dropped = total_lines - lines.len();
+    if dropped > 0 {
+        buffer.extend_from_slice(format!("... (truncated {} lines) ...\n", dropped).as_bytes());
+    }
+    buffer.extend(lines.into_iter().flatten());
+    Ok(buffer)
+}
+
+// Helper async function to manage the actual execution and I/O. Returns None if the child was
+// killed at `deadline`.
 async fn run_and_capture_output(
     mut child: Child, // Takes ownership of the child process
+    command_str: String,
     stdin_str: Option<String>,
-) -> Result<CommandOutput, CommandExecutorError> {
+    tail_lines: Option<usize>,
+    deadline: Option<tokio::time::Instant>,
+) -> Result<Option<CommandOutput>, CommandExecutorError> {
     let child_stdin_opt = child.stdin.take();
     let child_stdout_opt = child.stdout.take();
     let child_stderr_opt = child.stderr.take();
@@ -98,28 +554,23 @@ async fn run_and_capture_output(
     });
 
     // Spawn tasks to read stdout and stderr concurrently
-    let stdout_reader_task = tokio::spawn(async move {
-        let mut buffer = Vec::new();
-        if let Some(mut child_stdout) = child_stdout_opt {
-            child_stdout.read_to_end(&mut buffer).await?;
-        }
-        Ok::<_, std::io::Error>(buffer)
-    });
+    let stdout_reader_task = tokio::spawn(read_output(child_stdout_opt, tail_lines));
+    let stderr_reader_task = tokio::spawn(read_output(child_stderr_opt, tail_lines));
 
-    let stderr_reader_task = tokio::spawn(async move {
-        let mut buffer = Vec::new();
-        if let Some(mut child_stderr) = child_stderr_opt {
-            child_stderr.read_to_end(&mut buffer).await?;
-        }
-        Ok::<_, std::io::Error>(buffer)
-    });
+    // Wait for the child process to exit, the I/O tasks keep running meanwhile
+    let Some((status, rusage)) = wait_with_rusage(&mut child, deadline).await? else {
+        // Descendants of the killed child may still hold the pipes open
+        stdin_writer_task.abort();
+        stdout_reader_task.abort();
+        stderr_reader_task.abort();
+        return Ok(None);
+    };
 
-    // Wait for all I/O tasks and the child process to complete
-    let (stdin_result, stdout_result, stderr_result, status_result) = tokio::join!(
+    // Wait for all I/O tasks to complete
+    let (stdin_result, stdout_result, stderr_result) = tokio::join!(
         stdin_writer_task,
         stdout_reader_task,
         stderr_reader_task,
-        child.wait() // Wait for the child process to exit
     );
 
     // Process results from tokio::join, handling potential errors
@@ -128,21 +579,30 @@ async fn run_and_capture_output(
 
     let stdout_buf = stdout_result??; // Result<Result<Vec<u8>, std::io::Error>, JoinError>
     let stderr_buf = stderr_result??; // Result<Result<Vec<u8>, std::io::Error>, JoinError>
-    let status = status_result?;      // Result<std::process::ExitStatus, std::io::Error>
 
     let stdout = String::from_utf8_lossy(&stdout_buf).into_owned();
     let stderr = String::from_utf8_lossy(&stderr_buf).into_owned();
     let exit_code = status.code();
 
-    Ok(CommandOutput {
+    Ok(Some(CommandOutput {
+        command: command_str,
         stdout,
         stderr,
+        stdout_raw: stdout_buf,
+        stderr_raw: stderr_buf,
         exit_code,
-    })
+        success_exit_codes: vec![0],
+        max_rss_bytes: rusage.max_rss_bytes,
+        user_cpu_ms: rusage.user_cpu_ms,
+        sys_cpu_ms: rusage.sys_cpu_ms,
+        resolved_env: None,
+    }))
 }
 
 
 #[pyfunction]
+#[pyo3(signature = (command_str, cwd=None, env_vars=None, timeout_seconds=None, stdin_str=None, expand_env=false, stdout_path=None, stderr_path=None, timeout_ms=None, include_resolved_env=false, include_secret_env=false, kill_on_parent_exit=None, nice=None, prepend_command_header=false, encoding=None, stderr_encoding=None, umask=None, tail_lines=None, success_exit_codes=None))]
+#[allow(clippy::too_many_arguments)]
 fn execute_command_rust_async<'a>(
     py: Python<'a>,
     command_str: String,
@@ -150,74 +610,261 @@ fn execute_command_rust_async<'a>(
     env_vars: Option<HashMap<String, String>>,
     timeout_seconds: Option<u64>,
     stdin_str: Option<String>,
+    expand_env: bool,
+    stdout_path: Option<String>,
+    stderr_path: Option<String>,
+    timeout_ms: Option<u64>,
+    include_resolved_env: bool,
+    include_secret_env: bool,
+    kill_on_parent_exit: Option<bool>,
+    nice: Option<i32>,
+    prepend_command_header: bool,
+    encoding: Option<String>,
+    stderr_encoding: Option<String>,
+    umask: Option<u32>,
+    tail_lines: Option<usize>,
+    success_exit_codes: Option<Vec<i32>>,
 ) -> PyResult<Bound<'a, PyAny>> {
     pyo3_async_runtimes::tokio::future_into_py(py, async move {
         let result: Result<CommandOutput, CommandExecutorError> = async {
+            // stderr follows `encoding` unless it has its own
+            let stdout_encoding = resolve_encoding(encoding.as_deref());
+            let stderr_encoding = match stderr_encoding.as_deref() {
+                Some(label) => resolve_encoding(Some(label)),
+                None => stdout_encoding,
+            };
+            let resolved_env = include_resolved_env.then(|| {
+                let env = merged_env(env_vars.as_ref());
+                if include_secret_env { env } else { redact_env(env) }
+            });
+
+            let (command_str, cwd) = if expand_env {
+                let merged_env = merged_env(env_vars.as_ref());
+                let cwd = cwd.map(|dir| expand_env_vars(&dir, &merged_env)).transpose()?;
+                (expand_env_vars(&command_str, &merged_env)?, cwd)
+            } else {
+                (command_str, cwd)
+            };
+
             let original_command_str = command_str.clone(); // For error reporting
-            let parts = shlex::split(&command_str)
-                .ok_or_else(|| CommandExecutorError::ParseError(original_command_str.clone()))?;
+            let mut cmd_builder = build_command(&command_str, cwd.as_deref(), env_vars.as_ref())?;
+            if kill_on_parent_exit.unwrap_or(true) {
+                set_kill_on_parent_exit(&mut cmd_builder);
+            }
+            if let Some(nice) = nice {
+                set_priority(&mut cmd_builder, nice);
+            }
+            if let Some(umask) = umask {
+                set_umask(&mut cmd_builder, umask);
+            }
+
+        cmd_builder.stdin(Stdio::piped());
+        // Redirected output goes straight to disk and is left empty in CommandOutput
+        match &stdout_path {
+            Some(path) => cmd_builder.stdout(open_output_file(path)?),
+            None => cmd_builder.stdout(Stdio::piped()),
+        };
+        match &stderr_path {
+            Some(path) => cmd_builder.stderr(open_output_file(path)?),
+            None => cmd_builder.stderr(Stdio::piped()),
+        };
 
-            if parts.is_empty() {
-                return Err(CommandExecutorError::EmptyCommandError);
+        let child = spawn_command(&mut cmd_builder)?;
+
+        // Log records carry pid/command/exit_code/duration_ms/timed_out as kv fields for
+        // structured handlers, the message stays human readable. See `logging::KvLogger` for
+        // how they reach Python logging.
+        let pid = child.id();
+        let child_pid_str = pid.map(|id| id.to_string()).unwrap_or_else(|| "unknown".to_string());
+        let started_at = std::time::Instant::now();
+        info!(pid = pid, command = command_str.as_str(); "Spawned child process (PID: {}) for command: {}", child_pid_str, command_str);
+
+        let timeout = resolve_timeout(timeout_ms, timeout_seconds);
+        if timeout.is_none() {
+            info!(pid = pid, command = command_str.as_str(); "Command (PID: {}) running without timeout.", child_pid_str);
+        }
+        let deadline = timeout.map(|timeout_duration| tokio::time::Instant::now() + timeout_duration);
+        let result = match run_and_capture_output(child, command_str.clone(), stdin_str.clone(), tail_lines, deadline).await? {
+            Some(output) => Ok(output),
+            // Only a deadline gets the child killed
+            None => {
+                let timeout_duration = timeout.unwrap_or_default();
+                warn!(
+                    pid = pid, command = command_str.as_str(), duration_ms = started_at.elapsed().as_millis() as u64, timed_out = true;
+                    "Command (PID: {}) timed out after {}s and was killed.", child_pid_str, timeout_duration.as_secs_f64()
+                );
+                Err(CommandExecutorError::TimeoutError {
+                    command: original_command_str, // Use the cloned original command string
+                    duration: timeout_duration,
+                })
             }
+        };
+
+        if let Ok(output) = &result {
+            info!(
+                pid = pid, command = command_str.as_str(), exit_code = output.exit_code, duration_ms = started_at.elapsed().as_millis() as u64, timed_out = false;
+                "Command (PID: {}) finished with exit code {}.", child_pid_str, output.exit_code.map_or("unknown".to_string(), |code| code.to_string())
+            );
+        }
+        result.map(|output| {
+            let output = output.decoded(stdout_encoding, stderr_encoding);
+            // Only captured stdout gets the header, redirected output is left as the child wrote it
+            let stdout = if prepend_command_header && stdout_path.is_none() {
+                format!("$ {}\n{}", output.command, output.stdout)
+            } else {
+                output.stdout
+            };
+            let success_exit_codes = success_exit_codes.unwrap_or(output.success_exit_codes);
+            CommandOutput { stdout, resolved_env, success_exit_codes, ..output }
+        })
+    }.await; // End of inner async block
+    result.map_err(|e| e.into()) // Convert CommandExecutorError to PyErr
+    })
+}
+
+// Runs every stage of the pipeline concurrently, each stage's stdout feeding the next stage's stdin
+#[allow(clippy::too_many_arguments)]
+async fn run_pipeline(
+    commands: Vec<String>,
+    cwd: Option<String>,
+    env_vars: Option<HashMap<String, String>>,
+    stdin_str: Option<String>,
+    pipefail: bool,
+    kill_on_parent_exit: bool,
+    nice: Option<i32>,
+    umask: Option<u32>,
+    tail_lines: Option<usize>,
+    deadline: Option<tokio::time::Instant>,
+) -> Result<Option<PipelineOutput>, CommandExecutorError> {
+    if commands.is_empty() {
+        return Err(CommandExecutorError::EmptyCommandError);
+    }
 
-        let mut cmd_builder = TokioCommand::new(&parts[0]);
-        if parts.len() > 1 {
-            cmd_builder.args(&parts[1..]);
+    let mut stages = Vec::with_capacity(commands.len());
+    let mut previous_stdout: Option<Stdio> = None;
+    for (index, command_str) in commands.iter().enumerate() {
+        let mut cmd_builder = build_command(command_str, cwd.as_deref(), env_vars.as_ref())?;
+        if kill_on_parent_exit {
+            set_kill_on_parent_exit(&mut cmd_builder);
         }
-        if let Some(current_dir) = cwd {
-            cmd_builder.current_dir(current_dir);
+        if let Some(nice) = nice {
+            set_priority(&mut cmd_builder, nice);
         }
-        if let Some(env_map) = env_vars {
-            cmd_builder.envs(env_map);
+        if let Some(umask) = umask {
+            set_umask(&mut cmd_builder, umask);
         }
-
-        cmd_builder.stdin(Stdio::piped());
+        cmd_builder.stdin(previous_stdout.take().unwrap_or_else(Stdio::piped));
         cmd_builder.stdout(Stdio::piped());
         cmd_builder.stderr(Stdio::piped());
 
-        let child = match cmd_builder.spawn() {
-            Ok(child_process) => child_process,
-            Err(e) => {
-                return Err(CommandExecutorError::SpawnError {
-                    command: parts[0].to_string(),
-                    source: e,
-                });
-            }
-        };
+        let mut child = spawn_command(&mut cmd_builder)?;
+        info!(pid = child.id(), command = command_str.as_str(), stage = index; "Spawned pipeline stage {} (PID: {:?}): {}", index, child.id(), command_str);
 
-        let child_pid_str = child.id().map(|id| id.to_string()).unwrap_or_else(|| "unknown".to_string());
-        info!("Spawned child process (PID: {}) for command: {}", child_pid_str, command_str);
-
-        if let Some(secs) = timeout_seconds {
-            let timeout_duration = std::time::Duration::from_secs(secs);
-            tokio::select! {
-                biased;
-                _ = tokio::time::sleep(timeout_duration) => {
-                    warn!("Command (PID: {}) timed out after {}s.", child_pid_str, secs);
-                    Err(CommandExecutorError::TimeoutError {
-                        command: original_command_str, // Use the cloned original command string
-                        duration_secs: secs,
-                    })
-                }
-                res = run_and_capture_output(child, stdin_str.clone()) => {
-                    info!("Command (PID: {}) finished before timeout.", child_pid_str);
-                    res // This is Result<CommandOutput, CommandExecutorError>
-                }
+        if index + 1 < commands.len() {
+            if let Some(stdout) = child.stdout.take() {
+                previous_stdout = Some(stdout.try_into()?);
             }
-        } else {
-            info!("Command (PID: {}) running without timeout.", child_pid_str);
-            run_and_capture_output(child, stdin_str.clone()).await
         }
-    }.await; // End of inner async block
-    result.map_err(|e| e.into()) // Convert CommandExecutorError to PyErr
+        let stage_stdin = if index == 0 { stdin_str.clone() } else { None };
+        stages.push(tokio::spawn(run_and_capture_output(child, command_str.clone(), stage_stdin, tail_lines, deadline)));
+    }
+
+    // Every stage is waited for, so stages killed at the deadline are reaped as well
+    let mut outputs = Vec::with_capacity(stages.len());
+    let mut timed_out = false;
+    for stage in stages {
+        match stage.await?? {
+            Some(output) => outputs.push(output),
+            None => timed_out = true,
+        }
+    }
+    if timed_out {
+        return Ok(None);
+    }
+
+    let stage_exit_codes: Vec<Option<i32>> = outputs.iter().map(|o| o.exit_code).collect();
+    let stage_stderr = outputs.iter().map(|o| o.stderr.clone()).collect();
+    let output = outputs.pop().expect("pipeline has at least one stage");
+    // With pipefail the rightmost failing stage decides the exit code, like in bash
+    let exit_code = if pipefail {
+        stage_exit_codes
+            .iter()
+            .rev()
+            .find(|code| **code != Some(0))
+            .copied()
+            .unwrap_or(output.exit_code)
+    } else {
+        output.exit_code
+    };
+    for (index, code) in stage_exit_codes.iter().enumerate() {
+        if *code != Some(0) && index + 1 < stage_exit_codes.len() {
+            warn!(stage = index, exit_code = *code; "Pipeline stage {} exited with code {:?}.", index, code);
+        }
+    }
+
+    Ok(Some(PipelineOutput {
+        output,
+        exit_code,
+        stage_exit_codes,
+        stage_stderr,
+    }))
+}
+
+#[pyfunction]
+#[pyo3(signature = (commands, cwd=None, env_vars=None, timeout_seconds=None, stdin_str=None, pipefail=false, timeout_ms=None, kill_on_parent_exit=None, nice=None, umask=None, tail_lines=None))]
+#[allow(clippy::too_many_arguments)]
+fn execute_pipeline_rust_async<'a>(
+    py: Python<'a>,
+    commands: Vec<String>,
+    cwd: Option<String>,
+    env_vars: Option<HashMap<String, String>>,
+    timeout_seconds: Option<u64>,
+    stdin_str: Option<String>,
+    pipefail: bool,
+    timeout_ms: Option<u64>,
+    kill_on_parent_exit: Option<bool>,
+    nice: Option<i32>,
+    umask: Option<u32>,
+    tail_lines: Option<usize>,
+) -> PyResult<Bound<'a, PyAny>> {
+    pyo3_async_runtimes::tokio::future_into_py(py, async move {
+        let pipeline_str = commands.join(" | "); // For logging and error reporting
+        let timeout = resolve_timeout(timeout_ms, timeout_seconds);
+        let pipeline = run_pipeline(
+            commands,
+            cwd,
+            env_vars,
+            stdin_str,
+            pipefail,
+            kill_on_parent_exit.unwrap_or(true),
+            nice,
+            umask,
+            tail_lines,
+            timeout.map(|timeout_duration| tokio::time::Instant::now() + timeout_duration),
+        );
+
+        let result = match pipeline.await {
+            Ok(Some(output)) => Ok(output),
+            Ok(None) => {
+                let timeout_duration = timeout.unwrap_or_default();
+                warn!(command = pipeline_str.as_str(), timed_out = true; "Pipeline timed out after {}s and was killed: {}", timeout_duration.as_secs_f64(), pipeline_str);
+                Err(CommandExecutorError::TimeoutError {
+                    command: pipeline_str,
+                    duration: timeout_duration,
+                })
+            }
+            Err(err) => Err(err),
+        };
+        result.map_err(PyErr::from)
     })
 }
 
 #[pymodule]
 fn agent_lifecycle_rust(_py: Python, m: &Bound<'_, PyModule>) -> PyResult<()> {
-    pyo3_log::init();
+    logging::KvLogger::install().expect("A logger is already installed");
     m.add_function(pyo3::wrap_pyfunction!(execute_command_rust_async, m)?)?;
+    m.add_function(pyo3::wrap_pyfunction!(execute_pipeline_rust_async, m)?)?;
     m.add_class::<CommandOutput>()?;
+    m.add_class::<PipelineOutput>()?;
     Ok(())
 }
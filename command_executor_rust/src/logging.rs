@@ -0,0 +1,167 @@
+use log::kv::{self, VisitSource, VisitValue};
+use log::{LevelFilter, Log, Metadata, Record};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyTuple};
+
+// pyo3_log 0.12 only forwards the message of a record. Records with kv fields are built here
+// instead and pass the fields as `extra`, so Python handlers find them as LogRecord attributes,
+// e.g. `record.pid`. Records without fields still go through pyo3_log.
+pub struct KvLogger {
+    inner: pyo3_log::Logger,
+}
+
+impl KvLogger {
+    pub fn install() -> Result<(), log::SetLoggerError> {
+        let inner = Python::with_gil(|py| {
+            pyo3_log::Logger::new(py, pyo3_log::Caching::LoggersAndLevels)
+                .expect("Failed to initialize python logging")
+        });
+        log::set_boxed_logger(Box::new(KvLogger { inner }))?;
+        // Same default level as pyo3_log::init
+        log::set_max_level(LevelFilter::Debug);
+        Ok(())
+    }
+
+    fn log_with_fields(&self, py: Python<'_>, record: &Record) -> PyResult<()> {
+        let target = record.target().replace("::", ".");
+        let logger = py
+            .import("logging")?
+            .getattr("getLogger")?
+            .call1((&target,))?;
+        let level = python_level(record.level());
+        if !logger.call_method1("isEnabledFor", (level,))?.is_truthy()? {
+            return Ok(());
+        }
+
+        let mut fields = Fields(vec![]);
+        // Visiting only fails for values that can't be captured, those fields are left out
+        let _ = record.key_values().visit(&mut fields);
+        let extra = PyDict::new(py);
+        for (key, field) in fields.0 {
+            match field {
+                Field::Null => extra.set_item(key, py.None())?,
+                Field::Bool(value) => extra.set_item(key, value)?,
+                Field::U64(value) => extra.set_item(key, value)?,
+                Field::I64(value) => extra.set_item(key, value)?,
+                Field::F64(value) => extra.set_item(key, value)?,
+                Field::Str(value) => extra.set_item(key, value)?,
+            }
+        }
+
+        let py_record = logger.call_method1(
+            "makeRecord",
+            (
+                target,
+                level,
+                record.file(),
+                record.line().unwrap_or_default(),
+                format!("{}", record.args()),
+                PyTuple::empty(py), // args
+                py.None(),          // exc_info
+                py.None(),          // func
+                extra,
+            ),
+        )?;
+        logger.call_method1("handle", (py_record,))?;
+        Ok(())
+    }
+}
+
+impl Log for KvLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if record.key_values().count() == 0 {
+            self.inner.log(record);
+            return;
+        }
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        Python::with_gil(|py| {
+            // Like pyo3_log, an exception raised by logging must not replace one that was
+            // already set
+            let existing = PyErr::take(py);
+            if let Err(err) = self.log_with_fields(py, record) {
+                err.restore(py);
+            }
+            if let Some(existing) = existing {
+                existing.restore(py);
+            }
+        })
+    }
+
+    fn flush(&self) {}
+}
+
+fn python_level(level: log::Level) -> usize {
+    match level {
+        log::Level::Error => 40,
+        log::Level::Warn => 30,
+        log::Level::Info => 20,
+        log::Level::Debug => 10,
+        log::Level::Trace => 5,
+    }
+}
+
+enum Field {
+    Null,
+    Bool(bool),
+    U64(u64),
+    I64(i64),
+    F64(f64),
+    Str(String),
+}
+
+struct Fields(Vec<(String, Field)>);
+
+impl<'kvs> VisitSource<'kvs> for Fields {
+    fn visit_pair(&mut self, key: kv::Key<'kvs>, value: kv::Value<'kvs>) -> Result<(), kv::Error> {
+        let mut field = FieldVisitor(Field::Null);
+        value.visit(&mut field)?;
+        self.0.push((key.as_str().to_string(), field.0));
+        Ok(())
+    }
+}
+
+struct FieldVisitor(Field);
+
+impl<'v> VisitValue<'v> for FieldVisitor {
+    fn visit_any(&mut self, value: kv::Value) -> Result<(), kv::Error> {
+        self.0 = Field::Str(value.to_string());
+        Ok(())
+    }
+
+    fn visit_null(&mut self) -> Result<(), kv::Error> {
+        self.0 = Field::Null;
+        Ok(())
+    }
+
+    fn visit_u64(&mut self, value: u64) -> Result<(), kv::Error> {
+        self.0 = Field::U64(value);
+        Ok(())
+    }
+
+    fn visit_i64(&mut self, value: i64) -> Result<(), kv::Error> {
+        self.0 = Field::I64(value);
+        Ok(())
+    }
+
+    fn visit_f64(&mut self, value: f64) -> Result<(), kv::Error> {
+        self.0 = Field::F64(value);
+        Ok(())
+    }
+
+    fn visit_bool(&mut self, value: bool) -> Result<(), kv::Error> {
+        self.0 = Field::Bool(value);
+        Ok(())
+    }
+
+    fn visit_str(&mut self, value: &str) -> Result<(), kv::Error> {
+        self.0 = Field::Str(value.to_string());
+        Ok(())
+    }
+}
@@ -1,69 +1,1148 @@
-use serde::{Deserialize, Serialize};
-use spacetimedb::spacetimedb;
+use spacetimedb::{log, spacetimedb, Identity};
 
-#[spacetimedb(table)]
-#[derive(Serialize, Deserialize)]
-pub struct User {
-    #[primarykey]
-    pub id: String,
-    pub name: String,
-    pub email: Option<String>,
-    pub avatar_url: Option<String>,
-    pub slack_id: String,
-    pub created_at: u64,
-}
+mod tables;
+
+pub use tables::*;
+
+// At most this many tokens are issued per user within the rate limit window, unless an admin
+// configured another limit with `set_token_rate_limit`
+const DEFAULT_MAX_TOKENS_PER_WINDOW: u64 = 5;
+const DEFAULT_TOKEN_RATE_LIMIT_WINDOW_SECS: u64 = 60;
+// Primary key of the only TokenRateLimit row
+const TOKEN_RATE_LIMIT_ID: u32 = 0;
+const TOKEN_LIFETIME_SECS: u64 = 30 * 24 * 60 * 60; // 30 days
+const ONE_TIME_TOKEN_LIFETIME_SECS: u64 = 5 * 60; // long enough to enter a device-login code
 
-#[spacetimedb(table)]
-#[derive(Serialize, Deserialize)]
-pub struct AuthToken {
-    #[primarykey]
-    pub token: String,
-    pub user_id: String,
-    pub created_at: u64,
-    pub expires_at: u64,
+// How long a rotated-out token keeps working, so in-flight requests don't fail
+const ROTATED_TOKEN_GRACE_SECS: u64 = 60;
+// Upper bound for avatars stored in the table
+const MAX_AVATAR_BLOB_BYTES: usize = 256 * 1024;
+// Provider name of the identity links backing `User::slack_id`
+const SLACK_PROVIDER: &str = "slack";
+// Preferences are small flags and choices, not documents
+const MAX_PREFERENCE_KEY_LEN: usize = 128;
+const MAX_PREFERENCE_VALUE_BYTES: usize = 4 * 1024;
+
+#[spacetimedb(init)]
+pub fn init(ctx: spacetimedb::ReducerContext) {
+    let _ = Admin::insert(Admin {
+        identity: ctx.sender,
+    });
 }
 
 #[spacetimedb(reducer)]
 pub fn create_user(
-    _ctx: spacetimedb::ReducerContext,
+    ctx: spacetimedb::ReducerContext,
     name: String,
     email: Option<String>,
     avatar_url: Option<String>,
     slack_id: String,
-) -> () {
-    let user_id = generate_id();
-    let current_time = get_current_time();
+) {
+    upsert_slack_user(&ctx, "create_user", name, email, avatar_url, slack_id);
+}
 
-    let _ = User::insert(User {
-        id: user_id,
+// Entry point for Slack profile change webhooks, refreshes the profile fields in one
+// transaction and creates the user if it doesn't exist yet
+#[spacetimedb(reducer)]
+pub fn sync_slack_profile(
+    ctx: spacetimedb::ReducerContext,
+    slack_id: String,
+    name: String,
+    email: Option<String>,
+    avatar_url: Option<String>,
+) {
+    upsert_slack_user(
+        &ctx,
+        "sync_slack_profile",
         name,
         email,
         avatar_url,
         slack_id,
+    );
+}
+
+fn upsert_slack_user(
+    ctx: &spacetimedb::ReducerContext,
+    reducer: &str,
+    name: String,
+    email: Option<String>,
+    avatar_url: Option<String>,
+    slack_id: String,
+) {
+    if let Some(email) = &email {
+        if !is_valid_email(email) {
+            log::warn!(
+                "Rejecting {} for {}: invalid email {:?}",
+                reducer,
+                slack_id,
+                email
+            );
+            return;
+        }
+    }
+
+    // Reducers run in a single transaction, so concurrent logins for the same Slack user
+    // can't both take the insert path. Repeated calls refresh the profile instead.
+    if let Some(existing) = User::filter_by_slack_id(&slack_id) {
+        // Only the user or an admin, e.g. the webhook backend, may overwrite a profile
+        if !acts_for(ctx, &existing.id) {
+            log::warn!(
+                "Rejecting {} for {}: caller is neither the user nor an admin",
+                reducer,
+                slack_id
+            );
+            return;
+        }
+        let user_id = existing.id.clone();
+        // the cached image belongs to the old avatar url
+        let avatar_blob = if existing.avatar_url == avatar_url {
+            existing.avatar_blob.clone()
+        } else {
+            None
+        };
+        User::update_by_slack_id(
+            &slack_id,
+            User {
+                name,
+                email,
+                avatar_url,
+                avatar_blob,
+                ..existing
+            },
+        );
+        audit(ctx, "update_user", &user_id);
+        ensure_identity_link(ctx, &user_id, SLACK_PROVIDER, &slack_id);
+        return;
+    }
+
+    let user_id = generate_id();
+    let current_time = ctx.now();
+
+    if User::insert(User {
+        id: user_id.clone(),
+        name,
+        email,
+        avatar_url,
+        avatar_blob: None,
+        slack_id: slack_id.clone(),
         created_at: current_time,
-    });
+        status: UserStatus::Active,
+    })
+    .is_ok()
+    {
+        audit(ctx, "create_user", &user_id);
+        ensure_identity_link(ctx, &user_id, SLACK_PROVIDER, &slack_id);
+    }
+}
+
+#[spacetimedb(reducer)]
+pub fn link_identity(
+    ctx: spacetimedb::ReducerContext,
+    user_id: String,
+    provider: String,
+    provider_user_id: String,
+) {
+    if caller_user_id(&ctx).as_ref() != Some(&user_id) {
+        log::warn!(
+            "Rejecting link_identity for {}: caller is not the user",
+            user_id
+        );
+        return;
+    }
+    if !is_valid_provider(&provider) || provider_user_id.is_empty() {
+        log::warn!(
+            "Rejecting link_identity for {}: invalid provider {:?} or empty provider user id",
+            user_id,
+            provider
+        );
+        return;
+    }
+    if User::filter_by_id(&user_id).is_none() {
+        return;
+    }
+
+    let key = identity_link_key(&provider, &provider_user_id);
+    if let Some(existing) = IdentityLink::filter_by_key(&key) {
+        if existing.user_id != user_id {
+            log::warn!(
+                "Rejecting link_identity for {}: {} is already linked to another user",
+                user_id,
+                key
+            );
+        }
+        return;
+    }
+
+    ensure_identity_link(&ctx, &user_id, &provider, &provider_user_id);
+}
+
+// Only the user can link and unlink its identities. The last link can't be removed, it would
+// leave no way to sign in.
+#[spacetimedb(reducer)]
+pub fn unlink_identity(
+    ctx: spacetimedb::ReducerContext,
+    user_id: String,
+    provider: String,
+    provider_user_id: String,
+) {
+    if caller_user_id(&ctx).as_ref() != Some(&user_id) {
+        log::warn!(
+            "Rejecting unlink_identity for {}: caller is not the user",
+            user_id
+        );
+        return;
+    }
+
+    let key = identity_link_key(&provider, &provider_user_id);
+    match IdentityLink::filter_by_key(&key) {
+        Some(link) if link.user_id == user_id => {}
+        _ => return,
+    }
+    if IdentityLink::filter_by_user_id(&user_id).count() <= 1 {
+        log::warn!(
+            "Rejecting unlink_identity for {}: {} is the only linked identity",
+            user_id,
+            key
+        );
+        return;
+    }
+
+    if IdentityLink::delete_by_key(&key) {
+        audit(&ctx, "unlink_identity", &key);
+    }
+}
+
+// Creates the Slack links of users that signed up before identity links existed. Logins
+// backfill their own link as well, this covers users that haven't signed in since.
+#[spacetimedb(reducer)]
+pub fn backfill_slack_identity_links(ctx: spacetimedb::ReducerContext) {
+    if Admin::filter_by_identity(&ctx.sender).is_none() {
+        log::warn!("Rejecting backfill_slack_identity_links: caller is not an admin");
+        return;
+    }
+
+    for user in User::iter() {
+        ensure_identity_link(&ctx, &user.id, SLACK_PROVIDER, &user.slack_id);
+    }
+}
+
+fn ensure_identity_link(
+    ctx: &spacetimedb::ReducerContext,
+    user_id: &str,
+    provider: &str,
+    provider_user_id: &str,
+) {
+    let key = identity_link_key(provider, provider_user_id);
+    if IdentityLink::filter_by_key(&key).is_some() {
+        return;
+    }
+
+    if IdentityLink::insert(IdentityLink {
+        key: key.clone(),
+        user_id: user_id.to_string(),
+        provider: provider.to_string(),
+        provider_user_id: provider_user_id.to_string(),
+        linked_at: ctx.now(),
+    })
+    .is_ok()
+    {
+        audit(ctx, "link_identity", &key);
+    }
+}
+
+// Stores avatar bytes fetched from `avatar_url`, an empty payload clears the cached image
+#[spacetimedb(reducer)]
+pub fn set_user_avatar(ctx: spacetimedb::ReducerContext, user_id: String, avatar: Vec<u8>) {
+    if !acts_for(&ctx, &user_id) {
+        log::warn!(
+            "Rejecting avatar for user {}: caller is neither the user nor an admin",
+            user_id
+        );
+        return;
+    }
+    if avatar.len() > MAX_AVATAR_BLOB_BYTES {
+        log::warn!(
+            "Rejecting avatar for user {}: {} bytes exceeds the {} byte limit",
+            user_id,
+            avatar.len(),
+            MAX_AVATAR_BLOB_BYTES
+        );
+        return;
+    }
+
+    if let Some(user) = User::filter_by_id(&user_id) {
+        let avatar_blob = if avatar.is_empty() {
+            None
+        } else {
+            Some(avatar)
+        };
+        User::update_by_id(
+            &user_id,
+            User {
+                avatar_blob,
+                ..user
+            },
+        );
+        audit(&ctx, "set_user_avatar", &user_id);
+    }
+}
+
+// Issuances already recorded count against the new limit, shortening the window takes effect
+// with the next token
+#[spacetimedb(reducer)]
+pub fn set_token_rate_limit(ctx: spacetimedb::ReducerContext, max_tokens: u64, window_secs: u64) {
+    if Admin::filter_by_identity(&ctx.sender).is_none() {
+        log::warn!("Rejecting set_token_rate_limit: caller is not an admin");
+        return;
+    }
+    if !is_valid_rate_limit(max_tokens, window_secs) {
+        log::warn!(
+            "Rejecting set_token_rate_limit: {} tokens per {}s is not a usable limit",
+            max_tokens,
+            window_secs
+        );
+        return;
+    }
+
+    let limit = TokenRateLimit {
+        id: TOKEN_RATE_LIMIT_ID,
+        max_tokens,
+        window_secs,
+    };
+    if TokenRateLimit::filter_by_id(&TOKEN_RATE_LIMIT_ID).is_some() {
+        TokenRateLimit::update_by_id(&TOKEN_RATE_LIMIT_ID, limit);
+    } else if TokenRateLimit::insert(limit).is_err() {
+        return;
+    }
+    audit(
+        &ctx,
+        "set_token_rate_limit",
+        &format!("{}/{}s", max_tokens, window_secs),
+    );
+}
+
+// Suspending keeps the account and its data but blocks new tokens and fails verification
+// of existing ones.
+#[spacetimedb(reducer)]
+pub fn set_user_status(ctx: spacetimedb::ReducerContext, user_id: String, status: UserStatus) {
+    if Admin::filter_by_identity(&ctx.sender).is_none() {
+        log::warn!(
+            "Rejecting set_user_status for {}: caller is not an admin",
+            user_id
+        );
+        return;
+    }
+
+    if let Some(user) = User::filter_by_id(&user_id) {
+        User::update_by_id(&user_id, User { status, ..user });
+        let action = match status {
+            UserStatus::Active => "activate_user",
+            UserStatus::Suspended => "suspend_user",
+        };
+        audit(&ctx, action, &user_id);
+    }
 }
 
+// Called by the user on another device or by an admin, e.g. the backend completing a first login
 #[spacetimedb(reducer)]
-pub fn create_auth_token(_ctx: spacetimedb::ReducerContext, user_id: String) -> () {
+pub fn create_session(ctx: spacetimedb::ReducerContext, user_id: String, device_label: String) {
+    if !acts_for(&ctx, &user_id) {
+        log::warn!(
+            "Rejecting create_session for {}: caller is neither the user nor an admin",
+            user_id
+        );
+        return;
+    }
     if User::filter_by_id(&user_id).is_none() {
         return;
     }
 
-    let token = generate_id();
-    let current_time = get_current_time();
-    let expires_at = current_time + 30 * 24 * 60 * 60; // 30 days
+    let session_id = generate_id();
+    let current_time = ctx.now();
+
+    if Session::insert(Session {
+        session_id: session_id.clone(),
+        user_id,
+        device_label,
+        created_at: current_time,
+        last_active: current_time,
+    })
+    .is_ok()
+    {
+        audit(&ctx, "create_session", &session_id);
+    }
+}
+
+#[spacetimedb(reducer)]
+pub fn touch_session(ctx: spacetimedb::ReducerContext, session_id: String) {
+    if let Some(session) = Session::filter_by_session_id(&session_id) {
+        if !acts_for(&ctx, &session.user_id) {
+            log::warn!(
+                "Rejecting touch_session for {}: caller is neither the user nor an admin",
+                session_id
+            );
+            return;
+        }
+
+        Session::update_by_session_id(
+            &session_id,
+            Session {
+                last_active: ctx.now(),
+                ..session
+            },
+        );
+    }
+}
+
+#[spacetimedb(reducer)]
+pub fn end_session(ctx: spacetimedb::ReducerContext, session_id: String) {
+    let Some(session) = Session::filter_by_session_id(&session_id) else {
+        return;
+    };
+    if !acts_for(&ctx, &session.user_id) {
+        log::warn!(
+            "Rejecting end_session for {}: caller is neither the user nor an admin",
+            session_id
+        );
+        return;
+    }
+
+    let tokens: Vec<String> = AuthToken::filter_by_session_id(&session_id)
+        .map(|t| t.token_hash)
+        .collect();
+    for token_hash in tokens {
+        AuthToken::delete_by_token_hash(&token_hash);
+    }
+    unbind_session(&session_id);
+
+    if Session::delete_by_session_id(&session_id) {
+        audit(&ctx, "end_session", &session_id);
+    }
+}
+
+// Issues a token for a session, e.g. by the backend completing a login. Like one-time tokens,
+// the caller generates the token and only sends its `hash_secret`.
+#[spacetimedb(reducer)]
+pub fn create_auth_token(
+    ctx: spacetimedb::ReducerContext,
+    user_id: String,
+    session_id: String,
+    token_hash: String,
+    created_ip: Option<String>,
+    user_agent: Option<String>,
+) {
+    if !acts_for(&ctx, &user_id) {
+        log::warn!(
+            "Rejecting create_auth_token for {}: caller is neither the user nor an admin",
+            user_id
+        );
+        return;
+    }
+    if !is_secret_hash(&token_hash) {
+        log::warn!(
+            "Rejecting create_auth_token for {}: malformed token hash",
+            user_id
+        );
+        return;
+    }
+    match User::filter_by_id(&user_id) {
+        Some(user) if user.status == UserStatus::Active => {}
+        Some(_) => {
+            log::warn!("Refusing to issue a token for suspended user {}", user_id);
+            return;
+        }
+        None => return,
+    }
+    match Session::filter_by_session_id(&session_id) {
+        Some(session) if session.user_id == user_id => {}
+        _ => return,
+    }
+
+    let current_time = ctx.now();
+    if !check_token_rate_limit(&user_id, current_time) {
+        log::warn!(
+            "Rate limit exceeded for create_auth_token by user {}",
+            user_id
+        );
+        return;
+    }
+
+    // The token itself is a secret, the trail only records whom it was issued to
+    if issue_token(
+        &user_id,
+        token_hash,
+        session_id,
+        created_ip,
+        user_agent,
+        current_time,
+    ) {
+        audit(&ctx, "create_auth_token", &user_id);
+    }
+}
+
+// Registers a short-lived token for pairing a device, e.g. the CLI's device login. It isn't
+// tied to a session and stops working after its first successful verification. The signed-in
+// client generates the token and only sends its `hash_secret`.
+#[spacetimedb(reducer)]
+pub fn create_one_time_token(
+    ctx: spacetimedb::ReducerContext,
+    user_id: String,
+    token_hash: String,
+) {
+    if !acts_for(&ctx, &user_id) {
+        log::warn!(
+            "Rejecting create_one_time_token for {}: caller is neither the user nor an admin",
+            user_id
+        );
+        return;
+    }
+    if !is_secret_hash(&token_hash) {
+        log::warn!(
+            "Rejecting create_one_time_token for {}: malformed token hash",
+            user_id
+        );
+        return;
+    }
+    match User::filter_by_id(&user_id) {
+        Some(user) if user.status == UserStatus::Active => {}
+        Some(_) => {
+            log::warn!(
+                "Refusing to issue a one-time token for suspended user {}",
+                user_id
+            );
+            return;
+        }
+        None => return,
+    }
+
+    let current_time = ctx.now();
+    if !check_token_rate_limit(&user_id, current_time) {
+        log::warn!(
+            "Rate limit exceeded for create_one_time_token by user {}",
+            user_id
+        );
+        return;
+    }
+
+    if AuthToken::insert(AuthToken {
+        token_hash,
+        user_id: user_id.clone(),
+        session_id: String::new(),
+        created_at: current_time,
+        expires_at: one_time_token_expiry(current_time),
+        created_ip: None,
+        user_agent: None,
+        single_use: true,
+        rotated: false,
+    })
+    .is_ok()
+    {
+        audit(&ctx, "create_one_time_token", &user_id);
+    }
+}
+
+// One-time tokens are redeemed instead of rotated, verifying them again fails.
+// With `rotated_token_hash`, a valid token is replaced by a new one in the same session and
+// the old token only stays usable for a short grace window. The client generated the new
+// token, it only sends the `hash_secret` of it. Rotations count towards the token rate limit.
+#[spacetimedb(reducer)]
+pub fn verify_token(
+    ctx: spacetimedb::ReducerContext,
+    token: String,
+    rotated_token_hash: Option<String>,
+) {
+    let current_time = ctx.now();
+    if !is_token_valid(&token, &ctx) {
+        log::warn!("Token verification failed");
+        return;
+    }
+    if let Some(auth_token) = find_auth_token(&token) {
+        bind_identity(&ctx, &auth_token.user_id, &auth_token.session_id);
+        if auth_token.single_use {
+            AuthToken::delete_by_token_hash(&auth_token.token_hash);
+            audit(&ctx, "redeem_one_time_token", &auth_token.user_id);
+            return;
+        }
+    }
+    let Some(rotated_token_hash) = rotated_token_hash else {
+        return;
+    };
+    if !is_secret_hash(&rotated_token_hash) {
+        log::warn!("Rejecting token rotation: malformed token hash");
+        return;
+    }
+
+    if let Some(auth_token) = find_auth_token(&token) {
+        if !can_rotate(&auth_token) {
+            log::warn!("Refusing to rotate a token that was already rotated");
+            return;
+        }
+        let user_id = auth_token.user_id.clone();
+        let token_hash = auth_token.token_hash.clone();
+        if !check_token_rate_limit(&user_id, current_time) {
+            log::warn!(
+                "Rate limit exceeded for rotate_auth_token by user {}",
+                user_id
+            );
+            return;
+        }
+        // The rotated token belongs to the same device as the one it replaces
+        if !issue_token(
+            &user_id,
+            rotated_token_hash,
+            auth_token.session_id.clone(),
+            auth_token.created_ip.clone(),
+            auth_token.user_agent.clone(),
+            current_time,
+        ) {
+            return;
+        }
 
-    let _ = AuthToken::insert(AuthToken {
-        token,
+        let expires_at = rotated_token_expiry(auth_token.expires_at, current_time);
+        AuthToken::update_by_token_hash(
+            &token_hash,
+            AuthToken {
+                expires_at,
+                rotated: true,
+                ..auth_token
+            },
+        );
+        audit(&ctx, "rotate_auth_token", &user_id);
+    }
+}
+
+// Remembers that the caller acts for `user_id` as long as `session_id` lasts, a later token
+// moves the identity over
+fn bind_identity(ctx: &spacetimedb::ReducerContext, user_id: &str, session_id: &str) {
+    let binding = UserIdentity {
+        identity: ctx.sender,
+        user_id: user_id.to_string(),
+        session_id: session_id.to_string(),
+        linked_at: ctx.now(),
+    };
+    if UserIdentity::filter_by_identity(&ctx.sender).is_some() {
+        UserIdentity::update_by_identity(&ctx.sender, binding);
+    } else {
+        let _ = UserIdentity::insert(binding);
+    }
+}
+
+// Identities lose their binding together with the session of the token they verified
+fn unbind_session(session_id: &String) {
+    let identities: Vec<Identity> = UserIdentity::filter_by_session_id(session_id)
+        .map(|binding| binding.identity)
+        .collect();
+    for identity in identities {
+        UserIdentity::delete_by_identity(&identity);
+    }
+}
+
+// The active user the caller verified a token of, see `bind_identity`
+fn caller_user_id(ctx: &spacetimedb::ReducerContext) -> Option<String> {
+    UserIdentity::filter_by_identity(&ctx.sender)
+        .filter(|binding| is_binding_active(binding, ctx))
+        .and_then(|binding| User::filter_by_id(&binding.user_id))
+        .filter(|user| user.status == UserStatus::Active)
+        .map(|user| user.id)
+}
+
+// A binding lasts as long as its session. Redeeming a one-time token creates no session, that
+// binding only lasts long enough for the paired device to create its own.
+fn is_binding_active(binding: &UserIdentity, clock: &impl Clock) -> bool {
+    if binding.session_id.is_empty() {
+        return !is_expired(one_time_token_expiry(binding.linked_at), clock);
+    }
+
+    Session::filter_by_session_id(&binding.session_id).is_some()
+}
+
+// Whether the caller may manage `user_id`'s account: it verified one of the user's tokens or is
+// an admin
+fn acts_for(ctx: &spacetimedb::ReducerContext, user_id: &str) -> bool {
+    caller_user_id(ctx).is_some_and(|caller| caller == user_id)
+        || Admin::filter_by_identity(&ctx.sender).is_some()
+}
+
+// Users can only change their own preferences, the caller has to have verified one of the
+// user's tokens first
+#[spacetimedb(reducer)]
+pub fn set_user_preference(
+    ctx: spacetimedb::ReducerContext,
+    user_id: String,
+    key: String,
+    value: String,
+) {
+    if caller_user_id(&ctx).as_ref() != Some(&user_id) {
+        log::warn!(
+            "Rejecting set_user_preference for {}: caller is not the user",
+            user_id
+        );
+        return;
+    }
+    if !is_valid_preference(&key, &value) {
+        log::warn!(
+            "Rejecting set_user_preference for {}: invalid key {:?} or value",
+            user_id,
+            key
+        );
+        return;
+    }
+
+    let id = user_preference_id(&user_id, &key);
+    let preference = UserPreference {
+        id: id.clone(),
         user_id,
+        key,
+        value,
+        updated_at: ctx.now(),
+    };
+    if UserPreference::filter_by_id(&id).is_some() {
+        UserPreference::update_by_id(&id, preference);
+    } else if UserPreference::insert(preference).is_err() {
+        return;
+    }
+    audit(&ctx, "set_user_preference", &id);
+}
+
+// Removes the preference so clients fall back to their default
+#[spacetimedb(reducer)]
+pub fn delete_user_preference(ctx: spacetimedb::ReducerContext, user_id: String, key: String) {
+    if caller_user_id(&ctx).as_ref() != Some(&user_id) {
+        log::warn!(
+            "Rejecting delete_user_preference for {}: caller is not the user",
+            user_id
+        );
+        return;
+    }
+
+    let id = user_preference_id(&user_id, &key);
+    if UserPreference::delete_by_id(&id) {
+        audit(&ctx, "delete_user_preference", &id);
+    }
+}
+
+// The client generates the key with `generate_api_key`, shows it to the user once and only
+// registers its hash
+#[spacetimedb(reducer)]
+pub fn create_api_key(
+    ctx: spacetimedb::ReducerContext,
+    user_id: String,
+    label: String,
+    key_hash: String,
+) {
+    if !acts_for(&ctx, &user_id) {
+        log::warn!(
+            "Rejecting create_api_key for {}: caller is neither the user nor an admin",
+            user_id
+        );
+        return;
+    }
+    if !is_secret_hash(&key_hash) {
+        log::warn!(
+            "Rejecting create_api_key for {}: malformed key hash",
+            user_id
+        );
+        return;
+    }
+    let label = label.trim().to_string();
+    if label.is_empty() {
+        log::warn!("Refusing to create an API key without a label");
+        return;
+    }
+    match User::filter_by_id(&user_id) {
+        Some(user) if user.status == UserStatus::Active => {}
+        Some(_) => {
+            log::warn!(
+                "Refusing to create an API key for suspended user {}",
+                user_id
+            );
+            return;
+        }
+        None => return,
+    }
+
+    if ApiKey::insert(ApiKey {
+        key_hash,
+        user_id: user_id.clone(),
+        label,
+        created_at: ctx.now(),
+        last_used: None,
+        revoked: false,
+    })
+    .is_ok()
+    {
+        audit(&ctx, "create_api_key", &user_id);
+    }
+}
+
+// Keys are revoked by their hash, which is what owners see when listing them
+#[spacetimedb(reducer)]
+pub fn revoke_api_key(ctx: spacetimedb::ReducerContext, key_hash: String) {
+    if let Some(api_key) = ApiKey::filter_by_key_hash(&key_hash) {
+        if api_key.revoked {
+            return;
+        }
+        if !acts_for(&ctx, &api_key.user_id) {
+            log::warn!(
+                "Rejecting revoke_api_key for {}: caller is neither the owner nor an admin",
+                api_key.user_id
+            );
+            return;
+        }
+
+        let user_id = api_key.user_id.clone();
+        ApiKey::update_by_key_hash(
+            &key_hash,
+            ApiKey {
+                revoked: true,
+                ..api_key
+            },
+        );
+        audit(&ctx, "revoke_api_key", &user_id);
+    }
+}
+
+// Records the use of a valid key, so owners can spot keys nobody uses anymore
+#[spacetimedb(reducer)]
+pub fn verify_api_key(ctx: spacetimedb::ReducerContext, key: String) {
+    let key_hash = hash_secret(&key);
+    let Some(api_key) = ApiKey::filter_by_key_hash(&key_hash) else {
+        log::warn!("API key verification failed");
+        return;
+    };
+    let user = User::filter_by_id(&api_key.user_id);
+    if !is_api_key_usable(&api_key, user.as_ref()) {
+        log::warn!("API key verification failed");
+        return;
+    }
+
+    ApiKey::update_by_key_hash(
+        &key_hash,
+        ApiKey {
+            last_used: Some(ctx.now()),
+            ..api_key
+        },
+    );
+}
+
+fn issue_token(
+    user_id: &str,
+    token_hash: String,
+    session_id: String,
+    created_ip: Option<String>,
+    user_agent: Option<String>,
+    current_time: u64,
+) -> bool {
+    AuthToken::insert(AuthToken {
+        token_hash,
+        user_id: user_id.to_string(),
+        session_id,
         created_at: current_time,
-        expires_at,
+        expires_at: token_expiry(current_time),
+        created_ip,
+        user_agent,
+        single_use: false,
+        rotated: false,
+    })
+    .is_ok()
+}
+
+// Tokens issued to a user since `since`, newest first, for a security dashboard
+#[spacetimedb(reducer)]
+pub fn recent_logins(ctx: spacetimedb::ReducerContext, user_id: String, since: u64) {
+    if !acts_for(&ctx, &user_id) {
+        log::warn!(
+            "Rejecting recent_logins for {}: caller is neither the user nor an admin",
+            user_id
+        );
+        return;
+    }
+
+    let logins = select_recent_logins(AuthToken::filter_by_user_id(&user_id), since);
+    publish_query_result(&ctx, "recent_logins", token_rows(logins));
+}
+
+// Indexed lookup backed by the unique `slack_id` column. The result holds at most one user.
+#[spacetimedb(reducer)]
+pub fn get_user_by_slack_id(ctx: spacetimedb::ReducerContext, slack_id: String) {
+    let users = User::filter_by_slack_id(&slack_id).into_iter().collect();
+    publish_query_result(&ctx, "get_user_by_slack_id", QueryRows::Users(users));
+}
+
+fn select_recent_logins(tokens: impl Iterator<Item = AuthToken>, since: u64) -> Vec<AuthToken> {
+    let mut logins: Vec<AuthToken> = tokens.filter(|t| t.created_at >= since).collect();
+    logins.sort_by_key(|t| std::cmp::Reverse(t.created_at));
+    logins
+}
+
+// Resolves a sign-in through any linked provider. The result holds at most one user.
+#[spacetimedb(reducer)]
+pub fn get_user_by_identity(
+    ctx: spacetimedb::ReducerContext,
+    provider: String,
+    provider_user_id: String,
+) {
+    let users = IdentityLink::filter_by_key(&identity_link_key(&provider, &provider_user_id))
+        .and_then(|link| User::filter_by_id(&link.user_id))
+        .into_iter()
+        .collect();
+    publish_query_result(&ctx, "get_user_by_identity", QueryRows::Users(users));
+}
+
+// Unexpired tokens of a user, newest first, for reviewing and revoking sessions
+#[spacetimedb(reducer)]
+pub fn active_tokens(ctx: spacetimedb::ReducerContext, user_id: String) {
+    if !acts_for(&ctx, &user_id) {
+        log::warn!(
+            "Rejecting active_tokens for {}: caller is neither the user nor an admin",
+            user_id
+        );
+        return;
+    }
+
+    let tokens = select_active_tokens(AuthToken::filter_by_user_id(&user_id), &ctx);
+    publish_query_result(&ctx, "active_tokens", token_rows(tokens));
+}
+
+// Unexpired tokens of a user that expire within `within_secs`, soonest first, so the app can
+// prompt for a re-login before the session ends
+#[spacetimedb(reducer)]
+pub fn expiring_tokens(ctx: spacetimedb::ReducerContext, user_id: String, within_secs: u64) {
+    if !acts_for(&ctx, &user_id) {
+        log::warn!(
+            "Rejecting expiring_tokens for {}: caller is neither the user nor an admin",
+            user_id
+        );
+        return;
+    }
+
+    let tokens = select_expiring_tokens(AuthToken::filter_by_user_id(&user_id), within_secs, &ctx);
+    publish_query_result(&ctx, "expiring_tokens", token_rows(tokens));
+}
+
+fn select_active_tokens(
+    tokens: impl Iterator<Item = AuthToken>,
+    clock: &impl Clock,
+) -> Vec<AuthToken> {
+    let mut tokens: Vec<AuthToken> = tokens
+        .filter(|t| !is_expired(t.expires_at, clock))
+        .collect();
+    tokens.sort_by_key(|t| std::cmp::Reverse(t.created_at));
+    tokens
+}
+
+fn select_expiring_tokens(
+    tokens: impl Iterator<Item = AuthToken>,
+    within_secs: u64,
+    clock: &impl Clock,
+) -> Vec<AuthToken> {
+    let mut tokens: Vec<AuthToken> = tokens
+        .filter(|t| expires_within(t.expires_at, within_secs, clock))
+        .collect();
+    tokens.sort_by_key(|t| t.expires_at);
+    tokens
+}
+
+// The device label of each token is on its session, single-use tokens have none
+fn token_rows(tokens: Vec<AuthToken>) -> QueryRows {
+    QueryRows::Tokens(
+        tokens
+            .into_iter()
+            .map(|t| TokenSummary {
+                device_label: Session::filter_by_session_id(&t.session_id)
+                    .map(|session| session.device_label)
+                    .unwrap_or_default(),
+                session_id: t.session_id,
+                created_at: t.created_at,
+                expires_at: t.expires_at,
+                created_ip: t.created_ip,
+                user_agent: t.user_agent,
+                single_use: t.single_use,
+            })
+            .collect(),
+    )
+}
+
+// Replaces the caller's previous result of `query`, see `QueryResult`
+fn publish_query_result(ctx: &spacetimedb::ReducerContext, query: &str, rows: QueryRows) {
+    let previous: Vec<u64> = QueryResult::filter_by_caller(&ctx.sender)
+        .filter(|result| result.query == query)
+        .map(|result| result.id)
+        .collect();
+    for id in previous {
+        QueryResult::delete_by_id(&id);
+    }
+
+    let _ = QueryResult::insert(QueryResult {
+        id: 0, // assigned by autoinc
+        caller: ctx.sender,
+        query: query.to_string(),
+        rows,
+        updated_at: ctx.now(),
     });
 }
 
+// Preferences of a user ordered by key. Other devices' changes also arrive through a
+// subscription to the user's UserPreference rows.
 #[spacetimedb(reducer)]
-pub fn verify_token(_ctx: spacetimedb::ReducerContext, _token: String) -> () {
+pub fn get_user_preferences(ctx: spacetimedb::ReducerContext, user_id: String) {
+    if !acts_for(&ctx, &user_id) {
+        log::warn!(
+            "Rejecting get_user_preferences for {}: caller is neither the user nor an admin",
+            user_id
+        );
+        return;
+    }
+
+    let preferences = sort_preferences(UserPreference::filter_by_user_id(&user_id).collect());
+    publish_query_result(
+        &ctx,
+        "get_user_preferences",
+        QueryRows::Preferences(preferences),
+    );
+}
+
+fn sort_preferences(mut preferences: Vec<UserPreference>) -> Vec<UserPreference> {
+    preferences.sort_by(|a, b| a.key.cmp(&b.key));
+    preferences
+}
+
+// API keys of a user including revoked ones, newest first
+#[spacetimedb(reducer)]
+pub fn list_api_keys(ctx: spacetimedb::ReducerContext, user_id: String) {
+    if !acts_for(&ctx, &user_id) {
+        log::warn!(
+            "Rejecting list_api_keys for {}: caller is neither the user nor an admin",
+            user_id
+        );
+        return;
+    }
+
+    let keys = sort_api_keys(ApiKey::filter_by_user_id(&user_id).collect());
+    publish_query_result(&ctx, "list_api_keys", QueryRows::ApiKeys(keys));
+}
+
+fn sort_api_keys(mut keys: Vec<ApiKey>) -> Vec<ApiKey> {
+    keys.sort_by_key(|k| std::cmp::Reverse(k.created_at));
+    keys
+}
+
+// A key is usable until it's revoked, as long as its user is active
+fn is_api_key_usable(api_key: &ApiKey, user: Option<&User>) -> bool {
+    !api_key.revoked
+        && user.is_some_and(|user| user.id == api_key.user_id && user.status == UserStatus::Active)
+}
+
+// Tokens are only stored as their `hash_secret`
+fn find_auth_token(token: &str) -> Option<AuthToken> {
+    AuthToken::filter_by_token_hash(&hash_secret(token))
+}
+
+// A token is valid while it hasn't expired and its user is active
+pub fn is_token_valid(token: &str, clock: &impl Clock) -> bool {
+    match find_auth_token(token) {
+        Some(auth_token) if !is_expired(auth_token.expires_at, clock) => {
+            User::filter_by_id(&auth_token.user_id)
+                .map(|user| user.status == UserStatus::Active)
+                .unwrap_or(false)
+        }
+        _ => false,
+    }
+}
+
+// The configured (max tokens, window in seconds), or the defaults
+fn token_rate_limit() -> (u64, u64) {
+    TokenRateLimit::filter_by_id(&TOKEN_RATE_LIMIT_ID)
+        .map(|limit| (limit.max_tokens, limit.window_secs))
+        .unwrap_or((
+            DEFAULT_MAX_TOKENS_PER_WINDOW,
+            DEFAULT_TOKEN_RATE_LIMIT_WINDOW_SECS,
+        ))
+}
+
+// A zero limit would lock every user out and a zero window disables the limit
+fn is_valid_rate_limit(max_tokens: u64, window_secs: u64) -> bool {
+    max_tokens > 0 && window_secs > 0
+}
+
+// Prunes stale issuances and records a new one if the user is still below the limit
+fn check_token_rate_limit(user_id: &String, current_time: u64) -> bool {
+    let (max_tokens, window_secs) = token_rate_limit();
+    let window_start = current_time.saturating_sub(window_secs);
+    let mut recent = 0;
+    let mut stale = vec![];
+    for issuance in TokenIssuance::filter_by_user_id(user_id) {
+        if issuance.issued_at < window_start {
+            stale.push(issuance.id);
+        } else {
+            recent += 1;
+        }
+    }
+    for id in stale {
+        TokenIssuance::delete_by_id(&id);
+    }
+
+    if recent >= max_tokens {
+        return false;
+    }
+
+    let _ = TokenIssuance::insert(TokenIssuance {
+        id: 0, // assigned by autoinc
+        user_id: user_id.clone(),
+        issued_at: current_time,
+    });
+    true
+}
+
+fn audit(ctx: &spacetimedb::ReducerContext, action: &str, target: &str) {
+    let _ = AuditLog::insert(AuditLog {
+        id: 0, // assigned by autoinc
+        actor: ctx.sender,
+        action: action.to_string(),
+        target: target.to_string(),
+        timestamp: ctx.now(),
+    });
+}
+
+// Deliberately lightweight: a single `@`, a non-empty local part and a dotted domain
+// without empty labels or whitespace.
+fn is_valid_email(email: &str) -> bool {
+    if email.len() > 254 || email.chars().any(char::is_whitespace) {
+        return false;
+    }
+
+    match email.split_once('@') {
+        Some((local, domain)) => {
+            !local.is_empty()
+                && !domain.contains('@')
+                && domain.contains('.')
+                && domain.split('.').all(|label| !label.is_empty())
+        }
+        None => false,
+    }
+}
+
+// Provider names can't contain the key separator, so keys are never ambiguous
+fn is_valid_provider(provider: &str) -> bool {
+    !provider.is_empty()
+        && provider
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+}
+
+fn is_valid_preference(key: &str, value: &str) -> bool {
+    !key.trim().is_empty()
+        && key.len() <= MAX_PREFERENCE_KEY_LEN
+        && value.len() <= MAX_PREFERENCE_VALUE_BYTES
+}
+
+// User ids are hex, so the first `:` always ends the user id
+fn user_preference_id(user_id: &str, key: &str) -> String {
+    format!("{}:{}", user_id, key)
+}
+
+fn identity_link_key(provider: &str, provider_user_id: &str) -> String {
+    format!("{}:{}", provider, provider_user_id)
 }
 
 fn generate_id() -> String {
@@ -77,9 +1156,318 @@ fn generate_id() -> String {
         .collect::<String>()
 }
 
-fn get_current_time() -> u64 {
-    std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_secs()
+// Prefixed so keys are told apart from session tokens, e.g. by secret scanners
+pub fn generate_api_key() -> String {
+    format!("kled_{}", generate_id())
+}
+
+// Hex encoded Keccak-256 of a secret, for storing secrets in tables every client can read
+pub fn hash_secret(secret: &str) -> String {
+    spacetimedb::sats::hash::hash_bytes(secret)
+        .to_hex()
+        .to_string()
+}
+
+fn is_secret_hash(hash: &str) -> bool {
+    hash.len() == 64
+        && hash
+            .chars()
+            .all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c))
+}
+
+// Source of the current time in seconds since the Unix epoch. Reducers read it from their
+// context so every row written in a transaction agrees, tests supply a fixed clock.
+pub trait Clock {
+    fn now(&self) -> u64;
+}
+
+impl Clock for spacetimedb::ReducerContext {
+    fn now(&self) -> u64 {
+        self.timestamp.into_micros_since_epoch() / 1_000_000
+    }
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+    }
+}
+
+fn token_expiry(issued_at: u64) -> u64 {
+    issued_at + TOKEN_LIFETIME_SECS
+}
+
+fn one_time_token_expiry(issued_at: u64) -> u64 {
+    issued_at + ONE_TIME_TOKEN_LIFETIME_SECS
+}
+
+// Each token is replaced at most once, otherwise a leaked token could be rotated into new
+// tokens forever during its grace window
+fn can_rotate(auth_token: &AuthToken) -> bool {
+    !auth_token.single_use && !auth_token.rotated
+}
+
+// A rotated-out token never lives longer than it would have without rotation
+fn rotated_token_expiry(expires_at: u64, current_time: u64) -> u64 {
+    expires_at.min(current_time + ROTATED_TOKEN_GRACE_SECS)
+}
+
+fn is_expired(expires_at: u64, clock: &impl Clock) -> bool {
+    expires_at <= clock.now()
+}
+
+fn expires_within(expires_at: u64, within_secs: u64, clock: &impl Clock) -> bool {
+    !is_expired(expires_at, clock) && expires_at <= clock.now().saturating_add(within_secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FixedClock(u64);
+
+    impl Clock for FixedClock {
+        fn now(&self) -> u64 {
+            self.0
+        }
+    }
+
+    #[test]
+    fn token_expires_after_its_lifetime() {
+        let expires_at = token_expiry(1_000);
+
+        assert!(!is_expired(
+            expires_at,
+            &FixedClock(1_000 + TOKEN_LIFETIME_SECS - 1)
+        ));
+        assert!(is_expired(
+            expires_at,
+            &FixedClock(1_000 + TOKEN_LIFETIME_SECS)
+        ));
+    }
+
+    #[test]
+    fn rotated_token_keeps_a_grace_window() {
+        let expires_at = rotated_token_expiry(token_expiry(1_000), 2_000);
+
+        assert_eq!(expires_at, 2_000 + ROTATED_TOKEN_GRACE_SECS);
+        assert!(!is_expired(expires_at, &FixedClock(2_000)));
+        assert!(is_expired(
+            expires_at,
+            &FixedClock(2_000 + ROTATED_TOKEN_GRACE_SECS)
+        ));
+    }
+
+    #[test]
+    fn expiring_soon_excludes_expired_and_distant_tokens() {
+        let clock = FixedClock(1_000);
+
+        assert!(expires_within(1_100, 100, &clock));
+        assert!(!expires_within(1_101, 100, &clock));
+        assert!(!expires_within(1_000, 100, &clock));
+    }
+
+    #[test]
+    fn one_time_token_expires_long_before_regular_tokens() {
+        let expires_at = one_time_token_expiry(1_000);
+
+        assert!(!is_expired(
+            expires_at,
+            &FixedClock(1_000 + ONE_TIME_TOKEN_LIFETIME_SECS - 1)
+        ));
+        assert!(is_expired(
+            expires_at,
+            &FixedClock(1_000 + ONE_TIME_TOKEN_LIFETIME_SECS)
+        ));
+        assert!(expires_at < token_expiry(1_000));
+    }
+
+    #[test]
+    fn secret_hashes_are_recognized() {
+        let hash = hash_secret("device-code");
+
+        assert!(is_secret_hash(&hash));
+        assert_ne!(hash, hash_secret("device-codf"));
+        assert!(!is_secret_hash("device-code"));
+        assert!(!is_secret_hash(&hash.to_uppercase()));
+    }
+
+    fn token(created_at: u64, expires_at: u64) -> AuthToken {
+        AuthToken {
+            token_hash: hash_secret(&generate_id()),
+            user_id: "user".to_string(),
+            session_id: "session".to_string(),
+            created_at,
+            expires_at,
+            created_ip: None,
+            user_agent: None,
+            single_use: false,
+            rotated: false,
+        }
+    }
+
+    #[test]
+    fn active_tokens_are_unexpired_and_newest_first() {
+        let tokens = vec![token(100, 2_000), token(300, 900), token(200, 3_000)];
+
+        let active = select_active_tokens(tokens.into_iter(), &FixedClock(1_000));
+
+        let created: Vec<u64> = active.iter().map(|t| t.created_at).collect();
+        assert_eq!(created, vec![200, 100]);
+    }
+
+    #[test]
+    fn recent_logins_start_at_since_and_are_newest_first() {
+        let tokens = vec![token(100, 2_000), token(300, 900), token(200, 3_000)];
+
+        let logins = select_recent_logins(tokens.into_iter(), 200);
+
+        let created: Vec<u64> = logins.iter().map(|t| t.created_at).collect();
+        assert_eq!(created, vec![300, 200]);
+    }
+
+    #[test]
+    fn expiring_tokens_are_soonest_first() {
+        let tokens = vec![
+            token(100, 1_090),
+            token(200, 1_050),
+            token(300, 5_000),
+            token(400, 900),
+        ];
+
+        let expiring = select_expiring_tokens(tokens.into_iter(), 100, &FixedClock(1_000));
+
+        let expires: Vec<u64> = expiring.iter().map(|t| t.expires_at).collect();
+        assert_eq!(expires, vec![1_050, 1_090]);
+    }
+
+    #[test]
+    fn tokens_are_rotated_at_most_once() {
+        let fresh = token(100, token_expiry(100));
+        assert!(can_rotate(&fresh));
+
+        let rotated = AuthToken {
+            rotated: true,
+            ..token(100, token_expiry(100))
+        };
+        assert!(!can_rotate(&rotated));
+    }
+
+    #[test]
+    fn rotation_never_extends_a_token() {
+        assert_eq!(rotated_token_expiry(2_010, 2_000), 2_010);
+    }
+
+    #[test]
+    fn api_keys_stop_working_when_revoked_or_suspended() {
+        let user = User {
+            id: "user".to_string(),
+            name: "User".to_string(),
+            email: None,
+            avatar_url: None,
+            avatar_blob: None,
+            slack_id: "U1".to_string(),
+            created_at: 0,
+            status: UserStatus::Active,
+        };
+        let api_key = ApiKey {
+            key_hash: hash_secret(&generate_api_key()),
+            user_id: user.id.clone(),
+            label: "ci".to_string(),
+            created_at: 0,
+            last_used: None,
+            revoked: false,
+        };
+
+        assert!(is_api_key_usable(&api_key, Some(&user)));
+        assert!(!is_api_key_usable(&api_key, None));
+        assert!(!is_api_key_usable(
+            &ApiKey {
+                key_hash: api_key.key_hash.clone(),
+                user_id: api_key.user_id.clone(),
+                label: api_key.label.clone(),
+                created_at: api_key.created_at,
+                last_used: api_key.last_used,
+                revoked: true,
+            },
+            Some(&user)
+        ));
+        assert!(!is_api_key_usable(
+            &api_key,
+            Some(&User {
+                status: UserStatus::Suspended,
+                ..user
+            })
+        ));
+    }
+
+    #[test]
+    fn api_keys_are_listed_newest_first() {
+        let api_key = |created_at: u64| ApiKey {
+            key_hash: hash_secret(&generate_api_key()),
+            user_id: "user".to_string(),
+            label: "ci".to_string(),
+            created_at,
+            last_used: None,
+            revoked: false,
+        };
+
+        let keys = sort_api_keys(vec![api_key(100), api_key(300), api_key(200)]);
+
+        let created: Vec<u64> = keys.iter().map(|k| k.created_at).collect();
+        assert_eq!(created, vec![300, 200, 100]);
+    }
+
+    #[test]
+    fn preferences_stay_small() {
+        assert!(is_valid_preference("notifications.email", "true"));
+        assert!(!is_valid_preference(" ", "true"));
+        assert!(!is_valid_preference(
+            &"k".repeat(MAX_PREFERENCE_KEY_LEN + 1),
+            "true"
+        ));
+        assert!(!is_valid_preference(
+            "theme",
+            &"v".repeat(MAX_PREFERENCE_VALUE_BYTES + 1)
+        ));
+    }
+
+    #[test]
+    fn preferences_are_ordered_by_key() {
+        let preference = |key: &str| UserPreference {
+            id: user_preference_id("user", key),
+            user_id: "user".to_string(),
+            key: key.to_string(),
+            value: "true".to_string(),
+            updated_at: 0,
+        };
+
+        let preferences = sort_preferences(vec![preference("theme"), preference("editor")]);
+
+        let keys: Vec<&str> = preferences.iter().map(|p| p.key.as_str()).collect();
+        assert_eq!(keys, vec!["editor", "theme"]);
+    }
+
+    #[test]
+    fn rate_limits_must_allow_tokens_within_a_window() {
+        assert!(is_valid_rate_limit(
+            DEFAULT_MAX_TOKENS_PER_WINDOW,
+            DEFAULT_TOKEN_RATE_LIMIT_WINDOW_SECS
+        ));
+        assert!(!is_valid_rate_limit(0, 60));
+        assert!(!is_valid_rate_limit(5, 0));
+    }
+
+    #[test]
+    fn provider_names_cannot_contain_the_key_separator() {
+        assert!(is_valid_provider(SLACK_PROVIDER));
+        assert!(is_valid_provider("google-workspace"));
+        assert!(!is_valid_provider("slack:team"));
+        assert!(!is_valid_provider(""));
+    }
 }
@@ -0,0 +1,201 @@
+// The table macros generate field assertion helpers that are never called, the allow is kept
+// to this module so dead code elsewhere is still reported
+#![allow(dead_code)]
+
+// Incompatible changes to these tables need a bump of `DATA_SCHEMA_VERSION` in
+// desktop/src-tauri/src/spacetime_server.rs, which guards importing backups of the data.
+//
+// Tables named with a leading `_` are private, clients can't subscribe to them. Credentials and
+// the sessions they belong to are kept there and only reach their owner through query
+// reducers, see `QueryResult`.
+
+use serde::{Deserialize, Serialize};
+use spacetimedb::{spacetimedb, Identity, SpacetimeType};
+
+#[spacetimedb(table)]
+#[derive(Serialize, Deserialize)]
+pub struct User {
+    #[primarykey]
+    pub id: String,
+    pub name: String,
+    pub email: Option<String>,
+    pub avatar_url: Option<String>,
+    // Cached avatar image, clients prefer it and fall back to `avatar_url`
+    pub avatar_blob: Option<Vec<u8>>,
+    #[unique]
+    pub slack_id: String,
+    pub created_at: u64,
+    pub status: UserStatus,
+}
+
+#[derive(SpacetimeType, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum UserStatus {
+    Active,
+    Suspended,
+}
+
+// Identities allowed to run administrative reducers. The module publisher is seeded in `init`.
+#[spacetimedb(table)]
+pub struct Admin {
+    #[primarykey]
+    pub identity: Identity,
+}
+
+#[spacetimedb(table)]
+#[derive(Serialize, Deserialize)]
+#[sats(name = "_AuthToken")]
+pub struct AuthToken {
+    // The `hash_secret` of the token, the token itself never reaches the server
+    #[primarykey]
+    pub token_hash: String,
+    pub user_id: String,
+    pub session_id: String,
+    pub created_at: u64,
+    pub expires_at: u64,
+    // Client-reported context of the login that created the token
+    pub created_ip: Option<String>,
+    pub user_agent: Option<String>,
+    // Deleted by the first successful verification, see `create_one_time_token`
+    pub single_use: bool,
+    // Replaced by a newer token and only valid for the grace window, see `verify_token`
+    pub rotated: bool,
+}
+
+// Credential for programmatic access such as CI integrations. Unlike AuthToken it isn't tied
+// to a session and doesn't expire, it works until revoked. Revoked keys are kept so the
+// owner can still see what existed. Only the `hash_secret` of the key is stored, the key itself
+// never reaches the server.
+#[spacetimedb(table)]
+#[derive(Serialize, Deserialize)]
+pub struct ApiKey {
+    #[primarykey]
+    pub key_hash: String,
+    pub user_id: String,
+    pub label: String,
+    pub created_at: u64,
+    pub last_used: Option<u64>,
+    pub revoked: bool,
+}
+
+// Client identities that proved they act for a user by verifying one of the user's tokens.
+// Reducers resolve the calling user from `ctx.sender` through it.
+#[spacetimedb(table)]
+#[sats(name = "_UserIdentity")]
+pub struct UserIdentity {
+    #[primarykey]
+    pub identity: Identity,
+    pub user_id: String,
+    // Session of the verified token, ending it removes the binding. Empty for one-time tokens.
+    pub session_id: String,
+    pub linked_at: u64,
+}
+
+// A setting that follows the user across devices. `id` combines user id and key, so each user
+// has at most one value per key.
+#[spacetimedb(table)]
+#[derive(Serialize, Deserialize)]
+pub struct UserPreference {
+    #[primarykey]
+    pub id: String,
+    pub user_id: String,
+    pub key: String,
+    pub value: String,
+    pub updated_at: u64,
+}
+
+// Recent token issuances per user, kept separately from AuthToken so ending a
+// session doesn't reset the rate limit. Entries outside the window are pruned.
+#[spacetimedb(table)]
+#[derive(Serialize, Deserialize)]
+pub struct TokenIssuance {
+    #[primarykey]
+    #[autoinc]
+    pub id: u64,
+    pub user_id: String,
+    pub issued_at: u64,
+}
+
+// Token rate limit set by an admin, a single row with id 0. Without it the built-in defaults
+// apply.
+#[spacetimedb(table)]
+#[derive(Serialize, Deserialize)]
+pub struct TokenRateLimit {
+    #[primarykey]
+    pub id: u32,
+    pub max_tokens: u64,
+    pub window_secs: u64,
+}
+
+// A signed-in device. Tokens are the credentials issued for a session, ending the
+// session revokes all of them.
+#[spacetimedb(table)]
+#[derive(Serialize, Deserialize)]
+#[sats(name = "_Session")]
+pub struct Session {
+    #[primarykey]
+    pub session_id: String,
+    pub user_id: String,
+    pub device_label: String,
+    pub created_at: u64,
+    pub last_active: u64,
+}
+
+// An account at an identity provider a user can sign in with. `key` combines provider and
+// provider user id, so each external account belongs to at most one user.
+#[spacetimedb(table)]
+#[derive(Serialize, Deserialize)]
+pub struct IdentityLink {
+    #[primarykey]
+    pub key: String,
+    pub user_id: String,
+    pub provider: String,
+    pub provider_user_id: String,
+    pub linked_at: u64,
+}
+
+// Append-only trail of mutating reducer calls, only ever written through `audit`
+#[spacetimedb(table)]
+pub struct AuditLog {
+    #[primarykey]
+    #[autoinc]
+    pub id: u64,
+    pub actor: Identity,
+    pub action: String,
+    pub target: String,
+    pub timestamp: u64,
+}
+
+// A token as its owner sees it in query results, without the token itself
+#[derive(SpacetimeType)]
+pub struct TokenSummary {
+    pub session_id: String,
+    pub device_label: String,
+    pub created_at: u64,
+    pub expires_at: u64,
+    pub created_ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub single_use: bool,
+}
+
+#[derive(SpacetimeType)]
+pub enum QueryRows {
+    Tokens(Vec<TokenSummary>),
+    Users(Vec<User>),
+    Preferences(Vec<UserPreference>),
+    ApiKeys(Vec<ApiKey>),
+}
+
+// Rows found by the caller's last call of a query reducer such as `active_tokens`. Reducers
+// can't return values, so callers subscribe with `SELECT * FROM QueryResult WHERE caller =
+// X'<identity>'` and read the result of their call by `query`. Each call replaces the caller's
+// previous result of the same query.
+#[spacetimedb(table)]
+pub struct QueryResult {
+    #[primarykey]
+    #[autoinc]
+    pub id: u64,
+    pub caller: Identity,
+    pub query: String,
+    pub rows: QueryRows,
+    pub updated_at: u64,
+}
@@ -1,3 +1,37 @@
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
 fn main() {
+    // baked into the binary for the about screen and diagnostics, see `app_info.rs`
+    let git_hash = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .unwrap_or_default();
+    println!("cargo:rustc-env=KLED_GIT_HASH={}", git_hash);
+
+    let build_timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+    println!("cargo:rustc-env=KLED_BUILD_TIMESTAMP={}", build_timestamp);
+    println!("cargo:rerun-if-changed=../../.git/HEAD");
+    println!("cargo:rerun-if-changed=../../.git/refs/heads");
+
+    // checksum of the bundled CLI, checked by `verify_cli_integrity`. Empty if the binary isn't
+    // part of this build, the CLI then counts as unverified
+    let target = std::env::var("TARGET").unwrap_or_default();
+    let extension = if target.contains("windows") { ".exe" } else { "" };
+    let cli_path = PathBuf::from("bin").join(format!("devpod-cli-{}{}", target, extension));
+    let cli_checksum = std::fs::read(&cli_path)
+        .map(|contents| format!("{:x}", Sha256::digest(contents)))
+        .unwrap_or_default();
+    println!("cargo:rustc-env=KLED_CLI_SHA256={}", cli_checksum);
+    println!("cargo:rerun-if-changed={}", cli_path.display());
+
     tauri_build::build();
 }
@@ -1,18 +1,29 @@
-use crate::AppHandle;
+use crate::{resource_watcher::Identifiable, util, AppHandle, AppState};
 use anyhow::Context;
-use log::info;
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone, Utc};
+use lazy_static::lazy_static;
+use log::{debug, info, warn};
+use serde::Serialize;
 use std::{
     fs::{self, OpenOptions},
     io::Write,
     path::PathBuf,
+    sync::Mutex,
     time::{Duration, SystemTime},
 };
 use thiserror::Error;
-use tauri::Manager;
+use tauri::{Emitter, Manager};
 
 const ACTION_LOGS_DIR: &str = "action_logs";
+const COMBINED_LOG_LINE_EVENT: &str = "combined-log-line";
 const THIRTY_DAYS: Duration = Duration::new(60 * 60 * 24 * 30, 0);
 
+lazy_static! {
+    // Held while an action log is written or repaired, so a repair can't replace the file
+    // underneath a write and lose its record
+    static ref ACTION_LOG_LOCK: Mutex<()> = Mutex::new(());
+}
+
 #[derive(Error, Debug)]
 pub enum ActionLogError {
     #[error("unable to get app data dir")]
@@ -43,6 +54,7 @@ pub fn write_action_log(
     let mut path = get_actions_dir(&app_handle).map_err(|_| ActionLogError::NoDir)?;
     path.push(format!("{}.log", &action_id));
 
+    let _guard = ACTION_LOG_LOCK.lock().unwrap();
     let mut file = OpenOptions::new()
         .create(true)
         .append(true)
@@ -63,13 +75,71 @@ pub fn get_action_logs(
     let mut path = get_actions_dir(&app_handle).map_err(|_| ActionLogError::NoDir)?;
     path.push(format!("{}.log", &action_id));
 
-    let lines = fs::read_to_string(path)
-        .map_err(ActionLogError::FileOpen)?
-        .lines()
-        .map(|s| s.to_string())
+    let contents = fs::read(&path).map_err(ActionLogError::FileOpen)?;
+    let (records, dropped) = parse_records(&contents);
+    if dropped > 0 {
+        warn!(
+            "Skipped {} malformed records in {:?}, run repair_action_logs to remove them",
+            dropped, path
+        );
+    }
+
+    Ok(records)
+}
+
+// Rewrites the action log without the records `get_action_logs` skips, e.g. the half written
+// last record after a crash. Returns the number of dropped records.
+#[tauri::command]
+pub fn repair_action_logs(
+    app_handle: AppHandle,
+    action_id: String,
+) -> Result<usize, ActionLogError> {
+    let mut path = get_actions_dir(&app_handle).map_err(|_| ActionLogError::NoDir)?;
+    path.push(format!("{}.log", &action_id));
+
+    let _guard = ACTION_LOG_LOCK.lock().unwrap();
+    let contents = fs::read(&path).map_err(ActionLogError::FileOpen)?;
+    let (records, dropped) = parse_records(&contents);
+    if dropped == 0 {
+        return Ok(0);
+    }
+
+    // Replace the file in one step so a crash during the repair can't lose the intact records
+    let tmp_path = path.with_extension("log.repair");
+    let repaired: String = records
+        .iter()
+        .map(|record| format!("{}\n", record))
         .collect();
+    fs::write(&tmp_path, repaired).map_err(ActionLogError::Write)?;
+    fs::rename(&tmp_path, &path).map_err(ActionLogError::Write)?;
+    info!("Repaired {:?}, dropped {} malformed records", path, dropped);
+
+    Ok(dropped)
+}
+
+// Splits an action log into its records, one per line. Drops a last record without its newline
+// since `write_action_log` was interrupted while writing it, as well as records that aren't
+// UTF-8 or contain the NUL bytes a crash can leave behind. Returns the number of dropped records.
+fn parse_records(contents: &[u8]) -> (Vec<String>, usize) {
+    let mut records = vec![];
+    let mut dropped = 0;
+    let mut lines = contents.split_inclusive(|b| *b == b'\n').peekable();
+    while let Some(line) = lines.next() {
+        let complete = line.ends_with(b"\n");
+        if !complete && lines.peek().is_none() {
+            dropped += 1;
+            break;
+        }
 
-    Ok(lines)
+        let line = line.strip_suffix(b"\n").unwrap_or(line);
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        match std::str::from_utf8(line) {
+            Ok(record) if !record.contains('\0') => records.push(record.to_string()),
+            _ => dropped += 1,
+        }
+    }
+
+    (records, dropped)
 }
 
 #[tauri::command]
@@ -83,6 +153,130 @@ pub fn get_action_log_file(
     Ok(path.to_string_lossy().into())
 }
 
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CombinedLogLine {
+    // `action:<action id>` or `daemon:<pro instance host>`
+    source: String,
+    // In UTC, None if neither the line nor an earlier line of its source had a timestamp
+    timestamp: Option<DateTime<Utc>>,
+    line: String,
+}
+
+// Merges the last `lines` lines of the given action logs and of every running daemon's log into
+// one timeline and emits it line by line as `combined-log-line` events. Timestamps are normalized
+// to UTC, lines without one keep the timestamp of the line before them. Lines of the same source
+// are never reordered, so clock skew between the app and a daemon only affects interleaving.
+// Returns the number of emitted lines.
+#[tauri::command]
+pub async fn stream_combined_logs(
+    app_handle: AppHandle,
+    action_ids: Vec<String>,
+    lines: usize,
+) -> Result<usize, ActionLogError> {
+    let actions_dir = get_actions_dir(&app_handle).map_err(|_| ActionLogError::NoDir)?;
+    let mut sources: Vec<(String, PathBuf)> = action_ids
+        .iter()
+        .map(|id| {
+            (
+                format!("action:{}", id),
+                actions_dir.join(format!("{}.log", id)),
+            )
+        })
+        .collect();
+    {
+        let state = app_handle.state::<AppState>();
+        let pro_state = state.pro.read().await;
+        for instance in pro_state.instances() {
+            if let Some(log_path) = instance.daemon().as_ref().and_then(|d| d.log_path()) {
+                sources.push((format!("daemon:{}", instance.id()), log_path.clone()));
+            }
+        }
+    }
+
+    let mut tails = vec![];
+    for (source, path) in sources {
+        match util::read_last_lines(&path, lines) {
+            Ok(tail) => tails.push(timestamp_lines(source, tail)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                debug!("Skipping {} in combined logs, {:?} not found", source, path)
+            }
+            Err(err) => return Err(ActionLogError::FileOpen(err)),
+        }
+    }
+
+    let merged = merge_by_timestamp(tails);
+    for line in &merged {
+        if let Err(err) = app_handle.emit(COMBINED_LOG_LINE_EVENT, line) {
+            debug!("Failed to emit combined log line: {}", err);
+        }
+    }
+
+    Ok(merged.len())
+}
+
+fn timestamp_lines(source: String, lines: Vec<String>) -> Vec<CombinedLogLine> {
+    let mut last = None;
+    lines
+        .into_iter()
+        .map(|line| {
+            last = parse_timestamp(&line).or(last);
+            CombinedLogLine {
+                source: source.clone(),
+                timestamp: last,
+                line,
+            }
+        })
+        .collect()
+}
+
+// Repeatedly takes the earliest head of all sources, keeping every source in its own order
+fn merge_by_timestamp(sources: Vec<Vec<CombinedLogLine>>) -> Vec<CombinedLogLine> {
+    let mut sources: Vec<_> = sources
+        .into_iter()
+        .map(|s| s.into_iter().peekable())
+        .collect();
+    let mut merged = vec![];
+    loop {
+        let next = sources
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(i, source)| source.peek().map(|line| (i, line.timestamp)))
+            .min_by_key(|(_, timestamp)| *timestamp)
+            .map(|(i, _)| i);
+        match next.and_then(|i| sources[i].next()) {
+            Some(line) => merged.push(line),
+            None => return merged,
+        }
+    }
+}
+
+// Understands the CLI's JSON log lines and lines starting with an RFC 3339 or
+// `YYYY-MM-DD HH:MM:SS` timestamp, the latter is assumed to be local time
+fn parse_timestamp(line: &str) -> Option<DateTime<Utc>> {
+    let line = line.trim_start();
+    if line.starts_with('{') {
+        let value: serde_json::Value = serde_json::from_str(line).ok()?;
+        let time = ["time", "timestamp", "ts"]
+            .iter()
+            .find_map(|key| value.get(*key).and_then(|v| v.as_str()))?;
+        return DateTime::parse_from_rfc3339(time)
+            .ok()
+            .map(|t| t.with_timezone(&Utc));
+    }
+
+    let first = line.split_whitespace().next()?;
+    if let Ok(time) = DateTime::parse_from_rfc3339(first.trim_matches(|c| c == '[' || c == ']')) {
+        return Some(time.with_timezone(&Utc));
+    }
+
+    let naive = line.get(..19)?.replace('/', "-");
+    NaiveDateTime::parse_from_str(&naive, "%Y-%m-%d %H:%M:%S")
+        .ok()
+        .and_then(|t| Local.from_local_datetime(&t).earliest())
+        .map(|t| t.with_timezone(&Utc))
+}
+
 pub fn setup(app_handle: &AppHandle) -> anyhow::Result<()> {
     let dir_path = get_actions_dir(app_handle)?;
     let _ = fs::create_dir_all(&dir_path); // Make sure we have the action logs dir
@@ -121,7 +315,7 @@ pub fn setup(app_handle: &AppHandle) -> anyhow::Result<()> {
     Ok(())
 }
 
-fn get_actions_dir(app_handle: &AppHandle) -> anyhow::Result<PathBuf> {
+pub fn get_actions_dir(app_handle: &AppHandle) -> anyhow::Result<PathBuf> {
     let mut dir_path = app_handle
         .path()
         .app_data_dir()
@@ -130,3 +324,176 @@ fn get_actions_dir(app_handle: &AppHandle) -> anyhow::Result<PathBuf> {
 
     Ok(dir_path)
 }
+
+#[cfg(test)]
+mod tests {
+    mod parse_records {
+        use super::super::*;
+
+        #[test]
+        fn should_split_records() {
+            let (records, dropped) = parse_records(b"first\nsecond\r\n\n");
+
+            assert_eq!(records, vec!["first", "second", ""]);
+            assert_eq!(dropped, 0);
+        }
+
+        #[test]
+        fn should_drop_incomplete_last_record() {
+            let (records, dropped) = parse_records(b"first\nsecond");
+
+            assert_eq!(records, vec!["first"]);
+            assert_eq!(dropped, 1);
+        }
+
+        #[test]
+        fn should_drop_corrupt_records() {
+            let (records, dropped) = parse_records(b"first\n\0\0\0\n\xff\xfe\nlast\n");
+
+            assert_eq!(records, vec!["first", "last"]);
+            assert_eq!(dropped, 2);
+        }
+
+        #[test]
+        fn should_accept_empty_log() {
+            let (records, dropped) = parse_records(b"");
+
+            assert!(records.is_empty());
+            assert_eq!(dropped, 0);
+        }
+    }
+
+    mod combined_logs {
+        use super::super::*;
+
+        fn lines(source: &str, lines: &[&str]) -> Vec<CombinedLogLine> {
+            timestamp_lines(
+                source.to_string(),
+                lines.iter().map(|line| line.to_string()).collect(),
+            )
+        }
+
+        fn merged_lines(merged: &[CombinedLogLine]) -> Vec<&str> {
+            merged.iter().map(|line| line.line.as_str()).collect()
+        }
+
+        #[test]
+        fn should_parse_timestamps() {
+            let expected = DateTime::parse_from_rfc3339("2024-01-31T09:15:00Z")
+                .unwrap()
+                .with_timezone(&Utc);
+
+            assert_eq!(
+                parse_timestamp("2024-01-31T10:15:00+01:00 info started"),
+                Some(expected)
+            );
+            assert_eq!(
+                parse_timestamp("[2024-01-31T09:15:00Z] info started"),
+                Some(expected)
+            );
+            assert_eq!(
+                parse_timestamp(r#"{"level":"info","time":"2024-01-31T09:15:00Z"}"#),
+                Some(expected)
+            );
+            assert!(parse_timestamp("2024/01/31 09:15:00 info started").is_some());
+        }
+
+        #[test]
+        fn should_not_parse_lines_without_timestamp() {
+            assert_eq!(parse_timestamp("info started"), None);
+            assert_eq!(parse_timestamp(""), None);
+            assert_eq!(parse_timestamp(r#"{"level":"info"}"#), None);
+            assert_eq!(parse_timestamp(r#"{"time":"yesterday"}"#), None);
+            assert_eq!(parse_timestamp("2024-13-45 99:99:99 info"), None);
+        }
+
+        #[test]
+        fn should_interleave_sources_by_timestamp() {
+            let action = lines(
+                "action:1",
+                &[
+                    "2024-01-31T09:00:00Z a1",
+                    "2024-01-31T09:02:00Z a2",
+                    "2024-01-31T09:04:00Z a3",
+                ],
+            );
+            let daemon = lines(
+                "daemon:host",
+                &["2024-01-31T09:01:00Z d1", "2024-01-31T09:03:00Z d2"],
+            );
+
+            let merged = merge_by_timestamp(vec![action, daemon]);
+
+            assert_eq!(
+                merged_lines(&merged),
+                vec![
+                    "2024-01-31T09:00:00Z a1",
+                    "2024-01-31T09:01:00Z d1",
+                    "2024-01-31T09:02:00Z a2",
+                    "2024-01-31T09:03:00Z d2",
+                    "2024-01-31T09:04:00Z a3",
+                ]
+            );
+        }
+
+        #[test]
+        fn should_keep_unparsable_lines_with_the_line_before() {
+            let action = lines(
+                "action:1",
+                &[
+                    "2024-01-31T09:00:00Z a1",
+                    "  continued a1",
+                    "2024-01-31T09:02:00Z a2",
+                ],
+            );
+            let daemon = lines("daemon:host", &["2024-01-31T09:01:00Z d1"]);
+
+            let merged = merge_by_timestamp(vec![action, daemon]);
+
+            assert_eq!(merged[1].timestamp, merged[0].timestamp);
+            assert_eq!(
+                merged_lines(&merged),
+                vec![
+                    "2024-01-31T09:00:00Z a1",
+                    "  continued a1",
+                    "2024-01-31T09:01:00Z d1",
+                    "2024-01-31T09:02:00Z a2",
+                ]
+            );
+        }
+
+        #[test]
+        fn should_put_lines_without_any_timestamp_first() {
+            let action = lines("action:1", &["no timestamp", "2024-01-31T09:05:00Z a1"]);
+            let daemon = lines("daemon:host", &["2024-01-31T09:00:00Z d1"]);
+
+            let merged = merge_by_timestamp(vec![action, daemon]);
+
+            assert_eq!(merged[0].timestamp, None);
+            assert_eq!(
+                merged_lines(&merged),
+                vec![
+                    "no timestamp",
+                    "2024-01-31T09:00:00Z d1",
+                    "2024-01-31T09:05:00Z a1",
+                ]
+            );
+        }
+
+        #[test]
+        fn should_never_reorder_a_source() {
+            // A clock going backwards within one source must not move its lines around
+            let action = lines(
+                "action:1",
+                &["2024-01-31T09:05:00Z a1", "2024-01-31T09:00:00Z a2"],
+            );
+
+            let merged = merge_by_timestamp(vec![action]);
+
+            assert_eq!(
+                merged_lines(&merged),
+                vec!["2024-01-31T09:05:00Z a1", "2024-01-31T09:00:00Z a2"]
+            );
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use crate::AppHandle;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use ts_rs::TS;
+
+const GIT_HASH: &str = env!("KLED_GIT_HASH");
+const BUILD_TIMESTAMP: &str = env!("KLED_BUILD_TIMESTAMP");
+
+#[derive(Debug, Serialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct AppInfo {
+    pub name: String,
+    pub version: String,
+    // Empty when the app wasn't built from a git checkout
+    pub commit_hash: String,
+    #[ts(type = "string | null")]
+    pub build_date: Option<DateTime<Utc>>,
+    pub tauri_version: String,
+    pub os: OsInfo,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct OsInfo {
+    pub platform: String,
+    pub version: String,
+    pub arch: String,
+}
+
+pub fn collect(app_handle: &AppHandle) -> AppInfo {
+    let package_info = app_handle.package_info();
+
+    AppInfo {
+        name: package_info.name.clone(),
+        version: package_info.version.to_string(),
+        commit_hash: GIT_HASH.to_string(),
+        build_date: BUILD_TIMESTAMP
+            .parse::<i64>()
+            .ok()
+            .and_then(|secs| DateTime::from_timestamp(secs, 0)),
+        tauri_version: tauri::VERSION.to_string(),
+        os: OsInfo {
+            platform: tauri_plugin_os::platform().to_string(),
+            version: tauri_plugin_os::version().to_string(),
+            arch: tauri_plugin_os::arch().to_string(),
+        },
+    }
+}
+
+#[tauri::command]
+pub fn get_app_info(app_handle: AppHandle) -> AppInfo {
+    collect(&app_handle)
+}
@@ -0,0 +1,88 @@
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use log::info;
+use serde::Serialize;
+use std::{collections::HashMap, sync::Mutex};
+use thiserror::Error;
+
+lazy_static! {
+    // Long running CLI processes the app spawned and that haven't exited yet, keyed by pid.
+    // Short commands awaited with `Command::output` aren't tracked.
+    static ref CHILD_PROCESSES: Mutex<HashMap<u32, ChildProcess>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Error, Debug)]
+pub enum ChildProcessError {
+    #[error("process {0} was not started by the app or has already exited")]
+    NotTracked(u32),
+}
+impl serde::Serialize for ChildProcessError {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(self.to_string().as_ref())
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChildProcess {
+    pid: u32,
+    command_line: String,
+    started_at: DateTime<Utc>,
+}
+
+pub fn track(pid: u32, command_line: String) {
+    CHILD_PROCESSES.lock().unwrap().insert(
+        pid,
+        ChildProcess {
+            pid,
+            command_line,
+            started_at: Utc::now(),
+        },
+    );
+}
+
+// Call once the process exited or was killed, pids are reused by the OS
+pub fn untrack(pid: u32) {
+    CHILD_PROCESSES.lock().unwrap().remove(&pid);
+}
+
+// Running child processes, oldest first
+#[tauri::command]
+pub fn list_child_processes() -> Vec<ChildProcess> {
+    let mut processes: Vec<ChildProcess> =
+        CHILD_PROCESSES.lock().unwrap().values().cloned().collect();
+    processes.sort_by_key(|process| process.started_at);
+
+    processes
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KilledProcess {
+    process: ChildProcess,
+    // Processes the child started itself, only known on unix
+    descendants: Vec<u32>,
+}
+
+// Terminates a process the app spawned together with everything it started, for operations
+// that hang. Refuses pids that aren't listed by `list_child_processes`, so it can't be used to
+// kill arbitrary processes.
+#[tauri::command]
+pub fn kill_process(pid: u32) -> Result<KilledProcess, ChildProcessError> {
+    let process = CHILD_PROCESSES
+        .lock()
+        .unwrap()
+        .remove(&pid)
+        .ok_or(ChildProcessError::NotTracked(pid))?;
+
+    info!("Killing process {} ({})", pid, process.command_line);
+    let descendants = crate::util::kill_process_tree(pid);
+
+    Ok(KilledProcess {
+        process,
+        descendants,
+    })
+}
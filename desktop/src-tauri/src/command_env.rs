@@ -0,0 +1,142 @@
+use crate::{AppHandle, AppState};
+use log::{error, info};
+use std::collections::HashMap;
+use tauri::Manager;
+use tauri_plugin_store::StoreExt;
+use thiserror::Error;
+
+// Variables the user chose to keep across restarts
+pub const COMMAND_ENV_FILE_NAME: &str = ".command_env.json";
+
+#[derive(Error, Debug)]
+pub enum CommandEnvError {
+    #[error("invalid environment variable name {0:?}")]
+    InvalidKey(String),
+    #[error("unable to open store {0}")]
+    Store(String),
+    #[error("unable to save store {0}")]
+    Save(String),
+}
+impl serde::Serialize for CommandEnvError {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(self.to_string().as_ref())
+    }
+}
+
+// Environment variables added to every CLI invocation on top of the app's own environment,
+// e.g. proxy settings the app wasn't launched with
+#[derive(Debug, Default)]
+pub struct CommandEnv {
+    vars: HashMap<String, String>,
+    // Values `fix_env::refresh_env` read from the login shell. They're applied per command
+    // instead of changing the app's environment, which other threads read concurrently.
+    refreshed: HashMap<String, String>,
+}
+
+// Loads the persisted variables, call once the store plugin is set up
+pub fn setup(app_handle: &AppHandle) {
+    let store = match app_handle.store(COMMAND_ENV_FILE_NAME) {
+        Ok(store) => store,
+        Err(err) => {
+            error!("unable to open store {}: {}", COMMAND_ENV_FILE_NAME, err);
+            return;
+        }
+    };
+
+    let state = app_handle.state::<AppState>();
+    let mut command_env = state.command_env.lock().unwrap();
+    for (key, value) in store.entries() {
+        if let Some(value) = value.as_str() {
+            command_env.vars.insert(key, value.to_string());
+        }
+    }
+}
+
+pub fn overlay(app_handle: &AppHandle) -> HashMap<String, String> {
+    let state = app_handle.state::<AppState>();
+    let command_env = state.command_env.lock().unwrap();
+
+    // Variables the user set win over refreshed ones
+    let mut vars = command_env.refreshed.clone();
+    vars.extend(command_env.vars.clone());
+    vars
+}
+
+// The value the login shell had for `key` at the last `fix_env::refresh_env`, or the app's own
+// if it wasn't refreshed
+pub fn login_shell_var(app_handle: &AppHandle, key: &str) -> Option<String> {
+    let state = app_handle.state::<AppState>();
+    let refreshed = state
+        .command_env
+        .lock()
+        .unwrap()
+        .refreshed
+        .get(key)
+        .cloned();
+
+    refreshed.or_else(|| std::env::var(key).ok())
+}
+
+pub fn set_refreshed(app_handle: &AppHandle, key: String, value: String) {
+    let state = app_handle.state::<AppState>();
+    state
+        .command_env
+        .lock()
+        .unwrap()
+        .refreshed
+        .insert(key, value);
+}
+
+// Sets `key` for all CLI commands spawned from now on. With `persist` the variable is restored
+// on the next launch, otherwise it only lasts until the app quits.
+#[tauri::command]
+pub fn set_command_env(
+    app_handle: AppHandle,
+    key: String,
+    value: String,
+    persist: bool,
+) -> Result<(), CommandEnvError> {
+    if key.is_empty() || key.contains('=') || key.contains('\0') {
+        return Err(CommandEnvError::InvalidKey(key));
+    }
+
+    let store = app_handle
+        .store(COMMAND_ENV_FILE_NAME)
+        .map_err(|_| CommandEnvError::Store(COMMAND_ENV_FILE_NAME.to_string()))?;
+    if persist {
+        store.set(key.clone(), serde_json::json!(value));
+    } else {
+        store.delete(&key);
+    }
+    store
+        .save()
+        .map_err(|err| CommandEnvError::Save(err.to_string()))?;
+
+    info!("Setting {} for CLI commands", key);
+    let state = app_handle.state::<AppState>();
+    state.command_env.lock().unwrap().vars.insert(key, value);
+
+    Ok(())
+}
+
+// Stops adding `key` to CLI commands, the app's own value applies again if it has one
+#[tauri::command]
+pub fn unset_command_env(app_handle: AppHandle, key: String) -> Result<(), CommandEnvError> {
+    let store = app_handle
+        .store(COMMAND_ENV_FILE_NAME)
+        .map_err(|_| CommandEnvError::Store(COMMAND_ENV_FILE_NAME.to_string()))?;
+    if store.delete(&key) {
+        store
+            .save()
+            .map_err(|err| CommandEnvError::Save(err.to_string()))?;
+    }
+
+    info!("Unsetting {} for CLI commands", key);
+    let state = app_handle.state::<AppState>();
+    state.command_env.lock().unwrap().vars.remove(&key);
+
+    Ok(())
+}
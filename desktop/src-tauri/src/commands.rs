@@ -3,8 +3,18 @@ pub mod constants;
 pub use config::{DevpodCommandConfig, DevpodCommandError};
 pub use constants::KLED_BINARY_NAME;
 
+pub mod add_provider;
 pub mod delete_provider;
+pub mod delete_workspace;
 pub mod delete_pro_instance;
+pub mod exec_workspace;
+pub mod list_providers;
 pub mod list_workspaces;
 pub mod list_pro_instances;
+pub mod logs_workspace;
 pub mod start_daemon;
+pub mod stop_workspace;
+pub mod up_workspace;
+pub mod validate_provider_config;
+pub mod version;
+pub mod workspace_status;
@@ -0,0 +1,68 @@
+use std::collections::HashMap;
+
+use tauri::AppHandle;
+
+use super::{
+    config::{CommandConfig, DevpodCommandConfig, DevpodCommandError},
+    constants::{
+        FLAG_NAME, FLAG_OPTION, KLED_BINARY_NAME, KLED_COMMAND_ADD, KLED_COMMAND_PROVIDER,
+    },
+};
+
+pub struct AddProviderCommand {
+    source: String,
+    name_flag: String,
+    option_flags: Vec<String>,
+}
+impl AddProviderCommand {
+    pub fn new(name: String, source: String, options: HashMap<String, String>) -> Self {
+        let mut options: Vec<(String, String)> = options.into_iter().collect();
+        options.sort();
+
+        let option_flags = options
+            .into_iter()
+            .map(|(key, value)| format!("{}={}={}", FLAG_OPTION, key, value))
+            .collect();
+
+        AddProviderCommand {
+            source,
+            name_flag: format!("{}={}", FLAG_NAME, name),
+            option_flags,
+        }
+    }
+}
+impl DevpodCommandConfig<()> for AddProviderCommand {
+    fn config(&self) -> CommandConfig {
+        let mut args = vec![
+            KLED_COMMAND_PROVIDER,
+            KLED_COMMAND_ADD,
+            &self.source,
+            &self.name_flag,
+        ];
+        args.extend(self.option_flags.iter().map(String::as_str));
+
+        CommandConfig {
+            binary_name: KLED_BINARY_NAME,
+            args,
+        }
+    }
+
+    fn exec_blocking(self, app_handle: &AppHandle) -> Result<(), DevpodCommandError> {
+        let cmd = self.new_command(app_handle)?;
+
+        let output = tauri::async_runtime::block_on(async move { cmd.output().await })
+            .map_err(DevpodCommandError::from)?;
+
+        DevpodCommandError::check_output(&output)
+    }
+}
+
+impl AddProviderCommand {
+    pub async fn exec(self, app_handle: &AppHandle) -> Result<(), DevpodCommandError> {
+        let cmd = self.new_command(app_handle)?;
+
+        let output = cmd.output().await.map_err(DevpodCommandError::from)?;
+
+        DevpodCommandError::check_output(&output)
+    }
+}
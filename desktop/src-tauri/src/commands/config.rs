@@ -1,13 +1,22 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 
+use serde::ser::SerializeStruct;
+use tauri::async_runtime::Receiver;
 use tauri::AppHandle;
-use tauri_plugin_shell::{process::Command, ShellExt};
+use tauri_plugin_shell::{
+    process::{Command, CommandChild, CommandEvent, Output},
+    ShellExt,
+};
 use thiserror::Error;
 
 use crate::commands::constants::KLED_BINARY_NAME;
+use crate::{child_processes, command_env, logging, operations};
 
 use super::constants::KLED_UI_ENV_VAR;
 
+// Stderr lines `output_logging_stderr` keeps for the error if the command fails
+const STDERR_TAIL_LINES: usize = 20;
+
 pub struct CommandConfig<'a> {
     pub(crate) binary_name: &'static str,
     pub(crate) args: Vec<&'a str>,
@@ -25,25 +34,69 @@ impl<'a> CommandConfig<'_> {
 
 #[derive(Error, Debug)]
 pub enum DevpodCommandError {
-    #[error("unable to parse command response")]
-    Parse(#[from] serde_json::Error),
-    #[error("unable to find sidecar binary")]
-    Sidecar,
-    #[error("unable to collect output from command")]
-    Output,
-    #[error("command failed")]
-    Failed(#[from] tauri_plugin_shell::Error),
-    #[error("command exited with non-zero code")]
-    Exit,
+    #[error("unable to find the kled binary")]
+    BinaryNotFound,
+    #[error("unable to spawn command: {0}")]
+    Spawn(tauri_plugin_shell::Error),
+    #[error("command exited with code {}: {stderr}", code.map_or("unknown".to_string(), |c| c.to_string()))]
+    NonZeroExit { code: Option<i32>, stderr: String },
+    #[error("command timed out")]
+    Timeout,
+    #[error("operation was cancelled")]
+    Cancelled,
+    #[error("unable to parse command response: {0}")]
+    JsonParse(#[from] serde_json::Error),
     #[error("error")]
     Any(#[from] anyhow::Error)
 }
+impl DevpodCommandError {
+    // Stable identifier the frontend can branch on, independent of the message
+    pub fn kind(&self) -> &'static str {
+        match self {
+            DevpodCommandError::BinaryNotFound => "binaryNotFound",
+            DevpodCommandError::Spawn(_) => "spawn",
+            DevpodCommandError::NonZeroExit { .. } => "nonZeroExit",
+            DevpodCommandError::Timeout => "timeout",
+            DevpodCommandError::Cancelled => "cancelled",
+            DevpodCommandError::JsonParse(_) => "jsonParse",
+            DevpodCommandError::Any(_) => "other",
+        }
+    }
+
+    // Turns a finished command into a `NonZeroExit` error unless it succeeded
+    pub fn check_output(output: &Output) -> Result<(), DevpodCommandError> {
+        if output.status.success() {
+            return Ok(());
+        }
+
+        Err(DevpodCommandError::NonZeroExit {
+            code: output.status.code(),
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        })
+    }
+}
+// A sidecar that is configured but missing on disk only fails once it's spawned
+impl From<tauri_plugin_shell::Error> for DevpodCommandError {
+    fn from(err: tauri_plugin_shell::Error) -> Self {
+        match err {
+            tauri_plugin_shell::Error::Io(io_err)
+                if io_err.kind() == std::io::ErrorKind::NotFound =>
+            {
+                DevpodCommandError::BinaryNotFound
+            }
+            err => DevpodCommandError::Spawn(err),
+        }
+    }
+}
 impl serde::Serialize for DevpodCommandError {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: serde::Serializer,
     {
-        serializer.serialize_str(self.to_string().as_ref())
+        let mut state = serializer.serialize_struct("DevpodCommandError", 2)?;
+        state.serialize_field("kind", self.kind())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.end()
     }
 }
 pub trait DevpodCommandConfig<T> {
@@ -63,10 +116,128 @@ pub trait DevpodCommandConfig<T> {
         let cmd = app_handle
             .shell()
             .sidecar(config.binary_name())
-            .map_err(|_| DevpodCommandError::Sidecar)?
+            .map_err(|_| DevpodCommandError::BinaryNotFound)?
+            .envs(command_env::overlay(app_handle))
             .envs(env_vars)
             .args(config.args());
 
         Ok(cmd)
     }
+
+    // Binary and arguments as they'd be typed in a terminal
+    fn command_line(&self) -> String {
+        let config = self.config();
+        std::iter::once(config.binary_name())
+            .chain(config.args().iter().copied().filter(|arg| !arg.is_empty()))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+// Spawns the command and lists it in `child_processes` until the caller untracks it
+pub fn spawn_tracked(
+    cmd: Command,
+    command_line: String,
+) -> Result<(Receiver<CommandEvent>, CommandChild), DevpodCommandError> {
+    let (rx, child) = cmd.spawn().map_err(DevpodCommandError::from)?;
+    child_processes::track(child.pid(), command_line);
+
+    Ok((rx, child))
+}
+
+// Runs the command and hands every output line to `on_progress` as it arrives,
+// stderr is kept for the error if the command fails. The output is also copied to the CLI log. With an `operation_id` the process
+// can be cancelled through `operations::cancel_operation`.
+pub async fn exec_with_progress<F>(
+    cmd: Command,
+    command_line: String,
+    app_handle: &AppHandle,
+    operation_id: Option<&str>,
+    on_progress: F,
+) -> Result<(), DevpodCommandError>
+where
+    F: Fn(String),
+{
+    let (rx, child) = spawn_tracked(cmd, command_line.clone())?;
+    let mut rx = logging::tee_cli_output(app_handle, &command_line, rx);
+    let pid = child.pid();
+    if let Some(operation_id) = operation_id {
+        operations::register(app_handle, operation_id, pid);
+    }
+
+    let mut stderr = vec![];
+    let mut code = None;
+    while let Some(event) = rx.recv().await {
+        match event {
+            CommandEvent::Stdout(line) => {
+                on_progress(String::from_utf8_lossy(&line).trim_end().to_string());
+            }
+            CommandEvent::Stderr(line) => {
+                let line = String::from_utf8_lossy(&line).trim_end().to_string();
+                on_progress(line.clone());
+                stderr.push(line);
+            }
+            CommandEvent::Terminated(payload) => {
+                code = payload.code;
+            }
+            _ => {}
+        }
+    }
+    child_processes::untrack(pid);
+
+    if let Some(operation_id) = operation_id {
+        if operations::finish(app_handle, operation_id) {
+            return Err(DevpodCommandError::Cancelled);
+        }
+    }
+    if code == Some(0) {
+        return Ok(());
+    }
+
+    Err(DevpodCommandError::NonZeroExit {
+        code,
+        stderr: stderr.join("\n"),
+    })
+}
+
+// Runs the command to completion like `Command::output`, but streams stderr line by line
+// into the app log at `level` instead of capturing it. Returns stdout if the command succeeded,
+// otherwise the error carries the last `STDERR_TAIL_LINES` lines of stderr.
+pub async fn output_logging_stderr(
+    cmd: Command,
+    command_line: String,
+    binary_name: &str,
+    level: log::Level,
+) -> Result<Vec<u8>, DevpodCommandError> {
+    let (mut rx, child) = spawn_tracked(cmd, command_line)?;
+
+    let mut code = None;
+    let mut stdout = Vec::new();
+    let mut stderr_tail = VecDeque::with_capacity(STDERR_TAIL_LINES);
+    while let Some(event) = rx.recv().await {
+        match event {
+            // Lines keep their trailing newline
+            CommandEvent::Stdout(line) => stdout.extend(line),
+            CommandEvent::Stderr(line) => {
+                logging::log_child_stderr(binary_name, level, &line);
+                if stderr_tail.len() == STDERR_TAIL_LINES {
+                    stderr_tail.pop_front();
+                }
+                stderr_tail.push_back(String::from_utf8_lossy(&line).trim_end().to_string());
+            }
+            CommandEvent::Terminated(payload) => code = payload.code,
+            CommandEvent::Error(err) => log::warn!("[{}] {}", binary_name, err),
+            _ => {}
+        }
+    }
+    child_processes::untrack(child.pid());
+
+    if code != Some(0) {
+        return Err(DevpodCommandError::NonZeroExit {
+            code,
+            stderr: Vec::from(stderr_tail).join("\n"),
+        });
+    }
+
+    Ok(stdout)
 }
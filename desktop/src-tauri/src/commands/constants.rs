@@ -8,12 +8,26 @@ pub(super) const KLED_COMMAND_PRO: &str = "pro";
 pub(super) const KLED_COMMAND_DELETE: &str = "delete";
 pub(super) const KLED_COMMAND_DAEMON: &str = "daemon";
 pub(super) const KLED_COMMAND_START: &str = "start";
+pub(super) const KLED_COMMAND_SET_OPTIONS: &str = "set-options";
+pub(super) const KLED_COMMAND_VERSION: &str = "version";
+pub(super) const KLED_COMMAND_STOP: &str = "stop";
+pub(super) const KLED_COMMAND_STATUS: &str = "status";
+pub(super) const KLED_COMMAND_ADD: &str = "add";
+pub(super) const KLED_COMMAND_UP: &str = "up";
+pub(super) const KLED_COMMAND_SSH: &str = "ssh";
+pub(super) const KLED_COMMAND_LOGS: &str = "logs";
 
 // Flags
 pub(super) const FLAG_OUTPUT_JSON: &str = "--output=json";
 pub(super) const FLAG_HOST: &str = "--host";
 pub(super) const FLAG_DEBUG: &str = "--debug";
 pub(super) const FLAG_IGNORE_NOT_FOUND: &str = "--ignore-not-found";
+pub(super) const FLAG_DRY_RUN: &str = "--dry-run";
+pub(super) const FLAG_OPTION: &str = "--option";
+pub(super) const FLAG_NAME: &str = "--name";
+pub(super) const FLAG_IDE: &str = "--ide";
+pub(super) const FLAG_OPEN_IDE: &str = "--open-ide";
+pub(super) const FLAG_COMMAND: &str = "--command";
 
 // Env vars
 pub(super) const KLED_UI_ENV_VAR: &str = "DEVPOD_UI";
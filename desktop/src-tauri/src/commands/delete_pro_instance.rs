@@ -31,10 +31,9 @@ impl DevpodCommandConfig<()> for DeleteProInstanceCommand {
     fn exec_blocking(self, app_handle: &AppHandle) -> Result<(), DevpodCommandError> {
         let cmd = self.new_command(app_handle)?;
 
-        tauri::async_runtime::block_on(async move { cmd.status().await })
-            .map_err(DevpodCommandError::Failed)?
-            .success()
-            .then_some(())
-            .ok_or_else(|| DevpodCommandError::Exit)
+        let output = tauri::async_runtime::block_on(async move { cmd.output().await })
+            .map_err(DevpodCommandError::from)?;
+
+        DevpodCommandError::check_output(&output)
     }
 }
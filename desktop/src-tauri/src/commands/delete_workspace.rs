@@ -0,0 +1,60 @@
+use tauri::AppHandle;
+
+use super::{
+    config::{exec_with_progress, CommandConfig, DevpodCommandConfig, DevpodCommandError},
+    constants::{FLAG_IGNORE_NOT_FOUND, KLED_BINARY_NAME, KLED_COMMAND_DELETE},
+};
+
+pub struct DeleteWorkspaceCommand {
+    workspace_id: String,
+}
+impl DeleteWorkspaceCommand {
+    pub fn new(workspace_id: String) -> Self {
+        DeleteWorkspaceCommand { workspace_id }
+    }
+}
+impl DevpodCommandConfig<()> for DeleteWorkspaceCommand {
+    fn config(&self) -> CommandConfig {
+        CommandConfig {
+            binary_name: KLED_BINARY_NAME,
+            args: vec![
+                KLED_COMMAND_DELETE,
+                &self.workspace_id,
+                FLAG_IGNORE_NOT_FOUND,
+            ],
+        }
+    }
+
+    fn exec_blocking(self, app_handle: &AppHandle) -> Result<(), DevpodCommandError> {
+        let cmd = self.new_command(app_handle)?;
+
+        let output = tauri::async_runtime::block_on(async move { cmd.output().await })
+            .map_err(DevpodCommandError::from)?;
+
+        DevpodCommandError::check_output(&output)
+    }
+}
+
+impl DeleteWorkspaceCommand {
+    // Runs the delete and hands every output line to `on_progress` as it arrives
+    pub async fn exec_with_progress<F>(
+        self,
+        app_handle: &AppHandle,
+        operation_id: Option<&str>,
+        on_progress: F,
+    ) -> Result<(), DevpodCommandError>
+    where
+        F: Fn(String),
+    {
+        let cmd = self.new_command(app_handle)?;
+
+        exec_with_progress(
+            cmd,
+            self.command_line(),
+            app_handle,
+            operation_id,
+            on_progress,
+        )
+        .await
+    }
+}
@@ -0,0 +1,96 @@
+use tauri::AppHandle;
+use tauri_plugin_shell::process::CommandEvent;
+
+use crate::{child_processes, operations};
+
+use super::{
+    config::{spawn_tracked, CommandConfig, DevpodCommandConfig, DevpodCommandError},
+    constants::{FLAG_COMMAND, KLED_BINARY_NAME, KLED_COMMAND_SSH},
+};
+
+#[derive(Debug, Clone, Copy, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ExecStream {
+    Stdout,
+    Stderr,
+}
+
+// Runs a command inside the workspace over the CLI's ssh connection
+pub struct ExecWorkspaceCommand {
+    workspace_id: String,
+    command: String,
+}
+impl ExecWorkspaceCommand {
+    pub fn new(workspace_id: String, command: String) -> Self {
+        ExecWorkspaceCommand {
+            workspace_id,
+            command,
+        }
+    }
+}
+impl DevpodCommandConfig<Option<i32>> for ExecWorkspaceCommand {
+    fn config(&self) -> CommandConfig {
+        CommandConfig {
+            binary_name: KLED_BINARY_NAME,
+            args: vec![
+                KLED_COMMAND_SSH,
+                &self.workspace_id,
+                FLAG_COMMAND,
+                &self.command,
+            ],
+        }
+    }
+
+    fn exec_blocking(self, app_handle: &AppHandle) -> Result<Option<i32>, DevpodCommandError> {
+        let cmd = self.new_command(app_handle)?;
+
+        let output = tauri::async_runtime::block_on(async move { cmd.output().await })
+            .map_err(DevpodCommandError::from)?;
+
+        Ok(output.status.code())
+    }
+}
+
+impl ExecWorkspaceCommand {
+    // Hands output to `on_output` as it arrives, without waiting for complete lines. Input can be
+    // written with `operations::write_operation_stdin` and the command cancelled with
+    // `operations::cancel_operation` using `operation_id`. Returns the exit code of the command,
+    // a failing command is not an error.
+    pub async fn exec_streaming<F>(
+        self,
+        app_handle: &AppHandle,
+        operation_id: &str,
+        on_output: F,
+    ) -> Result<Option<i32>, DevpodCommandError>
+    where
+        F: Fn(ExecStream, String),
+    {
+        let cmd = self.new_command(app_handle)?.set_raw_out(true);
+        let (mut rx, child) = spawn_tracked(cmd, self.command_line())?;
+        let pid = child.pid();
+        operations::register_with_stdin(app_handle, operation_id, child);
+
+        let mut code = None;
+        while let Some(event) = rx.recv().await {
+            match event {
+                CommandEvent::Stdout(data) => on_output(
+                    ExecStream::Stdout,
+                    String::from_utf8_lossy(&data).to_string(),
+                ),
+                CommandEvent::Stderr(data) => on_output(
+                    ExecStream::Stderr,
+                    String::from_utf8_lossy(&data).to_string(),
+                ),
+                CommandEvent::Terminated(payload) => code = payload.code,
+                _ => {}
+            }
+        }
+        child_processes::untrack(pid);
+
+        if operations::finish(app_handle, operation_id) {
+            return Err(DevpodCommandError::Cancelled);
+        }
+
+        Ok(code)
+    }
+}
@@ -13,7 +13,7 @@ impl ListProInstancesCommand {
     }
 
     fn deserialize(&self, d: Vec<u8>) -> Result<Vec<ProInstance>, DevpodCommandError> {
-        serde_json::from_slice(&d).map_err(DevpodCommandError::Parse)
+        serde_json::from_slice(&d).map_err(DevpodCommandError::JsonParse)
     }
 }
 impl DevpodCommandConfig<Vec<ProInstance>> for ListProInstancesCommand {
@@ -28,7 +28,8 @@ impl DevpodCommandConfig<Vec<ProInstance>> for ListProInstancesCommand {
         let cmd = self.new_command(app_handle)?;
 
         let output = tauri::async_runtime::block_on(async move { cmd.output().await })
-            .map_err(|_| DevpodCommandError::Output)?;
+            .map_err(DevpodCommandError::from)?;
+        DevpodCommandError::check_output(&output)?;
 
         self.deserialize(output.stdout)
     }
@@ -40,7 +41,8 @@ impl ListProInstancesCommand {
     ) -> Result<Vec<ProInstance>, DevpodCommandError> {
         let cmd = self.new_command(app_handle)?;
 
-        let output = cmd.output().await.map_err(|_| DevpodCommandError::Output)?;
+        let output = cmd.output().await.map_err(DevpodCommandError::from)?;
+        DevpodCommandError::check_output(&output)?;
 
         self.deserialize(output.stdout)
     }
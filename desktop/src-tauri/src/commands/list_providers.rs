@@ -0,0 +1,65 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use tauri::AppHandle;
+
+use super::{
+    config::{CommandConfig, DevpodCommandConfig, DevpodCommandError},
+    constants::{FLAG_OUTPUT_JSON, KLED_BINARY_NAME, KLED_COMMAND_LIST, KLED_COMMAND_PROVIDER},
+};
+
+// Only the fields the app needs from `provider list --output=json`
+#[derive(Debug, Deserialize)]
+pub struct ListedProvider {
+    #[serde(default)]
+    pub default: bool,
+}
+
+pub struct ListProvidersCommand {}
+impl ListProvidersCommand {
+    pub fn new() -> Self {
+        ListProvidersCommand {}
+    }
+
+    fn deserialize(
+        &self,
+        d: Vec<u8>,
+    ) -> Result<HashMap<String, ListedProvider>, DevpodCommandError> {
+        serde_json::from_slice(&d).map_err(DevpodCommandError::JsonParse)
+    }
+}
+impl DevpodCommandConfig<HashMap<String, ListedProvider>> for ListProvidersCommand {
+    fn config(&self) -> CommandConfig {
+        CommandConfig {
+            binary_name: KLED_BINARY_NAME,
+            args: vec![KLED_COMMAND_PROVIDER, KLED_COMMAND_LIST, FLAG_OUTPUT_JSON],
+        }
+    }
+
+    fn exec_blocking(
+        self,
+        app_handle: &AppHandle,
+    ) -> Result<HashMap<String, ListedProvider>, DevpodCommandError> {
+        let cmd = self.new_command(app_handle)?;
+
+        let output = tauri::async_runtime::block_on(async move { cmd.output().await })
+            .map_err(DevpodCommandError::from)?;
+        DevpodCommandError::check_output(&output)?;
+
+        self.deserialize(output.stdout)
+    }
+}
+
+impl ListProvidersCommand {
+    pub async fn exec(
+        self,
+        app_handle: &AppHandle,
+    ) -> Result<HashMap<String, ListedProvider>, DevpodCommandError> {
+        let cmd = self.new_command(app_handle)?;
+
+        let output = cmd.output().await.map_err(DevpodCommandError::from)?;
+        DevpodCommandError::check_output(&output)?;
+
+        self.deserialize(output.stdout)
+    }
+}
@@ -1,9 +1,10 @@
+use log::Level;
 use tauri::AppHandle;
 
 use crate::resource_watcher::Workspace;
 
 use super::{
-    config::{CommandConfig, DevpodCommandConfig, DevpodCommandError},
+    config::{output_logging_stderr, CommandConfig, DevpodCommandConfig, DevpodCommandError},
     constants::{KLED_BINARY_NAME, KLED_COMMAND_LIST, FLAG_OUTPUT_JSON},
 };
 
@@ -14,7 +15,7 @@ impl ListWorkspacesCommand {
     }
 
     fn deserialize(&self, d: Vec<u8>) -> Result<Vec<Workspace>, DevpodCommandError> {
-        serde_json::from_slice(&d).map_err(DevpodCommandError::Parse)
+        serde_json::from_slice(&d).map_err(DevpodCommandError::JsonParse)
     }
 }
 impl DevpodCommandConfig<Vec<Workspace>> for ListWorkspacesCommand {
@@ -29,18 +30,21 @@ impl DevpodCommandConfig<Vec<Workspace>> for ListWorkspacesCommand {
         let cmd = self.new_command(app_handle)?;
 
         let output = tauri::async_runtime::block_on(async move { cmd.output().await })
-            .map_err(|_| DevpodCommandError::Output)?;
+            .map_err(DevpodCommandError::from)?;
+        DevpodCommandError::check_output(&output)?;
 
         self.deserialize(output.stdout)
     }
 }
 
 impl ListWorkspacesCommand {
+    // Polled by the resource watcher, the CLI's stderr is progress noise and only goes to the log
     pub async fn exec(self, app_handle: &AppHandle) -> Result<Vec<Workspace>, DevpodCommandError> {
         let cmd = self.new_command(app_handle)?;
 
-        let output = cmd.output().await.map_err(|_| DevpodCommandError::Output)?;
+        let stdout =
+            output_logging_stderr(cmd, self.command_line(), KLED_BINARY_NAME, Level::Debug).await?;
 
-        self.deserialize(output.stdout)
+        self.deserialize(stdout)
     }
 }
@@ -0,0 +1,52 @@
+use tauri::AppHandle;
+
+use super::{
+    config::{CommandConfig, DevpodCommandConfig, DevpodCommandError},
+    constants::{KLED_BINARY_NAME, KLED_COMMAND_LOGS},
+};
+
+// Prints the logs the agent keeps inside the workspace
+pub struct LogsWorkspaceCommand {
+    workspace_id: String,
+}
+impl LogsWorkspaceCommand {
+    pub fn new(workspace_id: String) -> Self {
+        LogsWorkspaceCommand { workspace_id }
+    }
+
+    fn parse(&self, stdout: Vec<u8>) -> Vec<String> {
+        String::from_utf8_lossy(&stdout)
+            .lines()
+            .map(String::from)
+            .collect()
+    }
+}
+impl DevpodCommandConfig<Vec<String>> for LogsWorkspaceCommand {
+    fn config(&self) -> CommandConfig {
+        CommandConfig {
+            binary_name: KLED_BINARY_NAME,
+            args: vec![KLED_COMMAND_LOGS, &self.workspace_id],
+        }
+    }
+
+    fn exec_blocking(self, app_handle: &AppHandle) -> Result<Vec<String>, DevpodCommandError> {
+        let cmd = self.new_command(app_handle)?;
+
+        let output = tauri::async_runtime::block_on(async move { cmd.output().await })
+            .map_err(DevpodCommandError::from)?;
+        DevpodCommandError::check_output(&output)?;
+
+        Ok(self.parse(output.stdout))
+    }
+}
+
+impl LogsWorkspaceCommand {
+    pub async fn exec(self, app_handle: &AppHandle) -> Result<Vec<String>, DevpodCommandError> {
+        let cmd = self.new_command(app_handle)?;
+
+        let output = cmd.output().await.map_err(DevpodCommandError::from)?;
+        DevpodCommandError::check_output(&output)?;
+
+        Ok(self.parse(output.stdout))
+    }
+}
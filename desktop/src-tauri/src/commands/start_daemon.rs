@@ -1,8 +1,10 @@
-use tauri::AppHandle;
-use tauri_plugin_shell::process::Command;
+use tauri::{async_runtime::Receiver, AppHandle};
+use tauri_plugin_shell::process::{CommandChild, CommandEvent};
+
+use crate::logging;
 
 use super::{
-    config::{CommandConfig, DevpodCommandConfig, DevpodCommandError},
+    config::{spawn_tracked, CommandConfig, DevpodCommandConfig, DevpodCommandError},
     constants::{KLED_BINARY_NAME, KLED_COMMAND_PRO, KLED_COMMAND_DAEMON, KLED_COMMAND_START, FLAG_DEBUG, FLAG_HOST},
 };
 
@@ -29,7 +31,7 @@ impl DevpodCommandConfig<()> for StartDaemonCommand {
         let cmd = self.new_command(app_handle)?;
 
         tauri::async_runtime::block_on(async move { cmd.output().await })
-            .map_err(|_| DevpodCommandError::Output)?;
+            .map_err(DevpodCommandError::from)?;
 
         return Ok(());
     }
@@ -49,7 +51,15 @@ impl DevpodCommandConfig<()> for StartDaemonCommand {
 }
 
 impl StartDaemonCommand {
-    pub fn command(self, app_handle: &AppHandle) -> Result<Command, DevpodCommandError> {
-        return self.new_command(app_handle);
+    // The daemon keeps running, untrack it once it's stopped. Its output is copied to the CLI log.
+    pub fn spawn(
+        self,
+        app_handle: &AppHandle,
+    ) -> Result<(Receiver<CommandEvent>, CommandChild), DevpodCommandError> {
+        let command_line = self.command_line();
+        let (rx, child) = spawn_tracked(self.new_command(app_handle)?, command_line.clone())?;
+        let rx = logging::tee_cli_output(app_handle, &command_line, rx);
+
+        return Ok((rx, child));
     }
 }
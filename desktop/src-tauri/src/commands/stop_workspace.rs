@@ -0,0 +1,42 @@
+use tauri::AppHandle;
+
+use super::{
+    config::{CommandConfig, DevpodCommandConfig, DevpodCommandError},
+    constants::{KLED_BINARY_NAME, KLED_COMMAND_STOP},
+};
+
+pub struct StopWorkspaceCommand {
+    workspace_id: String,
+}
+impl StopWorkspaceCommand {
+    pub fn new(workspace_id: String) -> Self {
+        StopWorkspaceCommand { workspace_id }
+    }
+}
+impl DevpodCommandConfig<()> for StopWorkspaceCommand {
+    fn config(&self) -> CommandConfig {
+        CommandConfig {
+            binary_name: KLED_BINARY_NAME,
+            args: vec![KLED_COMMAND_STOP, &self.workspace_id],
+        }
+    }
+
+    fn exec_blocking(self, app_handle: &AppHandle) -> Result<(), DevpodCommandError> {
+        let cmd = self.new_command(app_handle)?;
+
+        let output = tauri::async_runtime::block_on(async move { cmd.output().await })
+            .map_err(DevpodCommandError::from)?;
+
+        DevpodCommandError::check_output(&output)
+    }
+}
+
+impl StopWorkspaceCommand {
+    pub async fn exec(self, app_handle: &AppHandle) -> Result<(), DevpodCommandError> {
+        let cmd = self.new_command(app_handle)?;
+
+        let output = cmd.output().await.map_err(DevpodCommandError::from)?;
+
+        DevpodCommandError::check_output(&output)
+    }
+}
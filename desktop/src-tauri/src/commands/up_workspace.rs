@@ -0,0 +1,88 @@
+use tauri::AppHandle;
+
+use super::{
+    config::{exec_with_progress, CommandConfig, DevpodCommandConfig, DevpodCommandError},
+    constants::{FLAG_IDE, FLAG_OPEN_IDE, KLED_BINARY_NAME, KLED_COMMAND_UP},
+};
+
+// IDEs the CLI can open a workspace in, matching the values accepted by `--ide`
+pub const KNOWN_IDES: &[&str] = &[
+    "none",
+    "vscode",
+    "vscode-insiders",
+    "openvscode",
+    "cursor",
+    "codium",
+    "zed",
+    "positron",
+    "fleet",
+    "jupyternotebook",
+    "rstudio",
+    "intellij",
+    "goland",
+    "pycharm",
+    "phpstorm",
+    "clion",
+    "webstorm",
+    "rider",
+    "rubymine",
+];
+
+pub struct UpWorkspaceCommand {
+    workspace_id: String,
+    ide_flag: String,
+}
+impl UpWorkspaceCommand {
+    pub fn new(workspace_id: String, ide: String) -> Self {
+        UpWorkspaceCommand {
+            workspace_id,
+            ide_flag: format!("{}={}", FLAG_IDE, ide),
+        }
+    }
+}
+impl DevpodCommandConfig<()> for UpWorkspaceCommand {
+    fn config(&self) -> CommandConfig {
+        CommandConfig {
+            binary_name: KLED_BINARY_NAME,
+            args: vec![
+                KLED_COMMAND_UP,
+                &self.workspace_id,
+                &self.ide_flag,
+                FLAG_OPEN_IDE,
+            ],
+        }
+    }
+
+    fn exec_blocking(self, app_handle: &AppHandle) -> Result<(), DevpodCommandError> {
+        let cmd = self.new_command(app_handle)?;
+
+        let output = tauri::async_runtime::block_on(async move { cmd.output().await })
+            .map_err(DevpodCommandError::from)?;
+
+        DevpodCommandError::check_output(&output)
+    }
+}
+
+impl UpWorkspaceCommand {
+    // Starts the workspace and opens the IDE, handing every output line to `on_progress`
+    pub async fn exec_with_progress<F>(
+        self,
+        app_handle: &AppHandle,
+        operation_id: Option<&str>,
+        on_progress: F,
+    ) -> Result<(), DevpodCommandError>
+    where
+        F: Fn(String),
+    {
+        let cmd = self.new_command(app_handle)?;
+
+        exec_with_progress(
+            cmd,
+            self.command_line(),
+            app_handle,
+            operation_id,
+            on_progress,
+        )
+        .await
+    }
+}
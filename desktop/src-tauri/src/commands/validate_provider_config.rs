@@ -0,0 +1,143 @@
+use std::{collections::HashMap, time::Duration};
+
+use serde::Serialize;
+use tauri::AppHandle;
+use tauri_plugin_shell::process::CommandEvent;
+
+use crate::{child_processes, util};
+
+use super::{
+    config::{spawn_tracked, CommandConfig, DevpodCommandConfig, DevpodCommandError},
+    constants::{
+        FLAG_DRY_RUN, FLAG_OPTION, KLED_BINARY_NAME, KLED_COMMAND_PROVIDER,
+        KLED_COMMAND_SET_OPTIONS,
+    },
+};
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProviderValidationResult {
+    pub valid: bool,
+    pub errors: Vec<String>,
+}
+
+pub struct ValidateProviderConfigCommand {
+    provider_id: String,
+    option_flags: Vec<String>,
+}
+impl ValidateProviderConfigCommand {
+    pub fn new(provider_id: String, options: HashMap<String, String>) -> Self {
+        let mut options: Vec<(String, String)> = options.into_iter().collect();
+        options.sort();
+
+        let option_flags = options
+            .into_iter()
+            .map(|(key, value)| format!("{}={}={}", FLAG_OPTION, key, value))
+            .collect();
+
+        ValidateProviderConfigCommand {
+            provider_id,
+            option_flags,
+        }
+    }
+
+    fn to_result(&self, success: bool, stderr: Vec<u8>) -> ProviderValidationResult {
+        if success {
+            return ProviderValidationResult {
+                valid: true,
+                errors: vec![],
+            };
+        }
+
+        let mut errors: Vec<String> = String::from_utf8_lossy(&stderr)
+            .lines()
+            .map(|line| line.trim().to_string())
+            .filter(|line| !line.is_empty())
+            .collect();
+        if errors.is_empty() {
+            errors.push("provider configuration is invalid".to_string());
+        }
+
+        ProviderValidationResult {
+            valid: false,
+            errors,
+        }
+    }
+}
+impl DevpodCommandConfig<ProviderValidationResult> for ValidateProviderConfigCommand {
+    fn config(&self) -> CommandConfig {
+        // `--dry-run` makes the CLI validate the options without persisting them
+        let mut args = vec![
+            KLED_COMMAND_PROVIDER,
+            KLED_COMMAND_SET_OPTIONS,
+            &self.provider_id,
+            FLAG_DRY_RUN,
+        ];
+        args.extend(self.option_flags.iter().map(|flag| flag.as_str()));
+
+        CommandConfig {
+            binary_name: KLED_BINARY_NAME,
+            args,
+        }
+    }
+
+    fn exec_blocking(
+        self,
+        app_handle: &AppHandle,
+    ) -> Result<ProviderValidationResult, DevpodCommandError> {
+        let cmd = self.new_command(app_handle)?;
+
+        let output = tauri::async_runtime::block_on(async move { cmd.output().await })
+            .map_err(DevpodCommandError::from)?;
+
+        Ok(self.to_result(output.status.success(), output.stderr))
+    }
+}
+
+impl ValidateProviderConfigCommand {
+    pub async fn exec(
+        self,
+        app_handle: &AppHandle,
+    ) -> Result<ProviderValidationResult, DevpodCommandError> {
+        let cmd = self.new_command(app_handle)?;
+
+        let output = cmd.output().await.map_err(DevpodCommandError::from)?;
+
+        Ok(self.to_result(output.status.success(), output.stderr))
+    }
+
+    // Like `exec`, but kills the CLI together with everything it started if it doesn't finish
+    // within `timeout`, e.g. when the provider hangs on an unreachable host
+    pub async fn exec_with_timeout(
+        self,
+        app_handle: &AppHandle,
+        timeout: Duration,
+    ) -> Result<ProviderValidationResult, DevpodCommandError> {
+        let cmd = self.new_command(app_handle)?;
+        let (mut rx, child) = spawn_tracked(cmd, self.command_line())?;
+        let pid = child.pid();
+
+        let mut stderr = vec![];
+        let run = async {
+            let mut code = None;
+            while let Some(event) = rx.recv().await {
+                match event {
+                    CommandEvent::Stderr(line) => stderr.extend(line),
+                    CommandEvent::Terminated(payload) => code = payload.code,
+                    _ => {}
+                }
+            }
+            code
+        };
+        let result = tokio::time::timeout(timeout, run).await;
+        if result.is_err() {
+            util::kill_process_tree(pid);
+        }
+        child_processes::untrack(pid);
+
+        match result {
+            Ok(code) => Ok(self.to_result(code == Some(0), stderr)),
+            Err(_) => Err(DevpodCommandError::Timeout),
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use tauri::AppHandle;
+
+use super::{
+    config::{CommandConfig, DevpodCommandConfig, DevpodCommandError},
+    constants::{KLED_BINARY_NAME, KLED_COMMAND_VERSION},
+};
+
+pub struct VersionCommand {}
+impl VersionCommand {
+    pub fn new() -> Self {
+        VersionCommand {}
+    }
+
+    fn parse(&self, stdout: Vec<u8>) -> String {
+        String::from_utf8_lossy(&stdout).trim().to_string()
+    }
+}
+impl DevpodCommandConfig<String> for VersionCommand {
+    fn config(&self) -> CommandConfig {
+        CommandConfig {
+            binary_name: KLED_BINARY_NAME,
+            args: vec![KLED_COMMAND_VERSION],
+        }
+    }
+
+    fn exec_blocking(self, app_handle: &AppHandle) -> Result<String, DevpodCommandError> {
+        let cmd = self.new_command(app_handle)?;
+
+        let output = tauri::async_runtime::block_on(async move { cmd.output().await })
+            .map_err(DevpodCommandError::from)?;
+        DevpodCommandError::check_output(&output)?;
+
+        Ok(self.parse(output.stdout))
+    }
+}
+
+impl VersionCommand {
+    pub async fn exec(self, app_handle: &AppHandle) -> Result<String, DevpodCommandError> {
+        let cmd = self.new_command(app_handle)?;
+
+        let output = cmd.output().await.map_err(DevpodCommandError::from)?;
+        DevpodCommandError::check_output(&output)?;
+
+        Ok(self.parse(output.stdout))
+    }
+}
@@ -0,0 +1,65 @@
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+
+use super::{
+    config::{CommandConfig, DevpodCommandConfig, DevpodCommandError},
+    constants::{FLAG_OUTPUT_JSON, KLED_BINARY_NAME, KLED_COMMAND_STATUS},
+};
+
+// Mirrors the states reported by `status --output=json`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WorkspaceState {
+    Running,
+    Busy,
+    Stopped,
+    NotFound,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceStatus {
+    pub id: String,
+    pub state: WorkspaceState,
+}
+
+pub struct WorkspaceStatusCommand {
+    workspace_id: String,
+}
+impl WorkspaceStatusCommand {
+    pub fn new(workspace_id: String) -> Self {
+        WorkspaceStatusCommand { workspace_id }
+    }
+
+    fn deserialize(&self, d: Vec<u8>) -> Result<WorkspaceStatus, DevpodCommandError> {
+        serde_json::from_slice(&d).map_err(DevpodCommandError::JsonParse)
+    }
+}
+impl DevpodCommandConfig<WorkspaceStatus> for WorkspaceStatusCommand {
+    fn config(&self) -> CommandConfig {
+        CommandConfig {
+            binary_name: KLED_BINARY_NAME,
+            args: vec![KLED_COMMAND_STATUS, &self.workspace_id, FLAG_OUTPUT_JSON],
+        }
+    }
+
+    fn exec_blocking(self, app_handle: &AppHandle) -> Result<WorkspaceStatus, DevpodCommandError> {
+        let cmd = self.new_command(app_handle)?;
+
+        let output = tauri::async_runtime::block_on(async move { cmd.output().await })
+            .map_err(DevpodCommandError::from)?;
+        DevpodCommandError::check_output(&output)?;
+
+        self.deserialize(output.stdout)
+    }
+}
+
+impl WorkspaceStatusCommand {
+    pub async fn exec(self, app_handle: &AppHandle) -> Result<WorkspaceStatus, DevpodCommandError> {
+        let cmd = self.new_command(app_handle)?;
+
+        let output = cmd.output().await.map_err(DevpodCommandError::from)?;
+        DevpodCommandError::check_output(&output)?;
+
+        self.deserialize(output.stdout)
+    }
+}
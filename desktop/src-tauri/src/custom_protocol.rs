@@ -1,6 +1,6 @@
 use crate::ui_messages::{
-    send_ui_message, ImportWorkspaceMsg, OpenWorkspaceMsg, SetupProMsg, ShowToastMsg, ToastStatus,
-    UiMessage,
+    dispatch, send_ui_message, ImportWorkspaceMsg, OpenWorkspaceMsg, SetupProMsg, ShowToastMsg,
+    ToastStatus, UiMessage,
 };
 use crate::AppState;
 use log::{error, info, warn};
@@ -13,12 +13,19 @@ use thiserror::Error;
 use url::Url;
 
 // Should match the one from "tauri.config.json" and "Info.plist"
-const APP_IDENTIFIER: &str = "sh.loft.devpod";
-const APP_URL_SCHEME: &str = "devpod";
+pub const APP_IDENTIFIER: &str = "sh.loft.devpod";
+pub const APP_URL_SCHEME: &str = "kled";
+// Schemes we still accept from before the rename, they are routed like `APP_URL_SCHEME`
+pub const LEGACY_APP_URL_SCHEMES: [&str; 1] = ["devpod"];
 
-pub struct CustomProtocol;
+pub struct CustomProtocol {
+    schemes: Vec<String>,
+}
 
 pub struct Request {
+    // Always `APP_URL_SCHEME`, legacy schemes are normalized and unknown ones fail to parse
+    #[allow(dead_code)]
+    scheme: String,
     host: String,
     query: String,
 }
@@ -28,6 +35,15 @@ pub struct UrlParser {}
 impl UrlParser {
     const ALLOWED_METHODS: [&'static str; 3] = ["open", "import", "pro"];
 
+    // Maps legacy schemes onto the canonical one, `None` for schemes we don't handle
+    fn normalize_scheme(scheme: &str) -> Option<&'static str> {
+        if scheme == APP_URL_SCHEME || LEGACY_APP_URL_SCHEMES.contains(&scheme) {
+            return Some(APP_URL_SCHEME);
+        }
+
+        None
+    }
+
     fn get_host(url: &Url) -> String {
         url.host_str().unwrap_or("no host").to_string()
     }
@@ -46,12 +62,15 @@ impl UrlParser {
 
     pub fn parse(url_scheme: &str) -> Result<Request, ParseError> {
         let url = Self::parse_raw_url(url_scheme)?;
+        let scheme = Self::normalize_scheme(url.scheme())
+            .ok_or_else(|| ParseError::UnsupportedScheme(url.scheme().to_string()))?;
         let host_str = Self::get_host(&url);
 
         if !Self::is_allowed_method(&host_str) {
             return Err(ParseError::UnsupportedHost(host_str));
         }
         return Ok(Request {
+            scheme: scheme.to_string(),
             host: host_str,
             query: Self::parse_query(&url),
         });
@@ -155,8 +174,10 @@ impl ProHandler {
 }
 
 impl CustomProtocol {
-    pub fn init() -> Self {
-        Self {}
+    pub fn init(schemes: &[&str]) -> Self {
+        Self {
+            schemes: schemes.iter().map(|scheme| scheme.to_string()).collect(),
+        }
     }
 
     pub fn forward_deep_link() {
@@ -210,73 +231,83 @@ impl CustomProtocol {
 
     pub fn setup(&self, app: AppHandle) {
         let deep_link = app.deep_link();
-        
-        if let Err(error) = deep_link.register(APP_URL_SCHEME) {
-            #[cfg(target_os = "linux")]
-            {
-                let is_flatpak = env::var("FLATPAK_ID").is_ok();
-
-                if !is_flatpak {
-                    let msg = "Either update-desktop-database or xdg-mime are missing. Please make sure they are available on your system";
-                    log::warn!("Custom protocol setup failed; {}: {}", msg, error);
-
-                    tauri::async_runtime::block_on(async {
-                        let app_state = app.state::<AppState>();
-                        let show_toast_msg = ShowToastMsg::new(
-                            "Custom protocol handling needs to be configured".to_string(),
-                            msg.to_string(),
-                            ToastStatus::Warning,
+
+        for scheme in self.schemes.iter() {
+            if let Err(error) = deep_link.register(scheme) {
+                #[cfg(target_os = "linux")]
+                {
+                    let is_flatpak = env::var("FLATPAK_ID").is_ok();
+
+                    if !is_flatpak {
+                        let msg = "Either update-desktop-database or xdg-mime are missing. Please make sure they are available on your system";
+                        log::warn!(
+                            "Custom protocol setup for {} failed; {}: {}",
+                            scheme,
+                            msg,
+                            error
                         );
-                        if let Err(err) = app_state
-                            .ui_messages
-                            .send(UiMessage::ShowToast(show_toast_msg))
-                            .await
-                        {
-                            log::error!(
-                                "Failed to broadcast show toast message: {:?}, {}",
-                                err.0,
-                                err
+
+                        tauri::async_runtime::block_on(async {
+                            let app_state = app.state::<AppState>();
+                            let show_toast_msg = ShowToastMsg::new(
+                                "Custom protocol handling needs to be configured".to_string(),
+                                msg.to_string(),
+                                ToastStatus::Warning,
                             );
-                        };
-                    })
+                            dispatch(&app_state.ui_messages, UiMessage::ShowToast(show_toast_msg))
+                                .await;
+                        });
+
+                        // one toast is enough, the remaining schemes fail for the same reason
+                        break;
+                    }
                 }
             }
         }
-        
+
         let app_handle = app.clone();
         deep_link.on_open_url(move |event| {
             for url_scheme in event.urls() {
                 tauri::async_runtime::block_on(async {
                     info!("App opened with URL: {:?}", url_scheme.to_string());
 
-                    let request = UrlParser::parse(&url_scheme.to_string());
-                    let app_state = app_handle.state::<AppState>();
-                    if let Err(err) = request {
-                        warn!("Failed to broadcast custom protocol message: {:?}", err);
-                        return;
-                    }
-                    let request = request.unwrap();
-
-                    match request.host.as_str() {
-                        "open" => {
-                            let msg = CustomProtocol::parse(&request);
-                            OpenHandler::handle(msg, app_state).await
-                        }
-                        "import" => {
-                            let msg = CustomProtocol::parse(&request);
-                            ImportHandler::handle(msg, app_state).await
+                    let request = match UrlParser::parse(&url_scheme.to_string()) {
+                        Ok(request) => request,
+                        Err(ParseError::UnsupportedScheme(scheme)) => {
+                            warn!("Received URL with unknown scheme: {}", scheme);
+                            return;
                         }
-                        "pro" => {
-                            let msg = CustomProtocol::parse(&request);
-                            ProHandler::handle(msg, app_state).await
+                        Err(err) => {
+                            warn!("Failed to broadcast custom protocol message: {:?}", err);
+                            return;
                         }
-                        _ => {}
-                    }
+                    };
+                    let app_state = app_handle.state::<AppState>();
+
+                    Self::handle_app_request(&request, app_state).await;
                 });
             }
         });
     }
 
+    async fn handle_app_request(request: &Request, app_state: State<'_, AppState>) {
+        match request.host.as_str() {
+            "open" => {
+                let msg = CustomProtocol::parse(request);
+                OpenHandler::handle(msg, app_state).await
+            }
+            "import" => {
+                let msg = CustomProtocol::parse(request);
+                ImportHandler::handle(msg, app_state).await
+            }
+            "pro" => {
+                let msg = CustomProtocol::parse(request);
+                ProHandler::handle(msg, app_state).await
+            }
+            _ => {}
+        }
+    }
+
     fn parse<'a, Msg>(request: &'a Request) -> Result<Msg, ParseError>
     where
         Msg: Deserialize<'a>,
@@ -288,6 +319,8 @@ impl CustomProtocol {
 
 #[derive(Error, Debug, Clone, Serialize)]
 pub enum ParseError {
+    #[error("Unsupported scheme: {0}")]
+    UnsupportedScheme(String),
     #[error("Unsupported host: {0}")]
     UnsupportedHost(String),
     #[error("Unsupported query arguments: {0}")]
@@ -308,6 +341,30 @@ mod tests {
             assert_eq!(request.query, "workspace=workspace".to_string());
         }
 
+        #[test]
+        fn should_normalize_legacy_scheme() {
+            let url_str = "devpod://open?workspace=workspace";
+            let request = UrlParser::parse(&url_str).unwrap();
+
+            assert_eq!(request.scheme, APP_URL_SCHEME.to_string());
+        }
+
+        #[test]
+        fn should_parse_canonical_scheme() {
+            let url_str = "kled://open?workspace=workspace";
+            let request = UrlParser::parse(&url_str).unwrap();
+
+            assert_eq!(request.scheme, APP_URL_SCHEME.to_string());
+            assert_eq!(request.host, "open".to_string());
+        }
+
+        #[test]
+        #[should_panic]
+        fn should_fail_on_unknown_scheme() {
+            let url_str = "https://open?workspace=workspace";
+            let _ = UrlParser::parse(&url_str).unwrap();
+        }
+
         #[test]
         fn should_parse_with_empty_query() {
             let url_str = "devpod://import";
@@ -2,15 +2,134 @@ use serde::{Deserialize, Serialize};
 use ts_rs::TS;
 use lazy_static::lazy_static;
 use tauri::image::Image;
+use tauri::Manager;
+use thiserror::Error;
+use crate::{
+    system_tray::{load_icon, STATUS_ICON_SIZE},
+    util, AppHandle, AppState,
+};
 
 pub mod client;
 
+#[derive(Error, Debug)]
+pub enum DaemonError {
+    #[error("pro instance {0} not found")]
+    InstanceNotFound(String),
+    #[error("no daemon running for pro instance {0}")]
+    NotRunning(String),
+    #[error("daemon log {0} not found")]
+    LogNotFound(String),
+    #[error("unable to read daemon log")]
+    Io(#[from] std::io::Error),
+    #[error("daemon did not respond within {0}ms")]
+    Timeout(u128),
+    #[error("daemon is unreachable: {0}")]
+    Unreachable(String),
+}
+impl serde::Serialize for DaemonError {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(self.to_string().as_ref())
+    }
+}
+
+// Returns the URL the user has to open to log back in to the pro instance
+#[tauri::command]
+pub async fn get_daemon_login_url(app_handle: AppHandle, host: String) -> Result<String, DaemonError> {
+    let state = app_handle.state::<AppState>();
+    let mut pro_state = state.pro.write().await;
+    let daemon = pro_state
+        .find_instance_mut(host.clone())
+        .ok_or_else(|| DaemonError::InstanceNotFound(host.clone()))?
+        .daemon_mut()
+        .ok_or_else(|| DaemonError::NotRunning(host.clone()))?;
+
+    if let Some(url) = daemon.login_url() {
+        return Ok(url.clone());
+    }
+
+    return Ok(daemon.refresh_login_url(&host).await.clone());
+}
+
+// Whether the daemon of the pro instance needs the user to log in again, with the account and
+// reason so the prompt can say what to re-authenticate
+#[tauri::command]
+pub async fn get_daemon_login_status(
+    app_handle: AppHandle,
+    host: String,
+) -> Result<DaemonLoginStatus, DaemonError> {
+    let state = app_handle.state::<AppState>();
+    let pro_state = state.pro.read().await;
+    let daemon = pro_state
+        .find_instance(host.clone())
+        .ok_or_else(|| DaemonError::InstanceNotFound(host.clone()))?
+        .daemon()
+        .as_ref()
+        .ok_or_else(|| DaemonError::NotRunning(host.clone()))?;
+    let status = daemon.status();
+
+    Ok(DaemonLoginStatus {
+        host,
+        login_required: status.login_required,
+        provider: status.login_provider.clone(),
+        reason: status.login_reason.clone(),
+        login_url: daemon.login_url().cloned(),
+    })
+}
+
+// Returns the last `lines` lines of the daemon log for a quick glance, oldest first
+#[tauri::command]
+pub async fn get_daemon_log_tail(
+    app_handle: AppHandle,
+    host: String,
+    lines: usize,
+) -> Result<Vec<String>, DaemonError> {
+    let state = app_handle.state::<AppState>();
+    let log_path = {
+        let pro_state = state.pro.read().await;
+        pro_state
+            .find_instance(host.clone())
+            .ok_or_else(|| DaemonError::InstanceNotFound(host.clone()))?
+            .daemon()
+            .as_ref()
+            .ok_or_else(|| DaemonError::NotRunning(host.clone()))?
+            .log_path()
+            .cloned()
+            .ok_or_else(|| DaemonError::LogNotFound(host.clone()))?
+    };
+
+    match util::read_last_lines(&log_path, lines) {
+        Ok(tail) => Ok(tail),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Err(DaemonError::LogNotFound(
+            log_path.to_string_lossy().to_string(),
+        )),
+        Err(err) => Err(DaemonError::Io(err)),
+    }
+}
+
 #[derive(Debug, Default, Serialize, Deserialize, Eq, PartialEq, TS)]
 #[serde(rename_all = "camelCase", default)]
 #[ts(export)]
 pub struct DaemonStatus {
     pub state: DaemonState,
     pub login_required: bool,
+    // Which account needs to log in again and why, only set while login is required. Filled
+    // in with the pro instance's provider if the daemon doesn't name one.
+    pub login_provider: Option<String>,
+    pub login_reason: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DaemonLoginStatus {
+    host: String,
+    login_required: bool,
+    provider: Option<String>,
+    reason: Option<String>,
+    // Where to log back in, known once the daemon was asked for it
+    login_url: Option<String>,
 }
 
 #[derive(Debug, Default, Eq, PartialEq, Serialize, Deserialize, TS)]
@@ -25,22 +144,31 @@ pub enum DaemonState {
 impl DaemonState {
     pub fn running_icon() -> Image<'static> {
         lazy_static! {
-            static ref RUNNING_ICON: Image<'static> =
-                Image::new(include_bytes!("../icons/running.png"), 16, 16);
+            static ref RUNNING_ICON: Image<'static> = load_icon(
+                "running",
+                include_bytes!("../icons/running.png"),
+                STATUS_ICON_SIZE
+            );
         }
         return RUNNING_ICON.clone();
     }
     pub fn stopped_icon() -> Image<'static> {
         lazy_static! {
-            static ref STOPPED_ICON: Image<'static> =
-                Image::new(include_bytes!("../icons/stopped.png"), 16, 16);
+            static ref STOPPED_ICON: Image<'static> = load_icon(
+                "stopped",
+                include_bytes!("../icons/stopped.png"),
+                STATUS_ICON_SIZE
+            );
         }
         return STOPPED_ICON.clone();
     }
     pub fn pending_icon() -> Image<'static> {
         lazy_static! {
-            static ref PENDING_ICON: Image<'static> =
-                Image::new(include_bytes!("../icons/pending.png"), 16, 16);
+            static ref PENDING_ICON: Image<'static> = load_icon(
+                "pending",
+                include_bytes!("../icons/pending.png"),
+                STATUS_ICON_SIZE
+            );
         }
         return PENDING_ICON.clone();
     }
@@ -1,4 +1,5 @@
-use crate::daemon::DaemonStatus;
+use crate::daemon::{DaemonError, DaemonStatus};
+use crate::{AppHandle, AppState};
 use anyhow::anyhow;
 use bytes::{Buf, Bytes};
 // use core::time;
@@ -6,29 +7,94 @@ use http::Uri;
 use http_body_util::{BodyExt, Empty};
 use hyper::{
     // body::{Body, Incoming},
-    // client::conn::http1::SendRequest,
+    client::conn::http1::SendRequest,
     header,
 };
-use log::error;
+use log::{debug, error};
 use pin_project_lite::pin_project;
-use serde::de::DeserializeOwned;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::{
     io,
     path::Path,
     pin::Pin,
+    sync::Arc,
     task::{Context, Poll},
+    time::{Duration, Instant},
 };
-use tokio::io::AsyncWriteExt;
+use tauri::Manager;
+use tokio::{io::AsyncWriteExt, sync::Mutex};
+
+// How long a request, e.g. a ping, may take before the daemon is considered unresponsive
+const PING_TIMEOUT: Duration = Duration::from_secs(5);
+// Warming retries with a doubling delay while the daemon is still starting
+const WARM_ATTEMPTS: u32 = 6;
+const WARM_INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+
+#[derive(Deserialize)]
+struct LoginUrlResponse {
+    url: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DaemonLatency {
+    round_trip_ms: u128,
+    // Rolling average over the most recent pings, for the status indicator
+    average_ms: u128,
+}
 
 pub type Request = hyper::Request<axum::body::Body>;
 pub type Response = hyper::Response<hyper::body::Incoming>;
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Client {
     socket: String,
+    // Kept-alive connection for `get`, shared by all clones so the status polling of the
+    // resource watcher keeps it open for everyone else
+    conn: Arc<Mutex<Option<SendRequest<Empty<Bytes>>>>>,
 }
 impl Client {
     pub fn new(socket: String) -> Client {
-        return Client { socket };
+        return Client {
+            socket,
+            conn: Arc::new(Mutex::new(None)),
+        };
+    }
+
+    // Opens the connection ahead of the first request, retrying with backoff while the daemon
+    // isn't up yet
+    pub async fn warm(&self) -> anyhow::Result<()> {
+        let mut backoff = WARM_INITIAL_BACKOFF;
+        let mut attempt = 1;
+        loop {
+            match self.status().await {
+                Ok(_) => return Ok(()),
+                Err(err) if attempt == WARM_ATTEMPTS => return Err(err),
+                Err(_) => {}
+            }
+
+            debug!(
+                "daemon at {} not ready yet, retrying in {:?}",
+                self.socket, backoff
+            );
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+            attempt += 1;
+        }
+    }
+
+    async fn connect(&self) -> anyhow::Result<SendRequest<Empty<Bytes>>> {
+        let addr = Path::new(&self.socket);
+        let handshake_stream = HandshakeStream::connect(&addr).await?;
+        let io = TokioIo::new(handshake_stream);
+
+        let (sender, conn) = hyper::client::conn::http1::handshake(io).await?;
+        tokio::task::spawn(async move {
+            if let Err(err) = conn.await {
+                error!("Connection failed: {:?}", err);
+            }
+        });
+
+        return Ok(sender);
     }
 
     pub async fn status(&self) -> anyhow::Result<DaemonStatus> {
@@ -36,6 +102,18 @@ impl Client {
         return Ok(res);
     }
 
+    // Times a status request, the cheapest request the daemon answers
+    pub async fn ping(&self) -> anyhow::Result<Duration> {
+        let start = Instant::now();
+        self.status().await?;
+        return Ok(start.elapsed());
+    }
+
+    pub async fn login_url(&self) -> anyhow::Result<String> {
+        let res = self.get::<LoginUrlResponse>("/login-url").await?;
+        return Ok(res.url);
+    }
+
     pub async fn proxy(&self, mut req: Request) -> anyhow::Result<Response> {
         let addr = Path::new(&self.socket);
         let handshake_stream = HandshakeStream::connect(&addr).await?;
@@ -68,23 +146,42 @@ impl Client {
     }
 
     async fn get<T: DeserializeOwned>(&self, target_path: &str) -> anyhow::Result<T> {
-        let addr = Path::new(&self.socket);
-        let handshake_stream = HandshakeStream::connect(&addr).await?;
-        let io = TokioIo::new(handshake_stream);
-
-        let (mut sender, conn) = hyper::client::conn::http1::handshake(io).await?;
-        tokio::task::spawn(async move {
-            if let Err(err) = conn.await {
-                error!("Connection failed: {:?}", err);
-            }
-        });
-
         let req = hyper::Request::builder()
             .uri(format!("http://localclient.devpod{}", target_path))
             .header(hyper::header::HOST, "sh.loft.devpod.desktop")
             .body(Empty::<Bytes>::new())?;
 
-        let res = sender.send_request(req).await?;
+        // The connection is taken out of the shared slot while it's in use, so a daemon that
+        // hangs only blocks this request. Concurrent requests open a connection of their own.
+        let cached = self.conn.lock().await.take();
+        let deadline = tokio::time::Instant::now() + PING_TIMEOUT;
+        let timed_out = || {
+            anyhow!(
+                "request to {} timed out after {:?}",
+                target_path,
+                PING_TIMEOUT
+            )
+        };
+
+        let (sender, res) = tokio::time::timeout_at(deadline, async {
+            let mut sender = match cached {
+                Some(sender) if !sender.is_closed() => sender,
+                _ => self.connect().await?,
+            };
+            sender.ready().await?;
+            let res = sender.send_request(req).await?;
+            anyhow::Ok((sender, res))
+        })
+        .await
+        .map_err(|_| timed_out())??;
+        // a failed or timed out request drops the connection, the next one reconnects
+        {
+            let mut conn = self.conn.lock().await;
+            if conn.is_none() {
+                *conn = Some(sender);
+            }
+        }
+
         if res.status() != http::StatusCode::OK {
             return Err(anyhow!(
                 "request to {} failed: {}",
@@ -93,13 +190,114 @@ impl Client {
             ));
         }
 
-        let body = res.collect().await?.aggregate();
+        let body = tokio::time::timeout_at(deadline, res.collect())
+            .await
+            .map_err(|_| timed_out())??
+            .aggregate();
         let out: T = serde_json::from_reader(body.reader())?;
 
         return Ok(out);
     }
 }
 
+// Measures how long the daemon of a pro instance takes to answer, to tell a slow daemon apart
+// from a slow UI
+#[tauri::command]
+pub async fn ping_daemon(
+    app_handle: AppHandle,
+    host: String,
+) -> Result<DaemonLatency, DaemonError> {
+    let state = app_handle.state::<AppState>();
+    // don't hold the lock while waiting on the daemon
+    let client = {
+        let pro_state = state.pro.read().await;
+        pro_state
+            .find_instance(host.clone())
+            .ok_or_else(|| DaemonError::InstanceNotFound(host.clone()))?
+            .daemon()
+            .as_ref()
+            .ok_or_else(|| DaemonError::NotRunning(host.clone()))?
+            .client()
+            .clone()
+    };
+
+    let round_trip = match tokio::time::timeout(PING_TIMEOUT, client.ping()).await {
+        Ok(Ok(round_trip)) => round_trip,
+        Ok(Err(err)) => return Err(DaemonError::Unreachable(err.to_string())),
+        Err(_) => return Err(DaemonError::Timeout(PING_TIMEOUT.as_millis())),
+    };
+
+    let mut pro_state = state.pro.write().await;
+    let average = pro_state
+        .find_instance_mut(host.clone())
+        .and_then(|instance| instance.daemon_mut())
+        .map(|daemon| daemon.record_latency(round_trip))
+        .unwrap_or(round_trip);
+
+    return Ok(DaemonLatency {
+        round_trip_ms: round_trip.as_millis(),
+        average_ms: average.as_millis(),
+    });
+}
+
+// Connects to the daemon of the pro instance now instead of on the first request, waiting for
+// it to come up if it's still starting
+#[tauri::command]
+pub async fn warm_daemon(app_handle: AppHandle, host: String) -> Result<(), DaemonError> {
+    let state = app_handle.state::<AppState>();
+    let client = {
+        let pro_state = state.pro.read().await;
+        pro_state
+            .find_instance(host.clone())
+            .ok_or_else(|| DaemonError::InstanceNotFound(host.clone()))?
+            .daemon()
+            .as_ref()
+            .ok_or_else(|| DaemonError::NotRunning(host.clone()))?
+            .client()
+            .clone()
+    };
+
+    return client
+        .warm()
+        .await
+        .map_err(|err| DaemonError::Unreachable(err.to_string()));
+}
+
+// Warms the daemons that exist once the pro instances are loaded, so the first dashboard
+// interaction after launch doesn't wait for a connection. Instances show up a few seconds after
+// launch, so this backs off until there are some.
+pub fn setup(app_handle: &AppHandle) {
+    let app_handle = app_handle.clone();
+    tauri::async_runtime::spawn(async move {
+        let mut backoff = WARM_INITIAL_BACKOFF;
+        for _ in 0..WARM_ATTEMPTS {
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+
+            let clients: Vec<Client> = {
+                let state = app_handle.state::<AppState>();
+                let pro_state = state.pro.read().await;
+                pro_state
+                    .instances()
+                    .iter()
+                    .filter_map(|instance| instance.daemon().as_ref())
+                    .map(|daemon| daemon.client().clone())
+                    .collect()
+            };
+            if clients.is_empty() {
+                continue;
+            }
+
+            for client in clients {
+                if let Err(err) = client.warm().await {
+                    debug!("Failed to warm daemon connection: {}", err);
+                }
+            }
+            return;
+        }
+    });
+}
+
 const DEVPOD_PREFIX_BYTE: u8 = 0x01;
 
 pub struct HandshakeStream {
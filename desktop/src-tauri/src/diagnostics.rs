@@ -0,0 +1,250 @@
+use crate::{
+    action_logs, app_info, command_env,
+    commands::version::VersionCommand,
+    fix_env::{self, PathEntrySource},
+    install_cli,
+    resource_watcher::{Daemon, Identifiable},
+    settings, spacetime_server, AppHandle, AppState,
+};
+use log::{error, info};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use tauri::Manager;
+use tauri_plugin_clipboard_manager::ClipboardExt;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum DiagnosticsError {
+    #[error("unable to write diagnostics to clipboard")]
+    Clipboard(#[source] tauri_plugin_clipboard_manager::Error),
+}
+impl serde::Serialize for DiagnosticsError {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(self.to_string().as_ref())
+    }
+}
+
+// Parts of variable names that usually hold credentials, compared case insensitively
+const SECRET_NAME_PARTS: [&str; 8] = [
+    "TOKEN",
+    "SECRET",
+    "PASSWORD",
+    "PASSWD",
+    "KEY",
+    "CREDENTIAL",
+    "AUTH",
+    "COOKIE",
+];
+const REDACTED: &str = "<redacted>";
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigPath {
+    // None if the location couldn't be resolved on this system
+    path: Option<String>,
+    exists: bool,
+}
+impl ConfigPath {
+    fn new<E: std::fmt::Display>(path: Result<PathBuf, E>) -> Self {
+        match path {
+            Ok(path) => ConfigPath {
+                exists: path.exists(),
+                path: Some(path.to_string_lossy().to_string()),
+            },
+            Err(err) => {
+                error!("Failed to resolve config path: {}", err);
+                ConfigPath {
+                    path: None,
+                    exists: false,
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigPaths {
+    settings: ConfigPath,
+    command_env: ConfigPath,
+    action_logs: ConfigPath,
+    app_logs: ConfigPath,
+    // Home of the CLI, providers and daemon sockets and logs live below it
+    daemon_data: ConfigPath,
+    spacetime_data: ConfigPath,
+}
+
+// Where the app and the CLI keep their files on this machine
+#[tauri::command]
+pub fn get_config_paths(app_handle: AppHandle) -> ConfigPaths {
+    let app_data_dir = app_handle.path().app_data_dir();
+    let store_path = |name: &str| {
+        app_data_dir
+            .as_ref()
+            .map(|dir| dir.join(name))
+            .map_err(|err| err.to_string())
+    };
+
+    ConfigPaths {
+        settings: ConfigPath::new(store_path(settings::SETTINGS_FILE_NAME)),
+        command_env: ConfigPath::new(store_path(command_env::COMMAND_ENV_FILE_NAME)),
+        action_logs: ConfigPath::new(action_logs::get_actions_dir(&app_handle)),
+        app_logs: ConfigPath::new(app_handle.path().app_log_dir()),
+        daemon_data: ConfigPath::new(Daemon::get_home().map(PathBuf::from)),
+        spacetime_data: ConfigPath::new(spacetime_server::data_dir(&app_handle)),
+    }
+}
+
+// Assembles the diagnostics bundle, writes it to the clipboard and returns it for display
+#[tauri::command]
+pub async fn copy_diagnostics(app_handle: AppHandle) -> Result<String, DiagnosticsError> {
+    let diagnostics = collect(&app_handle).await;
+
+    app_handle
+        .clipboard()
+        .write_text(diagnostics.clone())
+        .map_err(|err| {
+            error!("Failed to copy diagnostics to clipboard: {}", err);
+            DiagnosticsError::Clipboard(err)
+        })?;
+    info!("Copied diagnostics to clipboard");
+
+    Ok(diagnostics)
+}
+
+pub async fn collect(app_handle: &AppHandle) -> String {
+    let app_info = app_info::collect(app_handle);
+    let cli_version = match VersionCommand::new().exec(app_handle).await {
+        Ok(version) => version,
+        Err(err) => format!("unknown ({})", err),
+    };
+
+    let mut lines = vec![
+        format!(
+            "App: {} {} ({})",
+            app_info.name, app_info.version, app_info.commit_hash
+        ),
+        format!("Tauri: {}", app_info.tauri_version),
+        format!("CLI: {}", cli_version),
+        format!(
+            "OS: {} {} ({})",
+            app_info.os.platform, app_info.os.version, app_info.os.arch
+        ),
+    ];
+
+    let state = app_handle.state::<AppState>();
+    let pro = state.pro.read().await;
+    if pro.instances().is_empty() {
+        lines.push("Daemons: none".to_string());
+    } else {
+        lines.push("Daemons:".to_string());
+        for instance in pro.instances() {
+            let status = match instance.daemon() {
+                Some(daemon) => format!(
+                    "{:?}, login required: {}",
+                    daemon.status().state,
+                    daemon.status().login_required
+                ),
+                None => "not started".to_string(),
+            };
+            lines.push(format!("  {}: {}", instance.id(), status));
+        }
+    }
+
+    let recent_errors = state.recent_errors.lock().unwrap().len();
+    lines.push(format!("Recent errors: {}", recent_errors));
+
+    lines.join("\n")
+}
+
+// Everything that decides how the CLI behaves when the app runs it, for bugs that only show up
+// in the app but not in the terminal. Returns a report to attach to a bug, with values that look
+// like credentials redacted.
+#[tauri::command]
+pub async fn capture_env_diagnostic(app_handle: AppHandle) -> String {
+    let mut lines = vec!["Environment:".to_string()];
+    let env: BTreeMap<String, String> = std::env::vars().collect();
+    for (key, value) in &env {
+        lines.push(format!("  {}={}", key, redact(key, value)));
+    }
+
+    lines.push("Command env overrides:".to_string());
+    let overlay: BTreeMap<String, String> = command_env::overlay(&app_handle).into_iter().collect();
+    if overlay.is_empty() {
+        lines.push("  none".to_string());
+    }
+    for (key, value) in &overlay {
+        lines.push(format!("  {}={}", key, redact(key, value)));
+    }
+
+    lines.push("PATH:".to_string());
+    for entry in fix_env::get_effective_path(app_handle.clone()) {
+        let source = match entry.source {
+            PathEntrySource::Inherited => "inherited",
+            PathEntrySource::FixEnv => "login shell",
+        };
+        lines.push(format!("  {} ({})", entry.path, source));
+    }
+
+    let cli_path = match install_cli::get_cli_path() {
+        Ok(path) => format!(
+            "{}{}",
+            path.to_string_lossy(),
+            if path.exists() { "" } else { " (missing)" }
+        ),
+        Err(err) => format!("unknown ({})", err),
+    };
+    lines.push(format!("CLI used by the app: {}", cli_path));
+    lines.push(format!(
+        "CLI on PATH: {}",
+        install_cli::is_cli_on_path().unwrap_or_else(|| "none".to_string())
+    ));
+
+    // Runs through the command layer, so it sees the same environment as every other command
+    let cli_version = match VersionCommand::new().exec(&app_handle).await {
+        Ok(version) => version,
+        Err(err) => format!("failed ({})", err),
+    };
+    lines.push(format!(
+        "CLI version: {}",
+        redact_output(&cli_version, &env)
+    ));
+
+    lines.join("\n")
+}
+
+fn is_secret_name(key: &str) -> bool {
+    let key = key.to_uppercase();
+    SECRET_NAME_PARTS.iter().any(|part| key.contains(part))
+}
+
+// Hides values of credential-like variables and passwords embedded in URLs, e.g. proxy settings
+fn redact(key: &str, value: &str) -> String {
+    if value.is_empty() {
+        return String::new();
+    }
+    if is_secret_name(key) {
+        return REDACTED.to_string();
+    }
+
+    match url::Url::parse(value) {
+        Ok(mut url) if url.password().is_some() => {
+            let _ = url.set_password(Some(REDACTED));
+            url.to_string()
+        }
+        _ => value.to_string(),
+    }
+}
+
+// Command output may echo secret values back, so they are masked wherever they show up
+fn redact_output(output: &str, env: &BTreeMap<String, String>) -> String {
+    env.iter()
+        .filter(|(key, value)| is_secret_name(key) && !value.is_empty())
+        .fold(output.to_string(), |output, (_, value)| {
+            output.replace(value.as_str(), REDACTED)
+        })
+}
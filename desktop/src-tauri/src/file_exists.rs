@@ -1,9 +1,59 @@
 // use crate::{commands::DevpodCommandError, AppState, UiMessage};
+use lazy_static::lazy_static;
 use log::info;
+use std::collections::HashMap;
 use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// How long a cached result is trusted before the path is checked again
+const CACHE_TTL: Duration = Duration::from_secs(1);
+// Upper bound on cached paths so a caller probing many paths can't grow the cache forever
+const CACHE_CAPACITY: usize = 256;
+
+lazy_static! {
+    static ref CACHE: Mutex<HashMap<String, (bool, Instant)>> = Mutex::new(HashMap::new());
+}
 
 #[tauri::command]
 pub fn file_exists(filepath: &str) -> bool {
     info!("finding file in {}", filepath);
     return Path::new(&filepath).exists();
 }
+
+// Same as `file_exists` but answers repeated checks of a path from a short lived cache. Only
+// meant for polling, use `file_exists` when the result has to be current.
+#[tauri::command]
+pub fn file_exists_cached(filepath: &str) -> bool {
+    let mut cache = CACHE.lock().unwrap();
+    if let Some((exists, checked_at)) = cache.get(filepath) {
+        if checked_at.elapsed() < CACHE_TTL {
+            return *exists;
+        }
+    }
+
+    if cache.len() >= CACHE_CAPACITY {
+        cache.retain(|_, (_, checked_at)| checked_at.elapsed() < CACHE_TTL);
+        if cache.len() >= CACHE_CAPACITY {
+            cache.clear();
+        }
+    }
+
+    let exists = Path::new(filepath).exists();
+    cache.insert(filepath.to_string(), (exists, Instant::now()));
+
+    return exists;
+}
+
+// Drops the cached result for `filepath`, or all cached results if no path is given. Call after
+// creating or removing a file that is being polled.
+#[tauri::command]
+pub fn invalidate_file_exists_cache(filepath: Option<String>) {
+    let mut cache = CACHE.lock().unwrap();
+    match filepath {
+        Some(filepath) => {
+            cache.remove(&filepath);
+        }
+        None => cache.clear(),
+    }
+}
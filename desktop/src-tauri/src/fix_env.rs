@@ -1,5 +1,33 @@
 // use std::fmt::format;
+use crate::{command_env, AppHandle};
+use lazy_static::lazy_static;
+use log::{info, warn};
+use serde::Serialize;
+use std::collections::HashMap;
 use std::process::Output;
+use std::sync::Mutex;
+use tauri::Emitter;
+
+const ENV_CHANGED_EVENT: &str = "env-changed";
+
+lazy_static! {
+    // Values the app was started with, for the variables `fix_env` replaced
+    static ref INHERITED: Mutex<HashMap<String, Option<String>>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PathEntrySource {
+    Inherited,
+    FixEnv,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PathEntry {
+    pub(crate) path: String,
+    pub(crate) source: PathEntrySource,
+}
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
@@ -10,6 +38,22 @@ pub enum Error {
     InvalidOutput(String),
     #[error("failed to run shell echo: {0}")]
     EchoFailed(String),
+    #[error("environment refresh failed: {0}")]
+    Refresh(String),
+}
+impl serde::Serialize for Error {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(self.to_string().as_ref())
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnvChangedMsg {
+    vars: Vec<String>,
 }
 
 fn get_shell() -> String {
@@ -31,6 +75,24 @@ fn read_path_env_cmd(shell: String, var_name: String) -> Result<Output, Error> {
         .map_err(Error::Shell);
 }
 
+// The value of `var_name` in a login shell, with the user's profile applied
+fn read_login_shell_var(var_name: &str) -> Result<String, Error> {
+    let shell = get_shell();
+    let out = read_path_env_cmd(shell, String::from(var_name))?;
+
+    if out.status.success() {
+        let stdout = String::from_utf8_lossy(&out.stdout).into_owned();
+        let cleaned = &strip_ansi_escapes::strip(stdout)?;
+        Ok(String::from_utf8_lossy(cleaned).into_owned())
+    } else {
+        Err(Error::EchoFailed(
+            String::from_utf8_lossy(&out.stderr).into_owned(),
+        ))
+    }
+}
+
+// Changes the process environment, so it must only run at startup before other threads exist.
+// Later refreshes go through `refresh_env`.
 pub fn fix_env(var_name: &str) -> Result<(), Error> {
     #[cfg(windows)]
     {
@@ -38,19 +100,78 @@ pub fn fix_env(var_name: &str) -> Result<(), Error> {
     }
     #[cfg(not(windows))]
     {
-        let shell = get_shell();
-        let out = read_path_env_cmd(shell, String::from(var_name))?;
-
-        if out.status.success() {
-            let stdout = String::from_utf8_lossy(&out.stdout).into_owned();
-            let cleaned = &strip_ansi_escapes::strip(stdout)?;
-            let value = String::from_utf8_lossy(cleaned);
-            std::env::set_var(var_name, value.as_ref());
-            Ok(())
-        } else {
-            Err(Error::EchoFailed(
-                String::from_utf8_lossy(&out.stderr).into_owned(),
-            ))
+        let value = read_login_shell_var(var_name)?;
+        INHERITED
+            .lock()
+            .unwrap()
+            .entry(var_name.to_string())
+            .or_insert_with(|| std::env::var(var_name).ok());
+        std::env::set_var(var_name, value);
+        Ok(())
+    }
+}
+
+// The PATH entries the app and the commands it spawns use, in lookup order. Entries that weren't
+// in the PATH the app was started with came from the login shell via `fix_env`.
+#[tauri::command]
+pub fn get_effective_path(app_handle: AppHandle) -> Vec<PathEntry> {
+    let inherited = INHERITED.lock().unwrap().get("PATH").cloned();
+    let current = command_env::login_shell_var(&app_handle, "PATH").unwrap_or_default();
+    let inherited: Vec<_> = match inherited {
+        // fix_env didn't touch PATH, everything is inherited
+        None => std::env::split_paths(&current).collect(),
+        Some(value) => std::env::split_paths(&value.unwrap_or_default()).collect(),
+    };
+
+    std::env::split_paths(&current)
+        .map(|path| PathEntry {
+            source: if inherited.contains(&path) {
+                PathEntrySource::Inherited
+            } else {
+                PathEntrySource::FixEnv
+            },
+            path: path.to_string_lossy().to_string(),
+        })
+        .collect()
+}
+
+// Reads the variables `fix_env` corrected at startup from the login shell again, e.g. after the
+// user edited their shell profile. Commands spawned afterwards see the new values through the
+// `command_env` overlay, the app's own environment is left alone. Returns the variables that
+// changed, `env-changed` is emitted if there are any.
+#[tauri::command]
+pub async fn refresh_env(app_handle: AppHandle) -> Result<Vec<String>, Error> {
+    let tracked: Vec<String> = INHERITED.lock().unwrap().keys().cloned().collect();
+
+    let values = tauri::async_runtime::spawn_blocking(move || {
+        tracked
+            .into_iter()
+            .map(|var_name| read_login_shell_var(&var_name).map(|value| (var_name, value)))
+            .collect::<Result<Vec<_>, Error>>()
+    })
+    .await
+    .map_err(|err| Error::Refresh(err.to_string()))??;
+
+    let mut changed = vec![];
+    for (var_name, value) in values {
+        if command_env::login_shell_var(&app_handle, &var_name).as_deref() != Some(value.as_str()) {
+            command_env::set_refreshed(&app_handle, var_name.clone(), value);
+            changed.push(var_name);
         }
     }
+
+    if changed.is_empty() {
+        info!("Environment is up to date");
+        return Ok(changed);
+    }
+
+    info!("Refreshed environment variables {}", changed.join(", "));
+    let msg = EnvChangedMsg {
+        vars: changed.clone(),
+    };
+    if let Err(err) = app_handle.emit(ENV_CHANGED_EVENT, msg) {
+        warn!("Failed to emit env changed event: {}", err);
+    }
+
+    Ok(changed)
 }
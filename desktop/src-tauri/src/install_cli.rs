@@ -1,10 +1,60 @@
-use crate::{commands::KLED_BINARY_NAME, AppHandle};
-use log::error;
+use crate::{
+    commands::{version::VersionCommand, KLED_BINARY_NAME},
+    AppHandle,
+};
+use log::{error, warn};
+use serde::{ser::SerializeStruct, Serialize};
+use sha2::{Digest, Sha256};
 use std::path::Path;
 use std::str::Lines;
 use std::{env, path::PathBuf};
+use tauri::Emitter;
 use thiserror::Error;
 
+const INSTALL_CLI_PROGRESS_EVENT: &str = "install-cli-progress";
+// Name the CLI is installed as for use in a terminal
+const CLI_LINK_NAME: &str = "devpod";
+// SHA-256 of the CLI bundled with this build, see build.rs
+const EXPECTED_CLI_CHECKSUM: &str = env!("KLED_CLI_SHA256");
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum InstallPhase {
+    Locate,
+    Link,
+    WriteEntryPoints,
+    UpdatePath,
+    NotifyEnvironment,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct InstallProgress {
+    phase: InstallPhase,
+    message: String,
+}
+
+// What the install did, so the UI can tell the user where to find the CLI
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstallSummary {
+    installed_to: Option<String>,
+    path_modified: bool,
+}
+
+// Result of `verify_cli_integrity`, `details` explains every failed check
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CliIntegrity {
+    passed: bool,
+    path: String,
+    expected_checksum: Option<String>,
+    actual_checksum: Option<String>,
+    // Output of `version`, only set if the smoke test ran and succeeded
+    version: Option<String>,
+    details: Vec<String>,
+}
+
 #[derive(Error, Debug)]
 #[allow(dead_code)]
 pub enum InstallCLIError {
@@ -25,27 +75,170 @@ pub enum InstallCLIError {
     #[error("Failed to inform Windows about the change in environment variables. You will need to reboot you machine for them to take effect.")]
     WindowsBroadcastChange,
 }
+impl InstallCLIError {
+    // The phase of the install that failed
+    fn phase(&self) -> InstallPhase {
+        match self {
+            InstallCLIError::NoExePath(_)
+            | InstallCLIError::PathConversion
+            | InstallCLIError::DataDir => InstallPhase::Locate,
+            InstallCLIError::Link(_) => InstallPhase::Link,
+            InstallCLIError::CreateDir(_) | InstallCLIError::WriteFile(_) => {
+                InstallPhase::WriteEntryPoints
+            }
+            InstallCLIError::Registry(_) => InstallPhase::UpdatePath,
+            InstallCLIError::WindowsBroadcastChange => InstallPhase::NotifyEnvironment,
+        }
+    }
+}
 impl serde::Serialize for InstallCLIError {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: serde::Serializer,
     {
-        serializer.serialize_str(self.to_string().as_ref())
+        let mut state = serializer.serialize_struct("InstallCLIError", 2)?;
+        state.serialize_field("phase", &self.phase())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.end()
     }
 }
 
+// Emits an `install-cli-progress` event when each phase starts and returns what was installed
+// where. Failures carry the phase they happened in.
 #[tauri::command]
-pub fn install_cli(app_handle: AppHandle, force: bool) -> Result<(), InstallCLIError> {
-    if let Err(err) = install(app_handle, force) {
-        error!("{}", err);
-        Err(err)
-    } else {
-        Ok(())
+pub fn install_cli(app_handle: AppHandle, force: bool) -> Result<InstallSummary, InstallCLIError> {
+    match install(&app_handle, force) {
+        Ok(summary) => Ok(summary),
+        Err(err) => {
+            error!("{:?} failed: {}", err.phase(), err);
+            Err(err)
+        }
+    }
+}
+
+// Compares the bundled CLI against the checksum recorded at build time and, if `smoke_test` is
+// set, checks that it runs by asking for its version. Catches truncated or corrupted binaries
+// left behind by interrupted updates.
+#[tauri::command]
+pub async fn verify_cli_integrity(app_handle: AppHandle, smoke_test: bool) -> CliIntegrity {
+    let mut details = vec![];
+    let path = match get_cli_path() {
+        // The bundled binary has the platform's executable suffix, e.g. `.exe` on Windows
+        Ok(path) => {
+            let mut path = path.into_os_string();
+            path.push(env::consts::EXE_SUFFIX);
+            PathBuf::from(path)
+        }
+        Err(err) => {
+            return CliIntegrity {
+                passed: false,
+                path: String::new(),
+                expected_checksum: None,
+                actual_checksum: None,
+                version: None,
+                details: vec![format!("unable to locate the CLI: {}", err)],
+            }
+        }
+    };
+
+    let expected_checksum =
+        (!EXPECTED_CLI_CHECKSUM.is_empty()).then(|| EXPECTED_CLI_CHECKSUM.to_string());
+    let actual_checksum = match std::fs::read(&path) {
+        Ok(contents) => Some(format!("{:x}", Sha256::digest(contents))),
+        Err(err) => {
+            details.push(format!("unable to read {:?}: {}", path, err));
+            None
+        }
+    };
+    match (&expected_checksum, &actual_checksum) {
+        (Some(expected), Some(actual)) if expected != actual => details.push(format!(
+            "checksum mismatch, expected {} but found {}",
+            expected, actual
+        )),
+        // A build without a recorded checksum can't vouch for the binary
+        (None, _) => {
+            details.push("unverified, no CLI checksum was recorded in this build".to_string())
+        }
+        _ => {}
+    }
+
+    let mut version = None;
+    if smoke_test {
+        match VersionCommand::new().exec(&app_handle).await {
+            Ok(output) => version = Some(output),
+            Err(err) => details.push(format!("smoke test failed: {}", err)),
+        }
+    }
+
+    if !details.is_empty() {
+        warn!("CLI integrity check failed: {}", details.join("; "));
+    }
+
+    CliIntegrity {
+        passed: details.is_empty(),
+        path: path.to_string_lossy().to_string(),
+        expected_checksum,
+        actual_checksum,
+        version,
+        details,
+    }
+}
+
+fn report_progress(app_handle: &AppHandle, phase: InstallPhase, message: String) {
+    let progress = InstallProgress { phase, message };
+    if let Err(err) = app_handle.emit(INSTALL_CLI_PROGRESS_EVENT, progress) {
+        warn!("Failed to emit install progress: {}", err);
     }
 }
 
+// Resolves the CLI the way the user's shell would, first under the name `install_cli` links it
+// as and then under the binary name. Uses the PATH corrected by `fix_env` at startup.
+#[tauri::command]
+pub fn is_cli_on_path() -> Option<String> {
+    let path = env::var_os("PATH")?;
+
+    [CLI_LINK_NAME, KLED_BINARY_NAME]
+        .iter()
+        .find_map(|name| find_in_path(name, &path))
+        .map(|path| path.to_string_lossy().into_owned())
+}
+
+fn find_in_path(name: &str, path: &std::ffi::OsStr) -> Option<PathBuf> {
+    // windows resolves commands without an extension through PATHEXT
+    let extensions: Vec<String> = if cfg!(windows) {
+        env::var("PATHEXT")
+            .unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".to_string())
+            .split(';')
+            .map(String::from)
+            .collect()
+    } else {
+        vec![String::new()]
+    };
+
+    env::split_paths(path).find_map(|dir| {
+        extensions
+            .iter()
+            .map(|ext| dir.join(format!("{}{}", name, ext)))
+            .find(|candidate| is_executable(candidate))
+    })
+}
+
+#[cfg(not(target_os = "windows"))]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    std::fs::metadata(path)
+        .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(target_os = "windows")]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
 // The path to the `devpod-cli` binary/executable. If bundled correctly, will be placed next to the desktop app executable.
-fn get_cli_path() -> Result<PathBuf, std::io::Error> {
+pub(crate) fn get_cli_path() -> Result<PathBuf, std::io::Error> {
     let mut exe_path = env::current_exe()?;
     exe_path.pop();
     exe_path.push(KLED_BINARY_NAME);
@@ -54,23 +247,29 @@ fn get_cli_path() -> Result<PathBuf, std::io::Error> {
 }
 
 #[cfg(not(target_os = "windows"))]
-fn install(_app_handle: AppHandle, force: bool) -> Result<(), InstallCLIError> {
+fn install(app_handle: &AppHandle, force: bool) -> Result<InstallSummary, InstallCLIError> {
     use anyhow::Context;
     use dirs::home_dir;
-    use log::{info, warn};
+    use log::info;
     use std::{fs::remove_file, os::unix::fs::symlink};
 
+    report_progress(app_handle, InstallPhase::Locate, "Locating bundled CLI".to_string());
     let cli_path = get_cli_path().map_err(InstallCLIError::NoExePath)?;
 
     // The binary we ship with is `devpod-cli`, but we want to link it to `devpod` so that users can just run `devpod` in their terminal
     let mut target_paths: Vec<PathBuf> = vec![];
 
     // /usr/local/bin/devpod
-    let raw_system_bin = format!("/usr/local/bin/{}", "devpod");
+    let raw_system_bin = format!("/usr/local/bin/{}", CLI_LINK_NAME);
     target_paths.push(PathBuf::from(&raw_system_bin));
 
     if force {
         info!("Attempting to force install CLI");
+        report_progress(
+            app_handle,
+            InstallPhase::Link,
+            format!("Linking CLI to {} as administrator", raw_system_bin),
+        );
         let script = format!("osascript -e \"do shell script \\\"mkdir -p /usr/local/bin && ln -sf '{}' '{}'\\\" with administrator privileges\"", cli_path.to_string_lossy(), raw_system_bin);
         let status = std::process::Command::new("sh")
             .arg("-c")
@@ -80,7 +279,10 @@ fn install(_app_handle: AppHandle, force: bool) -> Result<(), InstallCLIError> {
             .map_err(InstallCLIError::Link)?;
         info!("Status: {}", status);
 
-        return Ok(());
+        return Ok(InstallSummary {
+            installed_to: Some(raw_system_bin),
+            path_modified: false,
+        });
     }
 
     if let Some(home) = home_dir() {
@@ -123,6 +325,15 @@ fn install(_app_handle: AppHandle, force: bool) -> Result<(), InstallCLIError> {
             "Attempting to link cli to {}",
             target_path.to_string_lossy()
         );
+        report_progress(
+            app_handle,
+            InstallPhase::Link,
+            format!("Linking CLI to {}", str_target_path),
+        );
+        let installed = InstallSummary {
+            installed_to: Some(str_target_path.to_string()),
+            path_modified: false,
+        };
 
         let is_flatpak = env::var("FLATPAK_ID").is_ok();
 
@@ -132,7 +343,7 @@ fn install(_app_handle: AppHandle, force: bool) -> Result<(), InstallCLIError> {
                 .map_err(InstallCLIError::Link)
             {
                 Ok(..) => {
-                    return Ok(());
+                    return Ok(installed);
                 }
                 Err(err) => {
                     warn!(
@@ -152,7 +363,7 @@ fn install(_app_handle: AppHandle, force: bool) -> Result<(), InstallCLIError> {
                 .map_err(InstallCLIError::Link)
             {
                 Ok(..) => {
-                    return Ok(());
+                    return Ok(installed);
                 }
                 Err(err) => {
                     warn!(
@@ -170,7 +381,10 @@ fn install(_app_handle: AppHandle, force: bool) -> Result<(), InstallCLIError> {
         return Err(err);
     }
 
-    Ok(())
+    Ok(InstallSummary {
+        installed_to: None,
+        path_modified: false,
+    })
 }
 
 fn copy<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q) -> std::io::Result<()> {
@@ -207,7 +421,7 @@ fn find_fs_type(curr_path: &Path, mount_lines: &Lines) -> Option<String> {
 }
 
 #[cfg(target_os = "windows")]
-fn install(app_handle: AppHandle, force: bool) -> Result<(), InstallCLIError> {
+fn install(app_handle: &AppHandle, force: bool) -> Result<InstallSummary, InstallCLIError> {
     use log::error;
     use tauri::Manager;
     use std::fs;
@@ -225,6 +439,7 @@ fn install(app_handle: AppHandle, force: bool) -> Result<(), InstallCLIError> {
         content: String,
     }
 
+    report_progress(app_handle, InstallPhase::Locate, "Locating bundled CLI".to_string());
     let cli_path = get_cli_path().map_err(|e| InstallCLIError::NoExePath(e))?;
     let mut bin_dir = app_handle
         .path()
@@ -237,16 +452,21 @@ fn install(app_handle: AppHandle, force: bool) -> Result<(), InstallCLIError> {
     let cli_path = cli_path.to_str().ok_or(InstallCLIError::PathConversion)?;
 
     let sh_file = BinFile {
-        name: "devpod".to_string(),
+        name: CLI_LINK_NAME.to_string(),
         // WARN: we actually need to debug print here because this escapes the backslash to `\\` and will then be recognised by the shell
         content: format!("#!/usr/bin/env sh\n{:?}.exe \"$@\" \nexit $?", cli_path),
     };
 
     let cmd_file = BinFile {
-        name: format!("{}.cmd", "devpod".to_string()),
+        name: format!("{}.cmd", CLI_LINK_NAME),
         content: format!("@echo off\n\"{}.exe\" %*", cli_path),
     };
 
+    report_progress(
+        app_handle,
+        InstallPhase::WriteEntryPoints,
+        format!("Writing entry points to {}", bin_dir.to_string_lossy()),
+    );
     fs::create_dir_all(bin_dir.clone()).map_err(|e| InstallCLIError::CreateDir(e))?;
     for BinFile { content, name } in [sh_file, cmd_file] {
         let mut file_path = bin_dir.clone();
@@ -260,6 +480,11 @@ fn install(app_handle: AppHandle, force: bool) -> Result<(), InstallCLIError> {
     // Now that we placed our entry points in the /bin folder, we need to update the users path environment variable
     // to include said folder
     let current_dir_path = bin_dir.to_str().ok_or(InstallCLIError::PathConversion)?;
+    report_progress(
+        app_handle,
+        InstallPhase::UpdatePath,
+        format!("Adding {} to PATH", current_dir_path),
+    );
     let hkcu = RegKey::predef(HKEY_CURRENT_USER);
     let environment_key = hkcu
         .open_subkey_with_flags("Environment", KEY_ALL_ACCESS)
@@ -270,7 +495,10 @@ fn install(app_handle: AppHandle, force: bool) -> Result<(), InstallCLIError> {
 
     // Make sure we only add the path once
     if current_env_path.contains(current_dir_path) {
-        return Ok(());
+        return Ok(InstallSummary {
+            installed_to: Some(current_dir_path.to_string()),
+            path_modified: false,
+        });
     }
 
     current_env_path.push_str(&format!(";{}", current_dir_path));
@@ -279,6 +507,11 @@ fn install(app_handle: AppHandle, force: bool) -> Result<(), InstallCLIError> {
         .set_value("Path", &current_env_path)
         .map_err(|e| InstallCLIError::Registry(e))?;
 
+    report_progress(
+        app_handle,
+        InstallPhase::NotifyEnvironment,
+        "Notifying Windows about the changed PATH".to_string(),
+    );
     // After setting the registry key we need to inform windows about the changes.
     // Otherwise it would require a full system reboot for them to take effect.
     unsafe {
@@ -310,5 +543,8 @@ fn install(app_handle: AppHandle, force: bool) -> Result<(), InstallCLIError> {
         }
     };
 
-    Ok(())
+    Ok(InstallSummary {
+        installed_to: Some(current_dir_path.to_string()),
+        path_modified: true,
+    })
 }
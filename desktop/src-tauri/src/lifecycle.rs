@@ -0,0 +1,30 @@
+use crate::{
+    providers, resource_watcher, settings::Settings, single_instance, spacetime_server,
+    util::kill_child_processes, AppHandle,
+};
+use log::info;
+
+// Shared by `RunEvent::Exit` and `relaunch_app` so both leave nothing behind
+pub async fn cleanup(app_handle: &AppHandle) {
+    single_instance::release();
+    Settings::flush(app_handle);
+    kill_child_processes(std::process::id());
+    // The provider commands block on the async runtime themselves, which panics on a runtime
+    // thread
+    let provider_handle = app_handle.clone();
+    let _ = tauri::async_runtime::spawn_blocking(move || {
+        providers::check_dangling_provider(&provider_handle)
+    })
+    .await;
+    resource_watcher::shutdown(app_handle).await;
+    let _ = spacetime_server::shutdown(app_handle).await;
+}
+
+// Used by "apply and restart" flows after settings changes or updates
+#[tauri::command]
+pub async fn relaunch_app(app_handle: AppHandle) {
+    info!("Relaunching application");
+    cleanup(&app_handle).await;
+
+    app_handle.restart();
+}
@@ -1,6 +1,82 @@
-use log::LevelFilter;
-use tauri::{plugin::TauriPlugin, Wry};
-use tauri_plugin_log::{Target, TargetKind};
+use crate::{
+    settings::{LogLevel, Settings, SettingsError},
+    AppHandle,
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use log::{info, warn, Level, LevelFilter};
+use serde::Serialize;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tauri::async_runtime::{channel, Receiver};
+use tauri::{plugin::TauriPlugin, Manager, Wry};
+use tauri_plugin_log::{RotationStrategy, Target, TargetKind};
+use tauri_plugin_shell::process::CommandEvent;
+use thiserror::Error;
+
+const LOG_FILE_NAME: &str = "DevPod";
+// The active log is rotated into a dated archive once it grows past this size
+const MAX_LOG_FILE_BYTES: u128 = 5 * 1024 * 1024;
+const MAX_LOG_ARCHIVES: usize = 10;
+// Suffix format of rotated archives, `DevPod_2024-01-31_09-15-00.log`, always in UTC
+const LOG_ARCHIVE_DATE_FORMAT: &str = "%Y-%m-%d_%H-%M-%S";
+// Output of CLI commands is appended to this file in the directory set with `set_cli_log_dir`
+const CLI_LOG_FILE_NAME: &str = "cli.log";
+
+#[cfg(debug_assertions)]
+pub const DEFAULT_LOG_LEVEL: LevelFilter = LevelFilter::Debug;
+#[cfg(not(debug_assertions))]
+pub const DEFAULT_LOG_LEVEL: LevelFilter = LevelFilter::Info;
+
+#[derive(Error, Debug)]
+pub enum LogArchiveError {
+    #[error("log directory not found")]
+    LogDir,
+    #[error("log archive {0} not found")]
+    NotFound(String),
+    #[error("unable to read log archives")]
+    Io(#[from] std::io::Error),
+}
+impl serde::Serialize for LogArchiveError {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(self.to_string().as_ref())
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum CliLogDirError {
+    #[error("log directory {0} must be an absolute path")]
+    NotAbsolute(String),
+    #[error("log directory {path} is not writable")]
+    NotWritable {
+        path: String,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error(transparent)]
+    Settings(#[from] SettingsError),
+}
+impl serde::Serialize for CliLogDirError {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(self.to_string().as_ref())
+    }
+}
+
+// A rotated log file. It covers the time from the previous rotation, unknown for the oldest
+// archive, until its own rotation.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogArchive {
+    name: String,
+    size_bytes: u64,
+    from: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+}
 
 #[allow(unused_variables)]
 pub fn build_plugin() -> TauriPlugin<Wry> {
@@ -13,14 +89,241 @@ pub fn build_plugin() -> TauriPlugin<Wry> {
     }
     #[cfg(not(debug_assertions))] // only enable in release builds
     targets.push(Target::new(TargetKind::LogDir {
-        file_name: Some("DevPod".to_string()),
+        file_name: Some(LOG_FILE_NAME.to_string()),
     }));
 
-    let builder = tauri_plugin_log::Builder::default().targets(targets);
-    #[cfg(debug_assertions)] // only enable during development
-    let builder = builder.level(LevelFilter::Debug);
-    #[cfg(not(debug_assertions))] // only enable in release builds
-    let builder = builder.level(LevelFilter::Info);
+    // The plugin lets everything through, the active level is enforced by `log::max_level`
+    // so it can be changed at runtime, see `apply_log_level`
+    tauri_plugin_log::Builder::default()
+        .targets(targets)
+        .level(LevelFilter::Trace)
+        .max_file_size(MAX_LOG_FILE_BYTES)
+        .rotation_strategy(RotationStrategy::KeepSome(MAX_LOG_ARCHIVES))
+        .build()
+}
+
+// Restores the level the user picked last time, must run after the log plugin is set up
+pub fn apply_log_level(app_handle: &AppHandle) {
+    let level = Settings::log_level(app_handle)
+        .map(LevelFilter::from)
+        .unwrap_or(DEFAULT_LOG_LEVEL);
+    log::set_max_level(level);
+}
+
+#[tauri::command]
+pub fn set_log_level(app_handle: AppHandle, level: LogLevel) -> Result<LogLevel, SettingsError> {
+    log::set_max_level(level.into());
+    info!("Log level set to {:?}", level);
+
+    Settings::set_log_level(&app_handle, level)?;
+
+    Ok(level)
+}
+
+#[tauri::command]
+pub fn get_log_level() -> LogLevel {
+    log::max_level().into()
+}
+
+// Copies the output of the daemon and workspace commands spawned from now on into `path`. The
+// CLI has no option for where it logs, so the app appends what the commands print to
+// `CLI_LOG_FILE_NAME` in that directory. It's created if needed and has to be writable, `None`
+// stops copying. Already running processes keep their destination.
+#[tauri::command]
+pub fn set_cli_log_dir(app_handle: AppHandle, path: Option<String>) -> Result<(), CliLogDirError> {
+    if let Some(path) = &path {
+        if !Path::new(path).is_absolute() {
+            return Err(CliLogDirError::NotAbsolute(path.clone()));
+        }
+        check_writable(Path::new(path)).map_err(|source| CliLogDirError::NotWritable {
+            path: path.clone(),
+            source,
+        })?;
+    }
+
+    Settings::set_cli_log_dir(&app_handle, path.as_deref())?;
+    match path {
+        Some(path) => info!("Copying CLI output to {}", path),
+        None => info!("Stopped copying CLI output"),
+    }
+
+    Ok(())
+}
+
+fn check_writable(dir: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    let probe = dir.join(format!(".write-test-{}", std::process::id()));
+    std::fs::write(&probe, b"")?;
+    if let Err(err) = std::fs::remove_file(&probe) {
+        warn!("Failed to remove {:?}: {}", probe, err);
+    }
+
+    Ok(())
+}
 
-    builder.build()
+// Passes the events of a spawned CLI command through and appends its output to the CLI log,
+// if a directory was set with `set_cli_log_dir`. Otherwise `rx` is returned as is.
+pub fn tee_cli_output(
+    app_handle: &AppHandle,
+    command_line: &str,
+    mut rx: Receiver<CommandEvent>,
+) -> Receiver<CommandEvent> {
+    let Some(dir) = Settings::cli_log_dir(app_handle) else {
+        return rx;
+    };
+    let path = Path::new(&dir).join(CLI_LOG_FILE_NAME);
+    let mut file = match open_cli_log(&path, command_line) {
+        Ok(file) => file,
+        Err(err) => {
+            warn!("Failed to open CLI log {:?}: {}", path, err);
+            return rx;
+        }
+    };
+
+    let (tx, tee_rx) = channel(1);
+    tauri::async_runtime::spawn(async move {
+        while let Some(event) = rx.recv().await {
+            let written = match &event {
+                CommandEvent::Stdout(line) | CommandEvent::Stderr(line) => {
+                    write_cli_log_line(&mut file, line)
+                }
+                CommandEvent::Terminated(payload) => {
+                    writeln!(file, "--- exited with code {:?}", payload.code)
+                }
+                _ => Ok(()),
+            };
+            if let Err(err) = written {
+                warn!("Failed to write CLI log {:?}: {}", path, err);
+            }
+            if tx.send(event).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    tee_rx
+}
+
+fn open_cli_log(path: &Path, command_line: &str) -> std::io::Result<std::fs::File> {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    writeln!(file, "--- {} {}", Utc::now().to_rfc3339(), command_line)?;
+
+    Ok(file)
+}
+
+fn write_cli_log_line(file: &mut std::fs::File, line: &[u8]) -> std::io::Result<()> {
+    file.write_all(line)?;
+    if !line.ends_with(b"\n") {
+        file.write_all(b"\n")?;
+    }
+
+    Ok(())
+}
+
+// Writes a line a child process printed to stderr into the app log
+pub fn log_child_stderr(binary_name: &str, level: Level, line: &[u8]) {
+    let line = String::from_utf8_lossy(line);
+    let line = line.trim_end();
+    if !line.is_empty() {
+        log::log!(level, "[{}] {}", binary_name, line);
+    }
+}
+
+// Rotated log archives, oldest first
+#[tauri::command]
+pub fn list_log_archives(app_handle: AppHandle) -> Result<Vec<LogArchive>, LogArchiveError> {
+    let log_dir = app_handle
+        .path()
+        .app_log_dir()
+        .map_err(|_| LogArchiveError::LogDir)?;
+    if !log_dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut archives = vec![];
+    for entry in std::fs::read_dir(&log_dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if let Some(rotated_at) = archive_rotated_at(&name) {
+            archives.push((rotated_at, name, entry.metadata()?.len()));
+        }
+    }
+    archives.sort();
+
+    let mut from = None;
+    Ok(archives
+        .into_iter()
+        .map(|(rotated_at, name, size_bytes)| {
+            let archive = LogArchive {
+                name,
+                size_bytes,
+                from,
+                until: Some(rotated_at),
+            };
+            from = Some(rotated_at);
+            archive
+        })
+        .collect())
+}
+
+// Path of an archive returned by `list_log_archives`. Only names of existing archives are
+// accepted, so the name can't be used to reach other files.
+#[tauri::command]
+pub fn open_log_archive(app_handle: AppHandle, name: String) -> Result<String, LogArchiveError> {
+    let is_archive = list_log_archives(app_handle.clone())?
+        .iter()
+        .any(|archive| archive.name == name);
+    if !is_archive {
+        return Err(LogArchiveError::NotFound(name));
+    }
+
+    let log_dir: PathBuf = app_handle
+        .path()
+        .app_log_dir()
+        .map_err(|_| LogArchiveError::LogDir)?;
+
+    Ok(log_dir.join(name).to_string_lossy().into_owned())
+}
+
+fn archive_rotated_at(name: &str) -> Option<DateTime<Utc>> {
+    let date = name
+        .strip_prefix(LOG_FILE_NAME)?
+        .strip_prefix('_')?
+        .strip_suffix(".log")?;
+
+    NaiveDateTime::parse_from_str(date, LOG_ARCHIVE_DATE_FORMAT)
+        .ok()
+        .map(|date| date.and_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    mod log_archives {
+        use super::super::*;
+        use chrono::TimeZone;
+
+        #[test]
+        fn should_parse_rotation_time() {
+            assert_eq!(
+                archive_rotated_at("DevPod_2024-01-31_09-15-00.log"),
+                Some(Utc.with_ymd_and_hms(2024, 1, 31, 9, 15, 0).unwrap())
+            );
+        }
+
+        #[test]
+        fn should_ignore_active_log() {
+            assert_eq!(archive_rotated_at("DevPod.log"), None);
+        }
+
+        #[test]
+        fn should_ignore_other_files() {
+            assert_eq!(archive_rotated_at("Other_2024-01-31_09-15-00.log"), None);
+            assert_eq!(archive_rotated_at("DevPod_2024-01-31_09-15-00.txt"), None);
+            assert_eq!(archive_rotated_at("DevPod_2024-01-31.log"), None);
+            assert_eq!(archive_rotated_at("DevPod_2024-13-31_09-15-00.log"), None);
+        }
+    }
 }
@@ -8,19 +8,29 @@
 extern crate objc;
 
 mod action_logs;
+mod app_info;
+mod child_processes;
+mod command_env;
 mod commands;
 mod community_contributions;
 mod custom_protocol;
 mod daemon;
+mod diagnostics;
 mod file_exists;
 mod fix_env;
 mod get_env;
 mod install_cli;
+mod lifecycle;
 mod logging;
+mod open_external;
+mod operations;
+mod ports;
 mod providers;
+mod recent_errors;
 mod resource_watcher;
 mod server;
 mod settings;
+mod single_instance;
 mod spacetime_server;
 mod system_tray;
 mod ui_messages;
@@ -28,20 +38,23 @@ mod ui_ready;
 mod updates;
 mod util;
 mod window;
+mod workspaces;
 
 use community_contributions::CommunityContributions;
 use custom_protocol::CustomProtocol;
 use log::{error, info};
 use resource_watcher::{ProState, WorkspacesState};
+use settings::{CloseBehavior, Settings};
 use std::sync::{Arc, Mutex};
-use system_tray::{SystemTray, SYSTEM_TRAY_ICON_BYTES};
-use tauri::{image::Image, tray::TrayIconBuilder, Manager};
+use system_tray::{SystemTray, SYSTEM_TRAY_ICON_BYTES, SYSTEM_TRAY_ICON_SIZE};
+use tauri::{tray::TrayIconBuilder, Manager};
+use tauri_plugin_dialog::{DialogExt, MessageDialogKind};
 use tokio::sync::{
     mpsc::{self, Sender},
     RwLock,
 };
 use ui_messages::UiMessage;
-use util::{kill_child_processes, QUIT_EXIT_CODE};
+use util::QUIT_EXIT_CODE;
 
 pub type AppHandle = tauri::AppHandle;
 
@@ -50,11 +63,23 @@ pub struct AppState {
     pro: Arc<RwLock<ProState>>,
     community_contributions: Arc<Mutex<CommunityContributions>>,
     ui_messages: Sender<UiMessage>,
+    ui_message_listener: Arc<Mutex<Option<ui_messages::UiMessageListener>>>,
     releases: Arc<Mutex<updates::Releases>>,
+    releases_failure: Arc<Mutex<Option<updates::ReleasesFetchFailure>>>,
     pending_update: Arc<Mutex<Option<updates::Release>>>,
     #[allow(dead_code)]
     update_installed: Arc<Mutex<bool>>,
+    update_status: Arc<Mutex<updates::UpdateStatus>>,
+    update_download: Arc<Mutex<Option<updates::UpdateDownload>>>,
+    // Version the user backed out of, not offered again until the app restarts
+    skipped_update: Arc<Mutex<Option<String>>>,
     resources_handles: Arc<Mutex<Vec<tauri::async_runtime::JoinHandle<()>>>>,
+    pending_settings_changes: Arc<Mutex<settings::PendingSettingsChanges>>,
+    recent_errors: Arc<Mutex<recent_errors::RecentErrors>>,
+    operations: Arc<Mutex<operations::Operations>>,
+    disk_usage: Arc<Mutex<workspaces::DiskUsageCache>>,
+    spacetime_status: Arc<tokio::sync::Mutex<spacetime_server::ServerStatus>>,
+    command_env: Arc<Mutex<command_env::CommandEnv>>,
 }
 fn main() -> anyhow::Result<()> {
     // https://unix.stackexchange.com/questions/82620/gui-apps-dont-inherit-path-from-parent-console-apps
@@ -67,6 +92,12 @@ fn main() -> anyhow::Result<()> {
 
     CustomProtocol::forward_deep_link();
 
+    // covers macOS as well, where launching the binary again or `open -n` bypasses Reopen
+    let instance_listener = match single_instance::acquire() {
+        single_instance::Instance::Primary(listener) => listener,
+        single_instance::Instance::Secondary => return Ok(()),
+    };
+
     let (tx, rx) = mpsc::channel::<UiMessage>(10);
 
     let mut app_builder = tauri::Builder::default();
@@ -77,9 +108,7 @@ fn main() -> anyhow::Result<()> {
             let app_state = app.state::<AppState>();
 
             tauri::async_runtime::block_on(async move {
-                if let Err(err) = app_state.ui_messages.send(UiMessage::ShowDashboard).await {
-                    error!("Failed to broadcast show dashboard message: {}", err);
-                };
+                ui_messages::dispatch(&app_state.ui_messages, UiMessage::ShowDashboard).await;
             });
         }));
     }
@@ -89,10 +118,25 @@ fn main() -> anyhow::Result<()> {
             pro: Arc::new(RwLock::new(ProState::default())),
             community_contributions: Arc::new(Mutex::new(contributions)),
             ui_messages: tx.clone(),
+            ui_message_listener: Arc::new(Mutex::new(None)),
             releases: Arc::new(Mutex::new(updates::Releases::default())),
+            releases_failure: Arc::new(Mutex::new(None)),
             pending_update: Arc::new(Mutex::new(None)),
             update_installed: Arc::new(Mutex::new(false)),
+            update_status: Arc::new(Mutex::new(updates::UpdateStatus::default())),
+            update_download: Arc::new(Mutex::new(None)),
+            skipped_update: Arc::new(Mutex::new(None)),
             resources_handles: Arc::new(Mutex::new(vec![])),
+            pending_settings_changes: Arc::new(Mutex::new(
+                settings::PendingSettingsChanges::default(),
+            )),
+            recent_errors: Arc::new(Mutex::new(recent_errors::RecentErrors::default())),
+            operations: Arc::new(Mutex::new(operations::Operations::default())),
+            disk_usage: Arc::new(Mutex::new(workspaces::DiskUsageCache::default())),
+            spacetime_status: Arc::new(tokio::sync::Mutex::new(
+                spacetime_server::ServerStatus::default(),
+            )),
+            command_env: Arc::new(Mutex::new(command_env::CommandEnv::default())),
         })
         .plugin(logging::build_plugin())
         .plugin(tauri_plugin_store::Builder::default().build())
@@ -106,61 +150,117 @@ fn main() -> anyhow::Result<()> {
         .plugin(tauri_plugin_deep_link::init())
         .plugin(tauri_plugin_updater::Builder::new().build())
         .setup(move |app| {
+            logging::apply_log_level(app.handle());
             info!("Setup application");
 
             providers::check_dangling_provider(&app.handle());
             let window_helper = window::WindowHelper::new(app.handle().clone());
 
-            let window = app.get_webview_window("main").unwrap();
-            window_helper.setup(&window);
+            match app.get_webview_window("main") {
+                Some(window) => window_helper.setup(&window),
+                None => {
+                    error!("Main window was not created at startup, attempting to recreate it");
+                    if let Err(err) = window_helper.recreate_main(app_name.clone()) {
+                        error!("Failed to recreate main window: {}", err);
+
+                        // Nothing else has been started yet, so bail out before spawning
+                        // background tasks that would keep a windowless app alive
+                        let app_handle = app.handle().clone();
+                        app.dialog()
+                            .message(format!(
+                                "{} could not open its main window and will quit.\n\n{}",
+                                app_name, err
+                            ))
+                            .title("Failed to start")
+                            .kind(MessageDialogKind::Error)
+                            .show(move |_| app_handle.exit(QUIT_EXIT_CODE));
+
+                        return Ok(());
+                    }
+                }
+            }
+
+            // menu bar only, the icon would otherwise show up with the main window
+            #[cfg(target_os = "macos")]
+            if !Settings::show_dock_icon(app.handle()) {
+                window_helper.set_dock_icon_visibility(false);
+            }
 
             let app_handle = app.handle().clone();
             resource_watcher::setup(&app_handle);
+            daemon::client::setup(&app_handle);
 
             action_logs::setup(&app.handle())?;
+            command_env::setup(&app.handle());
 
-            let custom_protocol = CustomProtocol::init();
+            let mut url_schemes = vec![custom_protocol::APP_URL_SCHEME];
+            url_schemes.extend(custom_protocol::LEGACY_APP_URL_SCHEMES);
+            let custom_protocol = CustomProtocol::init(&url_schemes);
             custom_protocol.setup(app.handle().clone());
 
+            // aborted with the other background tasks on shutdown so a stalled fetch can't
+            // hold up quitting
+            let app_handle = app.handle().clone();
+            let releases_handle = tauri::async_runtime::spawn(async move {
+                updates::UpdateHelper::new(&app_handle).load_releases().await;
+            });
+            app.state::<AppState>()
+                .resources_handles
+                .lock()
+                .unwrap()
+                .push(releases_handle);
+
             let app_handle = app.handle().clone();
             tauri::async_runtime::spawn(async move {
-                let update_helper = updates::UpdateHelper::new(&app_handle);
-                if let Ok(releases) = update_helper.fetch_releases().await {
-                    let state = app_handle.state::<AppState>();
-                    let mut releases_state = state.releases.lock().unwrap();
-                    *releases_state = releases;
-                }
+                updates::UpdateHelper::new(&app_handle).poll().await;
+            });
 
-                update_helper.poll().await;
+            let app_handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                workspaces::auto_start(app_handle).await;
             });
 
             let app_handle = app.handle().clone();
             tauri::async_runtime::spawn(async move {
                 if let Err(err) = server::setup(&app_handle).await {
-                    error!("Failed to start server: {}", err);
+                    recent_errors::report_error(
+                        &app_handle,
+                        module_path!(),
+                        format!("Failed to start server: {}", err),
+                    );
                 }
             });
 
             let app_handle = app.handle().clone();
             tauri::async_runtime::spawn(async move {
                 if let Err(err) = spacetime_server::setup(&app_handle).await {
-                    error!("Failed to start SpacetimeDB server: {}", err);
+                    recent_errors::report_error(
+                        &app_handle,
+                        module_path!(),
+                        format!("Failed to start SpacetimeDB server: {}", err),
+                    );
                 }
             });
 
-            let app_handle = app.handle().clone();
-            tauri::async_runtime::spawn(async move {
-                ui_messages::UiMessageHelper::new(app_handle, app_name, window_helper)
-                    .listen(rx)
-                    .await;
-            });
+            let helper =
+                ui_messages::UiMessageHelper::new(app.handle().clone(), app_name, window_helper);
+            let mut listener = ui_messages::UiMessageListener::new(helper, rx);
+            listener.spawn();
+            *app.state::<AppState>().ui_message_listener.lock().unwrap() = Some(listener);
+            if let Some(instance_listener) = instance_listener {
+                single_instance::listen(app.handle().clone(), instance_listener);
+            }
 
             let system_tray = SystemTray::new();
             let app_handle = app.handle().clone();
             tauri::async_runtime::block_on(async move {
                 if let Ok(menu) = system_tray.init(&app_handle).await {
                     let _tray = TrayIconBuilder::with_id("main")
-                        .icon(Image::new(SYSTEM_TRAY_ICON_BYTES, 16, 16),)
+                        .icon(system_tray::load_icon(
+                            "tray",
+                            SYSTEM_TRAY_ICON_BYTES,
+                            SYSTEM_TRAY_ICON_SIZE,
+                        ))
                         .icon_as_template(true)
                         .menu(&menu)
                         .show_menu_on_left_click(true)
@@ -179,12 +279,78 @@ fn main() -> anyhow::Result<()> {
         action_logs::write_action_log,
         action_logs::get_action_logs,
         action_logs::get_action_log_file,
+        action_logs::repair_action_logs,
+        action_logs::stream_combined_logs,
         install_cli::install_cli,
         get_env::get_env,
         file_exists::file_exists,
+        file_exists::file_exists_cached,
+        file_exists::invalidate_file_exists_cache,
         community_contributions::get_contributions,
         updates::get_pending_update,
-        updates::check_updates
+        updates::check_updates,
+        updates::update_status,
+        updates::cancel_pending_update,
+        updates::check_update_connectivity,
+        ui_messages::restart_ui_message_listener,
+        settings::set_setting,
+        settings::get_setting,
+        providers::validate_provider_config,
+        daemon::get_daemon_login_url,
+        daemon::get_daemon_login_status,
+        providers::add_provider,
+        providers::remove_provider,
+        diagnostics::copy_diagnostics,
+        diagnostics::get_config_paths,
+        diagnostics::capture_env_diagnostic,
+        window::set_zoom,
+        window::get_zoom,
+        window::set_dock_icon_visibility,
+        window::save_window_layout,
+        window::restore_window_layout,
+        workspaces::stop_workspace,
+        workspaces::delete_workspace,
+        recent_errors::get_recent_errors,
+        lifecycle::relaunch_app,
+        ports::is_port_available,
+        ports::find_free_port,
+        daemon::get_daemon_log_tail,
+        daemon::client::ping_daemon,
+        daemon::client::warm_daemon,
+        providers::get_providers_overview,
+        providers::cleanup_dangling_providers,
+        logging::set_log_level,
+        logging::get_log_level,
+        logging::set_cli_log_dir,
+        logging::list_log_archives,
+        logging::open_log_archive,
+        workspaces::open_workspace,
+        workspaces::exec_in_workspace,
+        workspaces::open_workspace_logs,
+        workspaces::validate_workspace_name,
+        child_processes::list_child_processes,
+        child_processes::kill_process,
+        operations::cancel_operation,
+        operations::write_operation_stdin,
+        workspaces::get_workspace_disk_usage,
+        workspaces::get_workspaces_disk_usage,
+        open_external::open_external,
+        app_info::get_app_info,
+        resource_watcher::get_workspaces,
+        resource_watcher::set_workspace_order,
+        resource_watcher::pin_workspace,
+        spacetime_server::stop_spacetime_server,
+        spacetime_server::get_spacetime_server_status,
+        spacetime_server::export_spacetime_data,
+        spacetime_server::import_spacetime_data,
+        spacetime_server::reset_spacetime_database,
+        install_cli::is_cli_on_path,
+        install_cli::verify_cli_integrity,
+        fix_env::get_effective_path,
+        fix_env::refresh_env,
+        single_instance::get_instance_status,
+        command_env::set_command_env,
+        command_env::unset_command_env
     ]);
 
     let app = app_builder
@@ -200,9 +366,7 @@ fn main() -> anyhow::Result<()> {
         {
             if let tauri::RunEvent::Reopen { .. } = event {
                 tauri::async_runtime::block_on(async move {
-                    if let Err(err) = reopen_tx.send(UiMessage::ShowDashboard).await {
-                        error!("Failed to broadcast show dashboard message: {}", err);
-                    };
+                    ui_messages::dispatch(&reopen_tx, UiMessage::ShowDashboard).await;
                 });
 
                 return;
@@ -229,6 +393,12 @@ fn main() -> anyhow::Result<()> {
                     }
                 }
 
+                // Closing the last window quits the app if the user opted out of the system tray.
+                if code.is_none() && Settings::close_behavior(app_handle) == CloseBehavior::Quit {
+                    info!("Close behavior is set to quit, exiting.");
+                    return;
+                }
+
                 // Otherwise, we stay alive in the system tray.
                 api.prevent_exit();
             }
@@ -247,10 +417,8 @@ fn main() -> anyhow::Result<()> {
                 }
             }
             tauri::RunEvent::Exit => {
-                kill_child_processes(std::process::id());
-                providers::check_dangling_provider(app_handle);
                 tauri::async_runtime::block_on(async move {
-                    resource_watcher::shutdown(app_handle).await;
+                    lifecycle::cleanup(app_handle).await;
                 });
             }
             _ => {}
@@ -0,0 +1,51 @@
+use crate::AppHandle;
+use log::{info, warn};
+use tauri_plugin_shell::ShellExt;
+use thiserror::Error;
+use url::Url;
+
+// Anything else, `file://` in particular, could be abused by links from deep links or
+// provider metadata to run or reveal local files
+const ALLOWED_SCHEMES: &[&str] = &["http", "https", "mailto"];
+
+#[derive(Error, Debug)]
+pub enum OpenExternalError {
+    #[error("invalid url {0}")]
+    InvalidUrl(String),
+    #[error("refusing to open {0} urls")]
+    SchemeNotAllowed(String),
+    #[error("unable to open url")]
+    Open(#[from] tauri_plugin_shell::Error),
+}
+impl serde::Serialize for OpenExternalError {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(self.to_string().as_ref())
+    }
+}
+
+#[tauri::command]
+pub fn open_external(app_handle: AppHandle, url: String) -> Result<(), OpenExternalError> {
+    let parsed = Url::parse(&url).map_err(|err| {
+        warn!("Rejected opening invalid url {:?}: {}", url, err);
+        OpenExternalError::InvalidUrl(url.clone())
+    })?;
+    if !ALLOWED_SCHEMES.contains(&parsed.scheme()) {
+        warn!(
+            "Rejected opening url with scheme {}: {:?}",
+            parsed.scheme(),
+            url
+        );
+        return Err(OpenExternalError::SchemeNotAllowed(
+            parsed.scheme().to_string(),
+        ));
+    }
+
+    info!("Opening {}", parsed);
+    #[allow(deprecated)]
+    app_handle.shell().open(parsed.as_str(), None)?;
+
+    Ok(())
+}
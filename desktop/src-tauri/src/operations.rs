@@ -0,0 +1,98 @@
+use crate::{util, AppHandle, AppState};
+use log::info;
+use std::collections::{HashMap, HashSet};
+use tauri::Manager;
+use tauri_plugin_shell::process::CommandChild;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum OperationError {
+    #[error("operation {0} not found or already finished")]
+    NotFound(String),
+    #[error("unable to write to operation {0}: {1}")]
+    Stdin(String, String),
+}
+impl serde::Serialize for OperationError {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(self.to_string().as_ref())
+    }
+}
+
+// CLI processes of long running workspace operations, keyed by the operation id the
+// frontend passed in so it can cancel them
+#[derive(Debug, Default)]
+pub struct Operations {
+    pids: HashMap<String, u32>,
+    cancelled: HashSet<String>,
+    // Processes of operations that take input, see `write_operation_stdin`
+    children: HashMap<String, CommandChild>,
+}
+
+pub fn register(app_handle: &AppHandle, operation_id: &str, pid: u32) {
+    let state = app_handle.state::<AppState>();
+    let mut operations = state.operations.lock().unwrap();
+    operations.pids.insert(operation_id.to_string(), pid);
+}
+
+// Like `register`, but keeps the process around so input can be written to it
+pub fn register_with_stdin(app_handle: &AppHandle, operation_id: &str, child: CommandChild) {
+    let state = app_handle.state::<AppState>();
+    let mut operations = state.operations.lock().unwrap();
+    operations
+        .pids
+        .insert(operation_id.to_string(), child.pid());
+    operations.children.insert(operation_id.to_string(), child);
+}
+
+// Forgets the operation, returns whether it was cancelled
+pub fn finish(app_handle: &AppHandle, operation_id: &str) -> bool {
+    let state = app_handle.state::<AppState>();
+    let mut operations = state.operations.lock().unwrap();
+    operations.pids.remove(operation_id);
+    operations.children.remove(operation_id);
+
+    operations.cancelled.remove(operation_id)
+}
+
+// Kills the CLI process of the operation together with everything it started, so nothing the
+// operation spawned outlives the cancellation
+#[tauri::command]
+pub fn cancel_operation(app_handle: AppHandle, operation_id: String) -> Result<(), OperationError> {
+    let state = app_handle.state::<AppState>();
+    let pid = {
+        let mut operations = state.operations.lock().unwrap();
+        let pid = *operations
+            .pids
+            .get(&operation_id)
+            .ok_or_else(|| OperationError::NotFound(operation_id.clone()))?;
+        operations.cancelled.insert(operation_id.clone());
+        pid
+    };
+
+    info!("Cancelling operation {} (pid {})", operation_id, pid);
+    util::kill_process_tree(pid);
+
+    Ok(())
+}
+
+// Writes `data` to the stdin of an operation registered with `register_with_stdin`
+#[tauri::command]
+pub fn write_operation_stdin(
+    app_handle: AppHandle,
+    operation_id: String,
+    data: String,
+) -> Result<(), OperationError> {
+    let state = app_handle.state::<AppState>();
+    let mut operations = state.operations.lock().unwrap();
+    let child = operations
+        .children
+        .get_mut(&operation_id)
+        .ok_or_else(|| OperationError::NotFound(operation_id.clone()))?;
+
+    child
+        .write(data.as_bytes())
+        .map_err(|err| OperationError::Stdin(operation_id, err.to_string()))
+}
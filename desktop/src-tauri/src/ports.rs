@@ -0,0 +1,41 @@
+use serde::Serialize;
+use std::net::{Ipv4Addr, TcpListener};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum PortError {
+    #[error("invalid port range {0}-{1}")]
+    InvalidRange(u16, u16),
+    #[error("no free port in range {0}-{1}")]
+    NoFreePort(u16, u16),
+}
+impl Serialize for PortError {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(self.to_string().as_ref())
+    }
+}
+
+// The listener is dropped right away, so the port can still be taken before the caller binds it
+pub fn is_port_free(port: u16) -> bool {
+    TcpListener::bind((Ipv4Addr::LOCALHOST, port)).is_ok()
+}
+
+#[tauri::command]
+pub fn is_port_available(port: u16) -> bool {
+    is_port_free(port)
+}
+
+// Returns the first free port in the inclusive range `start..=end`
+#[tauri::command]
+pub fn find_free_port(start: u16, end: u16) -> Result<u16, PortError> {
+    if start == 0 || start > end {
+        return Err(PortError::InvalidRange(start, end));
+    }
+
+    (start..=end)
+        .find(|port| is_port_free(*port))
+        .ok_or(PortError::NoFreePort(start, end))
+}
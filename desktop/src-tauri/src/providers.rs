@@ -1,12 +1,262 @@
+use crate::commands::add_provider::AddProviderCommand;
 use crate::commands::delete_pro_instance::DeleteProInstanceCommand;
 use crate::commands::list_pro_instances::ListProInstancesCommand;
-use crate::commands::{delete_provider::DeleteProviderCommand, DevpodCommandConfig};
+use crate::commands::list_providers::ListProvidersCommand;
+use crate::commands::validate_provider_config::{
+    ProviderValidationResult, ValidateProviderConfigCommand,
+};
+use crate::commands::{
+    delete_provider::DeleteProviderCommand, DevpodCommandConfig, DevpodCommandError,
+};
 use crate::resource_watcher::{Identifiable, ProInstance};
 use crate::AppHandle;
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::Duration;
+use tauri::Emitter;
+use tauri_plugin_notification::NotificationExt;
 use tauri_plugin_store::StoreExt;
+use thiserror::Error;
 
-pub fn check_dangling_provider(app_handle: &AppHandle) {
+const PROVIDERS_CHANGED_EVENT: &str = "providers-changed";
+const PROVIDER_HEALTH_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Error, Debug)]
+pub enum ProviderError {
+    #[error("provider {0} already exists")]
+    AlreadyExists(String),
+    #[error("provider {0} is still in use by a workspace")]
+    InUse(String),
+    #[error(transparent)]
+    Command(#[from] DevpodCommandError),
+}
+impl ProviderError {
+    // Tells the expected CLI failures apart from other command errors by their stderr
+    fn from_command_error(name: &str, err: DevpodCommandError) -> Self {
+        if let DevpodCommandError::NonZeroExit { stderr, .. } = &err {
+            let stderr = stderr.to_lowercase();
+            if stderr.contains("already exists") {
+                return ProviderError::AlreadyExists(name.to_string());
+            }
+            if stderr.contains("still using") || stderr.contains("in use") {
+                return ProviderError::InUse(name.to_string());
+            }
+        }
+
+        ProviderError::Command(err)
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            ProviderError::AlreadyExists(_) => "alreadyExists",
+            ProviderError::InUse(_) => "inUse",
+            ProviderError::Command(err) => err.kind(),
+        }
+    }
+}
+impl serde::Serialize for ProviderError {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("ProviderError", 2)?;
+        state.serialize_field("kind", self.kind())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.end()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ProviderAction {
+    Added,
+    Removed,
+}
+
+// Returned to the caller and broadcast so cached provider lists in the UI can refresh
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProviderChange {
+    name: String,
+    action: ProviderAction,
+}
+
+#[tauri::command]
+pub async fn add_provider(
+    app_handle: AppHandle,
+    name: String,
+    source: String,
+    options: HashMap<String, String>,
+) -> Result<ProviderChange, ProviderError> {
+    AddProviderCommand::new(name.clone(), source, options)
+        .exec(&app_handle)
+        .await
+        .map_err(|err| ProviderError::from_command_error(&name, err))?;
+    info!("Added provider {}", name);
+
+    Ok(notify_providers_changed(
+        &app_handle,
+        ProviderChange {
+            name,
+            action: ProviderAction::Added,
+        },
+    ))
+}
+
+#[tauri::command]
+pub async fn remove_provider(
+    app_handle: AppHandle,
+    name: String,
+) -> Result<ProviderChange, ProviderError> {
+    DeleteProviderCommand::new(name.clone())
+        .exec(&app_handle)
+        .await
+        .map_err(|err| ProviderError::from_command_error(&name, err))?;
+    info!("Removed provider {}", name);
+
+    Ok(notify_providers_changed(
+        &app_handle,
+        ProviderChange {
+            name,
+            action: ProviderAction::Removed,
+        },
+    ))
+}
+
+fn notify_providers_changed(app_handle: &AppHandle, change: ProviderChange) -> ProviderChange {
+    if let Err(err) = app_handle.emit(PROVIDERS_CHANGED_EVENT, change.clone()) {
+        warn!("Failed to emit {} event: {}", PROVIDERS_CHANGED_EVENT, err);
+    }
+
+    change
+}
+
+// Runs the provider options through the CLI without persisting them, so misconfigured
+// credentials surface before the first workspace is created.
+#[tauri::command]
+pub async fn validate_provider_config(
+    app_handle: AppHandle,
+    name: String,
+    options: HashMap<String, String>,
+) -> Result<ProviderValidationResult, DevpodCommandError> {
+    let result = ValidateProviderConfigCommand::new(name.clone(), options)
+        .exec(&app_handle)
+        .await?;
+    if !result.valid {
+        info!(
+            "Provider {} configuration is invalid: {}",
+            name,
+            result.errors.join("; ")
+        );
+    }
+
+    Ok(result)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ProviderHealth {
+    Reachable,
+    Misconfigured,
+    Unknown,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProviderOverview {
+    name: String,
+    is_default: bool,
+    health: ProviderHealth,
+    // Why the provider is misconfigured or its health is unknown
+    errors: Vec<String>,
+}
+
+// Lists the providers with their health, checked concurrently by validating each provider's
+// stored options. A check that fails to run or exceeds PROVIDER_HEALTH_TIMEOUT is `Unknown`.
+#[tauri::command]
+pub async fn get_providers_overview(
+    app_handle: AppHandle,
+) -> Result<Vec<ProviderOverview>, DevpodCommandError> {
+    let providers = ListProvidersCommand::new().exec(&app_handle).await?;
+
+    let checks: Vec<_> = providers
+        .into_iter()
+        .map(|(name, provider)| {
+            let app_handle = app_handle.clone();
+            tauri::async_runtime::spawn(async move {
+                let (health, errors) = check_provider_health(&app_handle, &name).await;
+
+                ProviderOverview {
+                    name,
+                    is_default: provider.default,
+                    health,
+                    errors,
+                }
+            })
+        })
+        .collect();
+
+    let mut overview = vec![];
+    for check in checks {
+        match check.await {
+            Ok(provider) => overview.push(provider),
+            Err(err) => warn!("Provider health check failed: {}", err),
+        }
+    }
+    overview.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(overview)
+}
+
+// Validates the provider's stored options, with the reasons if it isn't reachable
+pub async fn check_provider_health(
+    app_handle: &AppHandle,
+    name: &str,
+) -> (ProviderHealth, Vec<String>) {
+    let check = ValidateProviderConfigCommand::new(name.to_string(), HashMap::new())
+        .exec_with_timeout(app_handle, PROVIDER_HEALTH_TIMEOUT)
+        .await;
+    match check {
+        Ok(result) if result.valid => (ProviderHealth::Reachable, vec![]),
+        Ok(result) => (ProviderHealth::Misconfigured, result.errors),
+        Err(err) => (ProviderHealth::Unknown, vec![err.to_string()]),
+    }
+}
+
+// Runs the same cleanup as on exit on demand, e.g. from a "clean up" button in the settings.
+// Returns the providers that were removed, nothing once everything has been cleaned up.
+#[tauri::command]
+pub async fn cleanup_dangling_providers(app_handle: AppHandle) -> Vec<String> {
+    let cleanup_handle = app_handle.clone();
+    let cleaned =
+        tauri::async_runtime::spawn_blocking(move || check_dangling_provider(&cleanup_handle))
+            .await
+            .unwrap_or_else(|err| {
+                error!("Dangling provider cleanup failed: {}", err);
+                vec![]
+            });
+
+    if !cleaned.is_empty() {
+        let res = app_handle
+            .notification()
+            .builder()
+            .title("Cleaned up providers")
+            .body(format!("Removed {}", cleaned.join(", ")))
+            .show();
+        if let Err(err) = res {
+            error!("Unable to send provider cleanup notification: {}", err);
+        }
+    }
+
+    cleaned
+}
+
+// Deletes providers whose setup was interrupted and returns the ones that are gone now. Providers
+// that couldn't be deleted stay recorded for the next attempt.
+pub fn check_dangling_provider(app_handle: &AppHandle) -> Vec<String> {
     let dangling_provider_key = "danglingProviders"; // WARN: needs to match the key defined in typescript
     let filename = ".providers.json"; // WARN: needs to match the file name defined in typescript
 
@@ -14,7 +264,7 @@ pub fn check_dangling_provider(app_handle: &AppHandle) {
     let store = app_handle.store(filename);
     if store.is_err() {
         error!("unable to open store {}", filename);
-        return;
+        return vec![];
     }
     let store = store.unwrap();
     let dangling_providers = store
@@ -25,13 +275,13 @@ pub fn check_dangling_provider(app_handle: &AppHandle) {
 
     if dangling_providers.is_none() {
         debug!("No dangling providers found");
-        return;
+        return vec![];
     }
     let dangling_providers = dangling_providers.unwrap();
 
     if dangling_providers.is_empty() {
         debug!("No dangling providers found");
-        return;
+        return vec![];
     }
 
     info!(
@@ -47,22 +297,34 @@ pub fn check_dangling_provider(app_handle: &AppHandle) {
         }
     };
 
-    for dangling_provider in dangling_providers.iter() {
+    let mut cleaned = vec![];
+    let mut remaining = vec![];
+    for dangling_provider in dangling_providers.into_iter() {
         // Make sure we clean up accompanying pro instances
         check_pro_instances(app_handle, &pro_instances, &dangling_provider);
 
         if DeleteProviderCommand::new(dangling_provider.clone())
             .exec_blocking(&app_handle)
             .is_ok()
-            && store.delete(dangling_provider_key)
         {
             info!(
                 "Successfully deleted dangling provider: {}",
                 dangling_provider
             );
-            let _ = store.save();
+            cleaned.push(dangling_provider);
+        } else {
+            remaining.push(dangling_provider);
         }
     }
+
+    if remaining.is_empty() {
+        store.delete(dangling_provider_key);
+    } else {
+        store.set(dangling_provider_key, serde_json::json!(remaining));
+    }
+    let _ = store.save();
+
+    cleaned
 }
 
 fn check_pro_instances(
@@ -0,0 +1,70 @@
+use crate::{AppHandle, AppState};
+use chrono::{DateTime, Utc};
+use log::{error, warn};
+use serde::Serialize;
+use std::collections::VecDeque;
+use tauri::{Emitter, Manager};
+
+const MAX_RECENT_ERRORS: usize = 50;
+const NEW_ERROR_EVENT: &str = "new-error";
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecentError {
+    timestamp: DateTime<Utc>,
+    source: String,
+    message: String,
+}
+
+// Bounded so a failure loop in a background task can't grow memory unchecked
+#[derive(Debug, Default)]
+pub struct RecentErrors {
+    errors: VecDeque<RecentError>,
+}
+impl RecentErrors {
+    fn push(&mut self, error: RecentError) {
+        if self.errors.len() == MAX_RECENT_ERRORS {
+            self.errors.pop_front();
+        }
+        self.errors.push_back(error);
+    }
+
+    pub fn len(&self) -> usize {
+        self.errors.len()
+    }
+}
+
+// Logs the error and records it for the problems panel in the UI.
+// `source` is expected to be `module_path!()` of the caller.
+pub fn report_error(app_handle: &AppHandle, source: &str, message: impl Into<String>) {
+    let message = message.into();
+    error!(target: source, "{}", message);
+
+    let recent_error = RecentError {
+        timestamp: Utc::now(),
+        source: source
+            .split_once("::")
+            .map_or(source, |(_, module)| module)
+            .to_string(),
+        message,
+    };
+    let state = app_handle.state::<AppState>();
+    state.recent_errors.lock().unwrap().push(recent_error.clone());
+
+    if let Err(err) = app_handle.emit(NEW_ERROR_EVENT, recent_error) {
+        warn!("Failed to emit {} event: {}", NEW_ERROR_EVENT, err);
+    }
+}
+
+// Oldest first, at most MAX_RECENT_ERRORS entries
+#[tauri::command]
+pub fn get_recent_errors(state: tauri::State<'_, AppState>) -> Vec<RecentError> {
+    state
+        .recent_errors
+        .lock()
+        .unwrap()
+        .errors
+        .iter()
+        .cloned()
+        .collect()
+}
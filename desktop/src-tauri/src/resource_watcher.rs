@@ -1,18 +1,27 @@
 use crate::{
     commands::{
         list_pro_instances::ListProInstancesCommand, list_workspaces::ListWorkspacesCommand,
-        start_daemon::StartDaemonCommand, DevpodCommandError,
+        start_daemon::StartDaemonCommand, workspace_status::WorkspaceState, DevpodCommandError,
     },
     daemon,
-    system_tray::{ToSystemTraySubmenu, SYSTEM_TRAY_ICON_BYTES, WARNING_SYSTEM_TRAY_ICON_BYTES},
+    system_tray::{
+        load_icon, ToSystemTraySubmenu, SYSTEM_TRAY_ICON_BYTES, SYSTEM_TRAY_ICON_SIZE,
+        WARNING_SYSTEM_TRAY_ICON_BYTES,
+    },
     ui_messages,
+    workspaces::{emit_lifecycle_event, WorkspaceLifecycle},
 };
 use crate::{AppHandle, AppState};
 use anyhow::anyhow;
 use dirs::home_dir;
-use log::{debug, error, info};
-use serde::Deserialize;
-use std::{collections::HashSet, hash::Hash, time};
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    hash::Hash,
+    path::PathBuf,
+    time,
+};
 use tauri::{
     async_runtime::Receiver,
     image::Image,
@@ -21,6 +30,8 @@ use tauri::{
 };
 use tauri_plugin_notification::NotificationExt;
 use tauri_plugin_shell::process::{CommandChild, CommandEvent};
+use tauri_plugin_store::StoreExt;
+use thiserror::Error;
 
 
 pub trait Identifiable {
@@ -31,14 +42,65 @@ pub trait Identifiable {
 pub struct WorkspacesState {
     workspaces: Vec<Workspace>,
     submenu: Option<Submenu<tauri::Wry>>,
+    // Last known state per workspace id, recorded by lifecycle commands like stop
+    states: HashMap<String, WorkspaceState>,
+    // Set while `workspaces` still comes from the snapshot of a previous run
+    stale: bool,
+    // Whether `workspaces` has been loaded from the CLI at least once
+    polled: bool,
+    // Order the user arranged the workspaces in, ids missing here follow in the CLI's order
+    order: Vec<String>,
+    // Pinned workspaces come first, in their arranged order
+    pinned: HashSet<String>,
+}
+
+#[derive(Error, Debug)]
+pub enum WorkspaceOrderError {
+    #[error("workspace {0} not found")]
+    NotFound(String),
+    #[error("unable to open store {0}")]
+    Store(String),
+    #[error("unable to save store {0}")]
+    Save(String),
+}
+impl serde::Serialize for WorkspaceOrderError {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(self.to_string().as_ref())
+    }
 }
 
-#[derive(Deserialize, Clone)]
+// What the dashboard renders before and after the first live poll
+#[derive(Serialize)]
+pub struct WorkspacesSnapshot {
+    workspaces: Vec<Workspace>,
+    stale: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Workspace {
     id: String,
+    context: Option<String>,
+    provider: Option<WorkspaceProvider>,
+    machine: Option<WorkspaceMachine>,
+    #[serde(default)]
+    pinned: bool,
     #[serde(skip)]
     menu_item: Option<MenuItem<tauri::Wry>>,
 }
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct WorkspaceProvider {
+    name: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceMachine {
+    machine_id: Option<String>,
+}
 impl Identifiable for Workspace {
     type ID = String;
     fn id(&self) -> String {
@@ -51,15 +113,50 @@ impl PartialEq for Workspace {
     }
 }
 impl Workspace {
+    pub fn context(&self) -> Option<&str> {
+        return self.context.as_deref();
+    }
+
+    pub fn provider(&self) -> Option<&str> {
+        return self.provider.as_ref().map(|p| p.name.as_str());
+    }
+
+    pub fn machine_id(&self) -> Option<&str> {
+        return self.machine.as_ref().and_then(|m| m.machine_id.as_deref());
+    }
+
+    // Directories the CLI keeps for this workspace: its own, holding the content for local
+    // providers, and the one of its machine if it has one
+    pub fn dirs(&self) -> anyhow::Result<Vec<PathBuf>> {
+        let context_dir = PathBuf::from(Daemon::get_home()?)
+            .join("contexts")
+            .join(self.context().unwrap_or("default"));
+
+        let mut dirs = vec![context_dir.join("workspaces").join(&self.id)];
+        if let Some(machine_id) = self.machine_id() {
+            dirs.push(context_dir.join("machines").join(machine_id));
+        }
+
+        return Ok(dirs);
+    }
+
     fn new_menu_item(&self, app_handle: &AppHandle) -> tauri::Result<MenuItem<tauri::Wry>> {
         return MenuItem::with_id(
             app_handle,
             WorkspacesState::item_id(&self.id()),
-            self.id(),
+            self.menu_label(),
             true,
             None::<&str>,
         );
     }
+
+    fn menu_label(&self) -> String {
+        if self.pinned {
+            format!("★ {}", self.id)
+        } else {
+            self.id.clone()
+        }
+    }
 }
 
 impl WorkspacesState {
@@ -74,6 +171,19 @@ impl WorkspacesState {
         self.submenu = Some(submenu);
     }
 
+    pub fn set_state(&mut self, id: String, state: WorkspaceState) {
+        self.states.insert(id, state);
+    }
+
+    pub fn find_workspace(&self, id: &str) -> Option<&Workspace> {
+        self.workspaces.iter().find(|w| w.id == id)
+    }
+
+    // Whether the workspaces reflect what the CLI reports rather than the startup snapshot
+    pub fn is_polled(&self) -> bool {
+        self.polled
+    }
+
     pub async fn load_workspaces(
         app_handle: &AppHandle,
     ) -> Result<Vec<Workspace>, DevpodCommandError> {
@@ -81,6 +191,182 @@ impl WorkspacesState {
 
         return list_workspaces_cmd.exec(app_handle).await;
     }
+
+    fn snapshot_path(app_handle: &AppHandle) -> anyhow::Result<PathBuf> {
+        let dir = app_handle.path().app_local_data_dir()?;
+
+        Ok(dir.join(WORKSPACES_SNAPSHOT_FILE))
+    }
+
+    // Seeds the state with the workspaces of the previous run so the dashboard has something
+    // to show before the CLI answers. The result is marked stale until the first live poll.
+    fn load_snapshot(&mut self, app_handle: &AppHandle) -> anyhow::Result<()> {
+        let path = Self::snapshot_path(app_handle)?;
+        if !path.exists() {
+            return Ok(());
+        }
+        let mut workspaces: Vec<Workspace> = serde_json::from_slice(&std::fs::read(path)?)?;
+        self.sort(&mut workspaces);
+
+        info!("Loaded {} workspaces from snapshot", workspaces.len());
+        self.workspaces = workspaces;
+        self.stale = true;
+
+        Ok(())
+    }
+
+    fn persist_snapshot(&self, app_handle: &AppHandle) -> anyhow::Result<()> {
+        let path = Self::snapshot_path(app_handle)?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_json::to_vec(&self.workspaces)?)?;
+
+        Ok(())
+    }
+
+    // Pinned workspaces first, then the arranged order. The sort is stable, so new workspaces
+    // are appended at the end in the order the CLI lists them.
+    fn sort(&self, workspaces: &mut [Workspace]) {
+        for workspace in workspaces.iter_mut() {
+            workspace.pinned = self.pinned.contains(&workspace.id);
+        }
+        workspaces.sort_by_key(|workspace| {
+            let position = self
+                .order
+                .iter()
+                .position(|id| id == &workspace.id)
+                .unwrap_or(usize::MAX);
+
+            (!workspace.pinned, position)
+        });
+    }
+
+    // Puts `ids` first, the workspaces missing from `ids` keep their previous relative order
+    fn set_order(&mut self, ids: Vec<String>) {
+        let mut order: Vec<String> = vec![];
+        for id in ids.into_iter().chain(self.order.drain(..)) {
+            if !order.contains(&id) {
+                order.push(id);
+            }
+        }
+        self.order = order;
+        self.prune_order();
+    }
+
+    // Forgets the order and pins of deleted workspaces so they don't pile up in the store.
+    // Skipped until the CLI listed the workspaces, a snapshot may be missing new ones.
+    fn prune_order(&mut self) {
+        if !self.polled {
+            return;
+        }
+        let ids: HashSet<&String> = self.workspaces.iter().map(|w| &w.id).collect();
+        self.order.retain(|id| ids.contains(id));
+        self.pinned.retain(|id| ids.contains(id));
+    }
+
+    // Applies a changed order or pins to the workspaces and the tray submenu
+    fn rearrange(&mut self) {
+        let mut workspaces = std::mem::take(&mut self.workspaces);
+        self.sort(&mut workspaces);
+        self.workspaces = workspaces;
+
+        let Some(submenu) = &self.submenu else {
+            return;
+        };
+        for workspace in &self.workspaces {
+            if let Some(menu_item) = &workspace.menu_item {
+                _ = submenu.remove(menu_item);
+                _ = menu_item.set_text(workspace.menu_label());
+                _ = submenu.append(menu_item);
+            }
+        }
+    }
+
+    fn load_order(&mut self, app_handle: &AppHandle) -> anyhow::Result<()> {
+        let store = app_handle.store(WORKSPACE_ORDER_FILE_NAME)?;
+        if let Some(order) = store.get(WORKSPACE_ORDER_KEY) {
+            self.order = serde_json::from_value(order)?;
+        }
+        if let Some(pinned) = store.get(WORKSPACE_PINNED_KEY) {
+            self.pinned = serde_json::from_value(pinned)?;
+        }
+
+        Ok(())
+    }
+
+    fn persist_order(&self, app_handle: &AppHandle) -> Result<(), WorkspaceOrderError> {
+        let store = app_handle
+            .store(WORKSPACE_ORDER_FILE_NAME)
+            .map_err(|_| WorkspaceOrderError::Store(WORKSPACE_ORDER_FILE_NAME.to_string()))?;
+        store.set(WORKSPACE_ORDER_KEY, serde_json::json!(self.order));
+        store.set(WORKSPACE_PINNED_KEY, serde_json::json!(self.pinned));
+
+        store
+            .save()
+            .map_err(|err| WorkspaceOrderError::Save(err.to_string()))
+    }
+
+    pub fn snapshot(&self) -> WorkspacesSnapshot {
+        WorkspacesSnapshot {
+            workspaces: self.workspaces.clone(),
+            stale: self.stale,
+        }
+    }
+}
+
+// Returns the workspaces known to the tray, flagged as stale while they still come from the
+// snapshot of a previous run
+#[tauri::command]
+pub async fn get_workspaces(app_handle: AppHandle) -> WorkspacesSnapshot {
+    app_handle
+        .state::<AppState>()
+        .workspaces
+        .read()
+        .await
+        .snapshot()
+}
+
+// Arranges the workspaces in the order of `ids`. Workspaces missing from `ids` keep their
+// previous relative order after the listed ones, ids of deleted workspaces are dropped.
+#[tauri::command]
+pub async fn set_workspace_order(
+    app_handle: AppHandle,
+    ids: Vec<String>,
+) -> Result<(), WorkspaceOrderError> {
+    let state = app_handle.state::<AppState>();
+    let mut workspaces_state = state.workspaces.write().await;
+
+    workspaces_state.set_order(ids);
+    workspaces_state.persist_order(&app_handle)?;
+    workspaces_state.rearrange();
+
+    Ok(())
+}
+
+// Pinned workspaces are listed first in the dashboard and the tray
+#[tauri::command]
+pub async fn pin_workspace(
+    app_handle: AppHandle,
+    id: String,
+    pinned: bool,
+) -> Result<(), WorkspaceOrderError> {
+    let state = app_handle.state::<AppState>();
+    let mut workspaces_state = state.workspaces.write().await;
+    if workspaces_state.find_workspace(&id).is_none() {
+        return Err(WorkspaceOrderError::NotFound(id));
+    }
+
+    if pinned {
+        workspaces_state.pinned.insert(id);
+    } else {
+        workspaces_state.pinned.remove(&id);
+    }
+    workspaces_state.prune_order();
+    workspaces_state.persist_order(&app_handle)?;
+    workspaces_state.rearrange();
+
+    Ok(())
 }
 
 impl ToSystemTraySubmenu for WorkspacesState {
@@ -102,8 +388,14 @@ impl ToSystemTraySubmenu for WorkspacesState {
 }
 
 static CAPABILITY_DAEMON: &str = "daemon";
+static WORKSPACES_SNAPSHOT_FILE: &str = "workspaces_snapshot.json";
+static WORKSPACE_ORDER_FILE_NAME: &str = ".workspace_order.json";
+static WORKSPACE_ORDER_KEY: &str = "order";
+static WORKSPACE_PINNED_KEY: &str = "pinned";
 static MAX_RETRY_COUNT: i64 = 10;
 static RETRY_DEBUG_THRESHOLD: i64 = 7;
+// Number of pings the average daemon latency is computed over
+const LATENCY_WINDOW: usize = 10;
 #[derive(Default)]
 pub struct ProState {
     instances: Vec<ProInstance>,
@@ -142,6 +434,14 @@ impl ProState {
         self.submenu = Some(submenu);
     }
 
+    pub fn all_ready(&self) -> bool {
+        self.all_ready
+    }
+
+    pub fn instances(&self) -> &[ProInstance] {
+        return &self.instances;
+    }
+
     pub fn find_instance(&self, pro_id: String) -> Option<&ProInstance> {
         return self.instances.iter().find(|i| i.id() == pro_id);
     }
@@ -212,11 +512,17 @@ pub struct Daemon {
 
     notified_user_daemon_failed: bool,
     notified_login_required: bool,
+    // Where the user can log back in, only known while login is required
+    login_url: Option<String>,
+    log_path: Option<PathBuf>,
+    // Round trips of the most recent pings, oldest first
+    latencies: VecDeque<time::Duration>,
 }
 impl Daemon {
     pub fn new(context: Option<String>, provider: Option<String>) -> anyhow::Result<Daemon> {
         let socket_addr = Daemon::get_socket_addr(context.clone(), provider.clone())?;
         let client = daemon::client::Client::new(socket_addr);
+        let log_path = Daemon::get_log_path(context, provider.clone()).ok();
 
         return Ok(Daemon {
             status: daemon::DaemonStatus::default(),
@@ -224,11 +530,32 @@ impl Daemon {
             retry_count: 0,
             notified_user_daemon_failed: false,
             notified_login_required: false,
+            login_url: None,
+            log_path,
+            latencies: VecDeque::with_capacity(LATENCY_WINDOW),
             provider,
             client,
         });
     }
 
+    // The CLI writes the daemon log next to its socket
+    fn get_log_path(context: Option<String>, provider: Option<String>) -> anyhow::Result<PathBuf> {
+        let provider = provider.ok_or(anyhow!("provider not set for pro instance"))?;
+        let context = context.unwrap_or("default".to_string());
+
+        return Ok(PathBuf::from(Self::get_home()?)
+            .join("contexts")
+            .join(context)
+            .join("providers")
+            .join(provider)
+            .join("daemon")
+            .join("daemon.log"));
+    }
+
+    pub fn log_path(&self) -> Option<&PathBuf> {
+        return self.log_path.as_ref();
+    }
+
     fn get_socket_addr(
         context: Option<String>,
         provider: Option<String>,
@@ -252,7 +579,7 @@ impl Daemon {
         }
     }
 
-    fn get_home() -> anyhow::Result<String> {
+    pub fn get_home() -> anyhow::Result<String> {
         if let Ok(devpod_home) = std::env::var("DEVPOD_HOME") {
             return Ok(devpod_home);
         }
@@ -275,6 +602,50 @@ impl Daemon {
         return self.client.status().await;
     }
 
+    fn set_status(&mut self, mut status: daemon::DaemonStatus) {
+        if status.login_required {
+            if status.login_provider.is_none() {
+                status.login_provider = self.provider.clone();
+            }
+        } else {
+            status.login_provider = None;
+            status.login_reason = None;
+        }
+
+        self.status = status;
+    }
+
+    pub fn client(&self) -> &daemon::client::Client {
+        return &self.client;
+    }
+
+    // Adds a ping round trip and returns the average over the last LATENCY_WINDOW pings
+    pub fn record_latency(&mut self, latency: time::Duration) -> time::Duration {
+        if self.latencies.len() == LATENCY_WINDOW {
+            self.latencies.pop_front();
+        }
+        self.latencies.push_back(latency);
+
+        return self.latencies.iter().sum::<time::Duration>() / self.latencies.len() as u32;
+    }
+
+    pub fn login_url(&self) -> Option<&String> {
+        return self.login_url.as_ref();
+    }
+
+    // Asks the daemon for its login URL, falling back to the platform's login page
+    pub async fn refresh_login_url(&mut self, host: &str) -> &String {
+        let url = match self.client.login_url().await {
+            Ok(url) => url,
+            Err(err) => {
+                debug!("[{}] daemon did not provide a login url: {}", host, err);
+                format!("https://{}/login", host)
+            }
+        };
+
+        return self.login_url.insert(url);
+    }
+
     pub async fn proxy_request(
         &self,
         req: daemon::client::Request,
@@ -296,7 +667,11 @@ impl Daemon {
                 self.command = Some(command);
             }
             Err(err) => {
-                error!("[{}] Failed to spawn daemon command {:?}", host, err);
+                crate::recent_errors::report_error(
+                    app_handle,
+                    module_path!(),
+                    format!("[{}] Failed to spawn daemon command {:?}", host, err),
+                );
             }
         }
     }
@@ -304,6 +679,7 @@ impl Daemon {
     pub async fn try_stop(&mut self) {
         if let Some(command) = self.command.take() {
             let pid = command.1.pid();
+            crate::child_processes::untrack(pid);
             if let Err(err) = command.1.kill() {
                 debug!("Failed to kill command {:?}", err);
                 // kill it with fire
@@ -312,6 +688,7 @@ impl Daemon {
         }
         self.command = None;
         self.status = daemon::DaemonStatus::default();
+        self.latencies.clear();
     }
 
     fn should_retry(&mut self, app_handle: &AppHandle) -> bool {
@@ -339,21 +716,21 @@ impl Daemon {
         host: String,
         app_handle: &AppHandle,
     ) -> Result<(Receiver<CommandEvent>, CommandChild), DevpodCommandError> {
-        let (mut rx, child) = StartDaemonCommand::new(host.clone(), self.should_debug())
-            .command(app_handle)?
-            .spawn()?;
+        let (mut rx, child) =
+            StartDaemonCommand::new(host.clone(), self.should_debug()).spawn(app_handle)?;
 
         tokio::select! {
             status = self.get_initial_status(&mut rx) => {
                 if let Ok(status) = status {
-                    self.status = status;
+                    self.set_status(status);
                     if self.status.login_required {
+                        self.refresh_login_url(&host).await;
                         self.try_notify_login(host, app_handle).await;
                     }
                 }
             },
             _ = tokio::time::sleep(tokio::time::Duration::from_secs(30)) => {
-                return Err(DevpodCommandError::Any(anyhow!("Timed out waiting for daemon to start")));
+                return Err(DevpodCommandError::Timeout);
             }
         }
 
@@ -386,15 +763,15 @@ impl Daemon {
 
         let msg = ui_messages::LoginRequiredMsg {
             host,
-            provider: self.provider.clone().unwrap_or("".to_string()),
+            provider: self.status.login_provider.clone().unwrap_or("".to_string()),
+            reason: self.status.login_reason.clone(),
         };
-        let _ = app_handle
-            .state::<AppState>()
-            .ui_messages
-            .send(ui_messages::UiMessage::LoginRequired(msg))
-            .await;
-
-        self.notified_login_required = true;
+        // a dropped message is retried on the next watch cycle
+        self.notified_login_required = ui_messages::dispatch(
+            &app_handle.state::<AppState>().ui_messages,
+            ui_messages::UiMessage::LoginRequired(msg),
+        )
+        .await;
     }
 
     fn try_notify_failed(&mut self, app_handle: &AppHandle) {
@@ -423,6 +800,16 @@ impl ToSystemTraySubmenu for ProState {
 
 pub fn setup(app_handle: &AppHandle) {
     let state = app_handle.state::<AppState>();
+    // nothing else holds the lock this early
+    if let Ok(mut workspaces_state) = state.workspaces.try_write() {
+        if let Err(err) = workspaces_state.load_order(app_handle) {
+            warn!("Failed to load workspace order: {}", err);
+        }
+        if let Err(err) = workspaces_state.load_snapshot(app_handle) {
+            warn!("Failed to load workspaces snapshot: {}", err);
+        }
+    }
+
     let mut resource_handles = state.resources_handles.lock().unwrap();
     // daemon watcher
     let daemon_app_handle = app_handle.clone();
@@ -455,11 +842,13 @@ pub async fn shutdown(app_handle: &AppHandle) {
     info!("Shutting down resource watchers");
     let state = app_handle.state::<AppState>();
     // shut down background tasks
-    let mut handles = state.resources_handles.lock().unwrap();
-    for handle in handles.iter() {
-        handle.abort();
+    {
+        let mut handles = state.resources_handles.lock().unwrap();
+        for handle in handles.iter() {
+            handle.abort();
+        }
+        handles.clear();
     }
-    handles.clear();
     // shut down daemons
     let mut pro_state = state.pro.write().await;
     for pro_instance in pro_state.instances.iter_mut() {
@@ -500,7 +889,12 @@ async fn watch_daemons(app_handle: &AppHandle) -> anyhow::Result<()> {
         }
         match daemon.get_status().await {
             Ok(status) => {
-                daemon.status = status;
+                daemon.set_status(status);
+                if !daemon.status.login_required {
+                    daemon.login_url = None;
+                } else if daemon.login_url.is_none() {
+                    daemon.refresh_login_url(&id).await;
+                }
                 match daemon.status.state {
                     daemon::DaemonState::Running => {
                         daemon.retry_count = 0;
@@ -533,6 +927,7 @@ async fn watch_daemons(app_handle: &AppHandle) -> anyhow::Result<()> {
                         }
                         // kill the current command and restart on the next iteration
                         crate::util::kill_process(cmd.1.pid());
+                        crate::child_processes::untrack(cmd.1.pid());
                         daemon.command = None;
                     }
                     None => {
@@ -554,8 +949,12 @@ async fn watch_daemons(app_handle: &AppHandle) -> anyhow::Result<()> {
         // update main system tray icon
         if let Some(main_tray) = app_handle.tray_by_id("main") {
             let icon = match pro_state.all_ready {
-                true => Image::new(SYSTEM_TRAY_ICON_BYTES, 16, 16),
-                false => Image::new(WARNING_SYSTEM_TRAY_ICON_BYTES, 16, 16),
+                true => load_icon("tray", SYSTEM_TRAY_ICON_BYTES, SYSTEM_TRAY_ICON_SIZE),
+                false => load_icon(
+                    "tray warning",
+                    WARNING_SYSTEM_TRAY_ICON_BYTES,
+                    SYSTEM_TRAY_ICON_SIZE,
+                ),
             };
             let _ = main_tray.set_icon(Some(icon));
             let _ = main_tray.set_icon_as_template(true);
@@ -574,12 +973,42 @@ async fn handle_workspaces(app_handle: &AppHandle) {
     let mut workspaces = workspaces.unwrap();
     let state = app_handle.state::<AppState>();
     let state = &mut state.workspaces.write().await;
-    if workspaces == state.workspaces {
+    state.sort(&mut workspaces);
+    let was_stale = std::mem::replace(&mut state.stale, false);
+    if !was_stale && workspaces == state.workspaces {
         return;
     }
 
+    // Only changes between live polls are announced, the first one just establishes what exists
+    if state.polled {
+        let previous: HashSet<String> = state.workspaces.iter().map(|w| w.id()).collect();
+        let current: HashSet<String> = workspaces.iter().map(|w| w.id()).collect();
+        for id in current.difference(&previous) {
+            emit_lifecycle_event(app_handle, WorkspaceLifecycle::Created, id, None);
+        }
+        for id in previous.difference(&current) {
+            // deletes through the app have been announced by `delete_workspace` already
+            if state.states.get(id) != Some(&WorkspaceState::NotFound) {
+                emit_lifecycle_event(
+                    app_handle,
+                    WorkspaceLifecycle::Deleted,
+                    id,
+                    Some(WorkspaceState::NotFound),
+                );
+            }
+        }
+    }
+    state.polled = true;
+
     if let Some(submenu) = &state.submenu {
-        let (removed, added) = diff_mut(&state.workspaces, &mut workspaces);
+        // snapshot entries never got menu items, so every live workspace needs a new one
+        let no_workspaces = vec![];
+        let current = if was_stale {
+            &no_workspaces
+        } else {
+            &state.workspaces
+        };
+        let (removed, added) = diff_mut(current, &mut workspaces);
         for w in removed {
             if let Some(menu_item) = &w.menu_item {
                 _ = submenu.remove(menu_item);
@@ -592,7 +1021,11 @@ async fn handle_workspaces(app_handle: &AppHandle) {
             }
         }
     }
+    state.states.retain(|id, _| workspaces.iter().any(|w| &w.id() == id));
     state.workspaces = workspaces;
+    if let Err(err) = state.persist_snapshot(app_handle) {
+        warn!("Failed to persist workspaces snapshot: {}", err);
+    }
 }
 
 async fn handle_pro_instances(app_handle: &AppHandle) {
@@ -641,3 +1074,76 @@ fn diff_mut<'a, T: Identifiable>(old: &'a [T], new: &'a mut [T]) -> (Vec<&'a T>,
 
     return (removed, added);
 }
+
+#[cfg(test)]
+mod tests {
+    mod workspace_order {
+        use super::super::*;
+
+        fn workspace(id: &str) -> Workspace {
+            Workspace {
+                id: id.to_string(),
+                context: None,
+                provider: None,
+                machine: None,
+                pinned: false,
+                menu_item: None,
+            }
+        }
+
+        fn state(ids: &[&str]) -> WorkspacesState {
+            WorkspacesState {
+                workspaces: ids.iter().map(|id| workspace(id)).collect(),
+                polled: true,
+                ..Default::default()
+            }
+        }
+
+        fn sorted_ids(state: &WorkspacesState, ids: &[&str]) -> Vec<String> {
+            let mut workspaces: Vec<Workspace> = ids.iter().map(|id| workspace(id)).collect();
+            state.sort(&mut workspaces);
+            workspaces.into_iter().map(|w| w.id).collect()
+        }
+
+        #[test]
+        fn should_sort_pinned_first_then_by_order() {
+            let mut state = state(&["a", "b", "c", "d"]);
+            state.set_order(vec!["c".to_string(), "a".to_string()]);
+            state.pinned.insert("d".to_string());
+
+            assert_eq!(
+                sorted_ids(&state, &["a", "b", "c", "d", "new"]),
+                vec!["d", "c", "a", "b", "new"]
+            );
+        }
+
+        #[test]
+        fn should_keep_previous_order_of_unlisted_workspaces() {
+            let mut state = state(&["a", "b", "c"]);
+            state.set_order(vec!["c".to_string(), "b".to_string(), "a".to_string()]);
+            state.set_order(vec!["a".to_string()]);
+
+            assert_eq!(state.order, vec!["a", "c", "b"]);
+        }
+
+        #[test]
+        fn should_prune_deleted_workspaces() {
+            let mut state = state(&["a", "b"]);
+            state.order = vec!["gone".to_string(), "b".to_string()];
+            state.pinned.insert("gone".to_string());
+            state.set_order(vec!["a".to_string(), "deleted".to_string()]);
+
+            assert_eq!(state.order, vec!["a", "b"]);
+            assert!(state.pinned.is_empty());
+        }
+
+        #[test]
+        fn should_not_prune_before_first_poll() {
+            let mut state = state(&["a"]);
+            state.polled = false;
+            state.set_order(vec!["b".to_string(), "a".to_string()]);
+
+            assert_eq!(state.order, vec!["b", "a"]);
+        }
+    }
+}
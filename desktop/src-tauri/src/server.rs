@@ -19,6 +19,8 @@ use std::net::SocketAddr;
 use tauri::Manager;
 use tower_http::cors::{Any, CorsLayer};
 
+const SERVER_PORT: u16 = 25842;
+
 #[derive(Clone)]
 struct ServerState {
     app_handle: AppHandle,
@@ -51,7 +53,14 @@ pub async fn setup(app_handle: &AppHandle) -> anyhow::Result<()> {
         .with_state(state)
         .layer(cors);
 
-    let listener = tokio::net::TcpListener::bind("127.0.0.1:25842").await?;
+    let listener = match tokio::net::TcpListener::bind(("127.0.0.1", SERVER_PORT)).await {
+        Ok(listener) => listener,
+        Err(err) if err.kind() == std::io::ErrorKind::AddrInUse => anyhow::bail!(
+            "port {} is already in use, is another instance or application using it?",
+            SERVER_PORT
+        ),
+        Err(err) => return Err(err.into()),
+    };
     info!("Listening on {}", listener.local_addr()?);
     return axum::serve(
         listener,
@@ -239,7 +248,7 @@ async fn slack_auth_handler() -> impl IntoResponse {
             warn!("SLACK_CLIENT_ID environment variable not set");
             "your_slack_client_id".to_string()
         });
-    let redirect_uri = "http://localhost:25842/auth/slack/callback";
+    let redirect_uri = format!("http://localhost:{}/auth/slack/callback", SERVER_PORT);
     let scope = "identity.basic,identity.email,identity.avatar";
     
     let auth_url = format!(
@@ -283,7 +292,7 @@ async fn slack_auth_callback_handler(
             warn!("SLACK_CLIENT_SECRET environment variable not set");
             "your_slack_client_secret".to_string()
         });
-    let redirect_uri = "http://localhost:25842/auth/slack/callback";
+    let redirect_uri = format!("http://localhost:{}/auth/slack/callback", SERVER_PORT);
     
     let token_request_url = format!(
         "https://slack.com/api/oauth.v2.access?code={}&client_id={}&client_secret={}&redirect_uri={}",
@@ -490,10 +499,9 @@ struct SpacetimeStatus {
 async fn spacetime_status_handler(
     AxumState(_server): AxumState<ServerState>,
 ) -> impl IntoResponse {
-    let spacetime_running = match spacetime_server::setup(&_server.app_handle).await {
-        Ok(_) => true,
-        Err(_) => false,
-    };
+    let spacetime_running = spacetime_server::SpacetimeServer::new(_server.app_handle.clone())
+        .is_running()
+        .await;
     
     let status = SpacetimeStatus {
         running: spacetime_running,
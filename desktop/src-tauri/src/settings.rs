@@ -1,12 +1,65 @@
 #![allow(dead_code)]
 
-use crate::AppHandle;
-use log::error;
-use serde::Serialize;
+use crate::{logging, window, AppHandle, AppState};
+use log::{error, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::time::Duration;
+use tauri::{Emitter, Manager};
 use tauri_plugin_store::StoreExt;
+use thiserror::Error;
 use ts_rs::TS;
 
-const SETTINGS_FILE_NAME: &str = ".settings.json";
+pub const SETTINGS_FILE_NAME: &str = ".settings.json";
+const SETTINGS_CHANGED_EVENT: &str = "settings-changed";
+const ZOOM_FACTOR_KEY: &str = "zoomFactor";
+const CLOSE_BEHAVIOR_KEY: &str = "closeBehavior";
+const LOG_LEVEL_KEY: &str = "logLevel";
+const COMPLETION_NOTIFICATIONS_KEY: &str = "completionNotifications";
+const AUTO_START_WORKSPACES_KEY: &str = "autoStartWorkspaces";
+const SHOW_DOCK_ICON_KEY: &str = "showDockIcon";
+const CLI_LOG_DIR_KEY: &str = "cliLogDir";
+pub const MIN_ZOOM_FACTOR: f64 = 0.5;
+pub const MAX_ZOOM_FACTOR: f64 = 3.0;
+// Writes within this window are batched into a single `settings-changed` event
+const SETTINGS_CHANGED_DEBOUNCE: Duration = Duration::from_millis(250);
+
+#[derive(Error, Debug)]
+pub enum SettingsError {
+    #[error("unable to open store {0}")]
+    Store(String),
+    #[error("unable to save store {0}")]
+    Save(String),
+    #[error("unknown setting {0}")]
+    UnknownKey(String),
+    #[error("invalid value for setting {key}, expected {expected}")]
+    InvalidValue { key: String, expected: String },
+    #[error("unable to apply setting {key}: {message}")]
+    Apply { key: String, message: String },
+}
+impl serde::Serialize for SettingsError {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(self.to_string().as_ref())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, TS)]
+#[ts(export)]
+pub struct SettingsChangedMsg {
+    keys: Vec<String>,
+}
+
+// Keys written since the last `settings-changed` event. `generation` is bumped on every write
+// so only the most recent debounce task flushes.
+#[derive(Debug, Default)]
+pub struct PendingSettingsChanges {
+    keys: HashSet<String>,
+    generation: u64,
+}
 
 #[derive(Debug, Serialize, TS)]
 #[ts(rename_all = "camelCase")]
@@ -49,6 +102,12 @@ pub struct Settings {
     experimental_devpod_pro: bool,
     #[serde(rename = "experimental_colorMode")]
     experimental_color_mode: ColorMode,
+    close_behavior: CloseBehavior,
+    log_level: LogLevel,
+    completion_notifications: bool,
+    auto_start_workspaces: Vec<String>,
+    show_dock_icon: bool,
+    cli_log_dir: String,
 }
 
 #[derive(Debug, Serialize, TS)]
@@ -67,6 +126,133 @@ enum ColorMode {
     Light,
 }
 
+// What closing the last window does: keep running in the system tray or quit the app
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "lowercase")]
+#[ts(rename_all = "lowercase")]
+#[ts(export)]
+pub enum CloseBehavior {
+    #[default]
+    Tray,
+    Quit,
+}
+
+// Verbosity of the app log, applied at runtime without a relaunch
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "lowercase")]
+#[ts(rename_all = "lowercase")]
+#[ts(export)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+impl From<LogLevel> for log::LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Error => log::LevelFilter::Error,
+            LogLevel::Warn => log::LevelFilter::Warn,
+            LogLevel::Info => log::LevelFilter::Info,
+            LogLevel::Debug => log::LevelFilter::Debug,
+            LogLevel::Trace => log::LevelFilter::Trace,
+        }
+    }
+}
+impl From<log::LevelFilter> for LogLevel {
+    // `Off` is never set by the app, it's treated as the least verbose level
+    fn from(level: log::LevelFilter) -> Self {
+        match level {
+            log::LevelFilter::Off | log::LevelFilter::Error => LogLevel::Error,
+            log::LevelFilter::Warn => LogLevel::Warn,
+            log::LevelFilter::Info => LogLevel::Info,
+            log::LevelFilter::Debug => LogLevel::Debug,
+            log::LevelFilter::Trace => LogLevel::Trace,
+        }
+    }
+}
+
+// Shape of a known setting's value, checked before anything from the frontend is stored
+enum SettingType {
+    Bool,
+    Text,
+    TextList,
+    Number { min: f64, max: f64 },
+    OneOf(&'static [&'static str]),
+}
+
+impl SettingType {
+    fn accepts(&self, value: &Value) -> bool {
+        match self {
+            SettingType::Bool => value.is_boolean(),
+            SettingType::Text => value.is_string(),
+            SettingType::TextList => value
+                .as_array()
+                .map(|items| items.iter().all(Value::is_string))
+                .unwrap_or(false),
+            SettingType::Number { min, max } => value
+                .as_f64()
+                .map(|v| (*min..=*max).contains(&v))
+                .unwrap_or(false),
+            SettingType::OneOf(variants) => value
+                .as_str()
+                .map(|v| variants.contains(&v))
+                .unwrap_or(false),
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            SettingType::Bool => "a boolean".to_string(),
+            SettingType::Text => "a string".to_string(),
+            SettingType::TextList => "a list of strings".to_string(),
+            SettingType::Number { min, max } => format!("a number from {} to {}", min, max),
+            SettingType::OneOf(variants) => format!("one of {}", variants.join(", ")),
+        }
+    }
+}
+
+// Type and default of every setting the app knows about, keyed like the store. Mirrors
+// `Settings`, plus the keys only the backend reads.
+fn known_setting(key: &str) -> Option<(SettingType, Value)> {
+    let setting = match key {
+        "sidebarPosition" => (SettingType::OneOf(&["left", "right"]), json!("left")),
+        "zoom" => (SettingType::OneOf(&["sm", "md", "lg", "xl"]), json!("md")),
+        "experimental_colorMode" => (SettingType::OneOf(&["dark", "light"]), json!("light")),
+        CLOSE_BEHAVIOR_KEY => (
+            SettingType::OneOf(&["tray", "quit"]),
+            json!(CloseBehavior::default()),
+        ),
+        LOG_LEVEL_KEY => (
+            SettingType::OneOf(&["error", "warn", "info", "debug", "trace"]),
+            json!(LogLevel::from(logging::DEFAULT_LOG_LEVEL)),
+        ),
+        ZOOM_FACTOR_KEY => (
+            SettingType::Number {
+                min: MIN_ZOOM_FACTOR,
+                max: MAX_ZOOM_FACTOR,
+            },
+            json!(1.0),
+        ),
+        "autoUpdate" | COMPLETION_NOTIFICATIONS_KEY | SHOW_DOCK_ICON_KEY => {
+            (SettingType::Bool, json!(true))
+        }
+        AUTO_START_WORKSPACES_KEY => (SettingType::TextList, json!([])),
+        "debugFlag" | "partyParrot" | "fixedIDE" | "transparency" => {
+            (SettingType::Bool, json!(false))
+        }
+        "additionalCliFlags" | "additionalEnvVars" | "dotfilesUrl" | "sshKeyPath"
+        | "httpProxyUrl" | "httpsProxyUrl" | "noProxy" | CLI_LOG_DIR_KEY => {
+            (SettingType::Text, json!(""))
+        }
+        _ if key.starts_with("experimental_") => (SettingType::Bool, json!(false)),
+        _ => return None,
+    };
+
+    Some(setting)
+}
+
 #[derive(Debug, Serialize, TS)]
 #[ts(rename_all = "camelCase")]
 #[ts(export)]
@@ -92,4 +278,265 @@ impl Settings {
             .and_then(|v| v.as_bool())
             .unwrap_or(true)
     }
+
+    // Whether long running operations notify the user when they finish in the background
+    pub fn completion_notifications_enabled(app_handle: &AppHandle) -> bool {
+        let store = app_handle.store(SETTINGS_FILE_NAME);
+        if store.is_err() {
+            error!("unable to open store {}", SETTINGS_FILE_NAME);
+            return true;
+        }
+
+        store
+            .unwrap()
+            .get(COMPLETION_NOTIFICATIONS_KEY)
+            .and_then(|v| v.as_bool())
+            .unwrap_or(true)
+    }
+
+    // Workspaces to start in the background when the app launches
+    pub fn auto_start_workspaces(app_handle: &AppHandle) -> Vec<String> {
+        let store = app_handle.store(SETTINGS_FILE_NAME);
+        if store.is_err() {
+            error!("unable to open store {}", SETTINGS_FILE_NAME);
+            return vec![];
+        }
+
+        store
+            .unwrap()
+            .get(AUTO_START_WORKSPACES_KEY)
+            .and_then(|v| serde_json::from_value::<Vec<String>>(v).ok())
+            .unwrap_or_default()
+    }
+
+    // Whether the app shows a dock icon on macOS while it has windows, without it the app only
+    // lives in the menu bar
+    pub fn show_dock_icon(app_handle: &AppHandle) -> bool {
+        let store = app_handle.store(SETTINGS_FILE_NAME);
+        if store.is_err() {
+            error!("unable to open store {}", SETTINGS_FILE_NAME);
+            return true;
+        }
+
+        store
+            .unwrap()
+            .get(SHOW_DOCK_ICON_KEY)
+            .and_then(|v| v.as_bool())
+            .unwrap_or(true)
+    }
+
+    pub fn set_show_dock_icon(app_handle: &AppHandle, visible: bool) -> Result<(), SettingsError> {
+        Self::set(app_handle, SHOW_DOCK_ICON_KEY, serde_json::json!(visible))
+    }
+
+    // Directory the output of CLI commands is copied to, `None` if it's only kept in the app log
+    pub fn cli_log_dir(app_handle: &AppHandle) -> Option<String> {
+        let store = app_handle.store(SETTINGS_FILE_NAME);
+        if store.is_err() {
+            error!("unable to open store {}", SETTINGS_FILE_NAME);
+            return None;
+        }
+
+        store
+            .unwrap()
+            .get(CLI_LOG_DIR_KEY)
+            .and_then(|v| v.as_str().map(String::from))
+            .filter(|v| !v.is_empty())
+    }
+
+    pub fn set_cli_log_dir(app_handle: &AppHandle, dir: Option<&str>) -> Result<(), SettingsError> {
+        Self::set(
+            app_handle,
+            CLI_LOG_DIR_KEY,
+            serde_json::json!(dir.unwrap_or_default()),
+        )
+    }
+
+    pub fn close_behavior(app_handle: &AppHandle) -> CloseBehavior {
+        let store = app_handle.store(SETTINGS_FILE_NAME);
+        if store.is_err() {
+            error!("unable to open store {}", SETTINGS_FILE_NAME);
+            return CloseBehavior::default();
+        }
+
+        store
+            .unwrap()
+            .get(CLOSE_BEHAVIOR_KEY)
+            .and_then(|v| serde_json::from_value::<CloseBehavior>(v).ok())
+            .unwrap_or_default()
+    }
+
+    // `None` if the user never picked a level, the build's default applies then
+    pub fn log_level(app_handle: &AppHandle) -> Option<LogLevel> {
+        let store = app_handle.store(SETTINGS_FILE_NAME);
+        if store.is_err() {
+            error!("unable to open store {}", SETTINGS_FILE_NAME);
+            return None;
+        }
+
+        store
+            .unwrap()
+            .get(LOG_LEVEL_KEY)
+            .and_then(|v| serde_json::from_value::<LogLevel>(v).ok())
+    }
+
+    pub fn set_log_level(app_handle: &AppHandle, level: LogLevel) -> Result<(), SettingsError> {
+        Self::set(app_handle, LOG_LEVEL_KEY, serde_json::json!(level))
+    }
+
+    pub fn zoom_factor(app_handle: &AppHandle) -> f64 {
+        let store = app_handle.store(SETTINGS_FILE_NAME);
+        if store.is_err() {
+            error!("unable to open store {}", SETTINGS_FILE_NAME);
+            return 1.0;
+        }
+
+        store
+            .unwrap()
+            .get(ZOOM_FACTOR_KEY)
+            .and_then(|v| v.as_f64())
+            .filter(|v| v.is_finite())
+            .map(|v| v.clamp(MIN_ZOOM_FACTOR, MAX_ZOOM_FACTOR))
+            .unwrap_or(1.0)
+    }
+
+    pub fn set_zoom_factor(app_handle: &AppHandle, factor: f64) -> Result<(), SettingsError> {
+        Self::set(app_handle, ZOOM_FACTOR_KEY, serde_json::json!(factor))
+    }
+
+    pub fn set(
+        app_handle: &AppHandle,
+        key: &str,
+        value: serde_json::Value,
+    ) -> Result<(), SettingsError> {
+        let store = app_handle
+            .store(SETTINGS_FILE_NAME)
+            .map_err(|_| SettingsError::Store(SETTINGS_FILE_NAME.to_string()))?;
+        store.set(key, value);
+        store
+            .save()
+            .map_err(|err| SettingsError::Save(err.to_string()))?;
+
+        Self::notify_changed(app_handle, key);
+
+        Ok(())
+    }
+
+    // Writes the store to disk, settings are already saved on every `set` so this only
+    // matters for writes that bypassed it
+    pub fn flush(app_handle: &AppHandle) {
+        match app_handle.store(SETTINGS_FILE_NAME) {
+            Ok(store) => {
+                if let Err(err) = store.save() {
+                    error!("Failed to save {}: {}", SETTINGS_FILE_NAME, err);
+                }
+            }
+            Err(_) => error!("unable to open store {}", SETTINGS_FILE_NAME),
+        }
+    }
+
+    fn notify_changed(app_handle: &AppHandle, key: &str) {
+        let state = app_handle.state::<AppState>();
+        let generation = {
+            let mut pending = state.pending_settings_changes.lock().unwrap();
+            pending.keys.insert(key.to_string());
+            pending.generation += 1;
+            pending.generation
+        };
+
+        let app_handle = app_handle.clone();
+        tauri::async_runtime::spawn(async move {
+            tokio::time::sleep(SETTINGS_CHANGED_DEBOUNCE).await;
+
+            let state = app_handle.state::<AppState>();
+            let keys = {
+                let mut pending = state.pending_settings_changes.lock().unwrap();
+                // a later write is going to flush the pending keys
+                if pending.generation != generation {
+                    return;
+                }
+                pending.keys.drain().collect::<Vec<String>>()
+            };
+
+            if let Err(err) = app_handle.emit(SETTINGS_CHANGED_EVENT, SettingsChangedMsg { keys }) {
+                warn!("Failed to emit settings changed event: {}", err);
+            }
+        });
+    }
+}
+
+// Values that don't match the setting's type are rejected instead of stored
+#[tauri::command]
+pub fn set_setting(
+    app_handle: AppHandle,
+    key: String,
+    value: serde_json::Value,
+) -> Result<(), SettingsError> {
+    let (setting_type, _) =
+        known_setting(&key).ok_or_else(|| SettingsError::UnknownKey(key.clone()))?;
+    if !setting_type.accepts(&value) {
+        return Err(SettingsError::InvalidValue {
+            key,
+            expected: setting_type.describe(),
+        });
+    }
+
+    // Settings that take effect outside the store are applied like their dedicated commands do
+    match key.as_str() {
+        LOG_LEVEL_KEY => {
+            let level = serde_json::from_value::<LogLevel>(value).map_err(|_| {
+                SettingsError::InvalidValue {
+                    key: key.clone(),
+                    expected: setting_type.describe(),
+                }
+            })?;
+            logging::set_log_level(app_handle, level).map(|_| ())
+        }
+        ZOOM_FACTOR_KEY => window::set_zoom(app_handle, value.as_f64().unwrap_or(1.0))
+            .map(|_| ())
+            .map_err(|err| apply_error(key, err)),
+        SHOW_DOCK_ICON_KEY => {
+            window::set_dock_icon_visibility(app_handle, value.as_bool().unwrap_or(true))
+                .map_err(|err| apply_error(key, err))
+        }
+        CLI_LOG_DIR_KEY => {
+            let dir = value
+                .as_str()
+                .filter(|dir| !dir.is_empty())
+                .map(String::from);
+            logging::set_cli_log_dir(app_handle, dir).map_err(|err| apply_error(key, err))
+        }
+        _ => Settings::set(&app_handle, &key, value),
+    }
+}
+
+fn apply_error(key: String, err: impl std::fmt::Display) -> SettingsError {
+    SettingsError::Apply {
+        key,
+        message: err.to_string(),
+    }
+}
+
+// The stored value of a setting, or its default if it's unset or no longer has a valid value
+#[tauri::command]
+pub fn get_setting(app_handle: AppHandle, key: String) -> Result<Value, SettingsError> {
+    let (setting_type, default) =
+        known_setting(&key).ok_or_else(|| SettingsError::UnknownKey(key.clone()))?;
+    let store = app_handle
+        .store(SETTINGS_FILE_NAME)
+        .map_err(|_| SettingsError::Store(SETTINGS_FILE_NAME.to_string()))?;
+
+    match store.get(&key) {
+        Some(value) if setting_type.accepts(&value) => Ok(value),
+        Some(value) => {
+            warn!(
+                "Ignoring stored value {} for setting {}, expected {}",
+                value,
+                key,
+                setting_type.describe()
+            );
+            Ok(default)
+        }
+        None => Ok(default),
+    }
 }
@@ -0,0 +1,177 @@
+use crate::{custom_protocol::APP_IDENTIFIER, ui_messages, AppHandle, AppState};
+use interprocess::local_socket::{LocalSocketListener, LocalSocketStream, NameTypeSupport};
+use log::{error, info, warn};
+use serde::Serialize;
+use std::{
+    io::{BufRead, BufReader, ErrorKind, Write},
+    sync::atomic::{AtomicBool, AtomicU32, Ordering},
+    thread,
+    time::Duration,
+};
+use tauri::Manager;
+use ui_messages::UiMessage;
+
+const SHOW_DASHBOARD_MESSAGE: &str = "show-dashboard";
+const ACK_MESSAGE: &str = "ok";
+// How often and how long a losing instance waits for the winner of a simultaneous start to
+// answer
+const CONNECT_ATTEMPTS: u32 = 10;
+const CONNECT_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+static DUPLICATE_LAUNCHES: AtomicU32 = AtomicU32::new(0);
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+
+pub enum Instance {
+    // We are the only instance, the listener receives messages from later launches
+    Primary(Option<LocalSocketListener>),
+    // Another instance is running and was asked to show the dashboard
+    Secondary,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstanceStatus {
+    pid: u32,
+    socket_name: String,
+    // Launches that were routed to this instance since it started
+    duplicate_launches: u32,
+}
+
+// Namespaced sockets disappear with the process, so there are no stale sockets after a crash.
+// They aren't separated by user though, the user name keeps two logged in users apart.
+fn socket_name() -> String {
+    match NameTypeSupport::query() {
+        NameTypeSupport::OnlyPaths => std::env::temp_dir()
+            .join(format!("{}-instance.sock", APP_IDENTIFIER))
+            .to_string_lossy()
+            .to_string(),
+        NameTypeSupport::OnlyNamespaced | NameTypeSupport::Both => {
+            let user = std::env::var("USER")
+                .or_else(|_| std::env::var("USERNAME"))
+                .unwrap_or_default();
+            format!("@{}-instance-{}", APP_IDENTIFIER, user)
+        }
+    }
+}
+
+fn is_path_name(name: &str) -> bool {
+    !name.starts_with('@')
+}
+
+// Returns whether a running instance accepted the message, instances that are shutting down
+// close the connection without answering
+fn notify_running(name: &str) -> bool {
+    let send = || -> std::io::Result<String> {
+        let mut conn = BufReader::new(LocalSocketStream::connect(name)?);
+        conn.get_mut()
+            .write_all(format!("{}\n", SHOW_DASHBOARD_MESSAGE).as_bytes())?;
+        let mut reply = String::new();
+        conn.read_line(&mut reply)?;
+
+        Ok(reply)
+    };
+
+    matches!(send(), Ok(reply) if reply.trim() == ACK_MESSAGE)
+}
+
+// Binding the socket is atomic, so when two instances start at the same time only one of them
+// wins. The other one keeps trying to reach the winner until it answers. This also covers
+// relaunches, where the previous instance may still hold the socket for a moment.
+pub fn acquire() -> Instance {
+    let name = socket_name();
+    for attempt in 0..CONNECT_ATTEMPTS {
+        if attempt > 0 {
+            thread::sleep(CONNECT_RETRY_DELAY);
+        }
+
+        if notify_running(&name) {
+            info!("Another instance is already running, showing its dashboard instead");
+            return Instance::Secondary;
+        }
+
+        match LocalSocketListener::bind(name.as_str()) {
+            Ok(listener) => return Instance::Primary(Some(listener)),
+            Err(err) if err.kind() != ErrorKind::AddrInUse => {
+                error!("Unable to create instance socket {}: {}", name, err);
+                return Instance::Primary(None);
+            }
+            Err(_) => {}
+        }
+    }
+
+    // Nobody answers on a socket file left behind by a crashed instance
+    if is_path_name(&name) {
+        warn!("Removing stale instance socket {}", name);
+        let _ = std::fs::remove_file(&name);
+        if let Ok(listener) = LocalSocketListener::bind(name.as_str()) {
+            return Instance::Primary(Some(listener));
+        }
+    }
+
+    // Don't keep the user from starting the app just because the socket is unusable
+    error!("Unable to detect other instances, starting anyway");
+    Instance::Primary(None)
+}
+
+// Routes launches of further instances to the dashboard of this one
+pub fn listen(app_handle: AppHandle, listener: LocalSocketListener) {
+    thread::spawn(move || {
+        for conn in listener.incoming() {
+            let conn = match conn {
+                Ok(conn) => conn,
+                Err(err) => {
+                    warn!("Failed to accept instance connection: {}", err);
+                    continue;
+                }
+            };
+
+            let mut conn = BufReader::new(conn);
+            let mut message = String::new();
+            if let Err(err) = conn.read_line(&mut message) {
+                warn!("Failed to read instance message: {}", err);
+                continue;
+            }
+            if message.trim() != SHOW_DASHBOARD_MESSAGE {
+                warn!("Ignoring unknown instance message {:?}", message.trim());
+                continue;
+            }
+
+            if SHUTTING_DOWN.load(Ordering::Relaxed) {
+                continue;
+            }
+            if let Err(err) = conn
+                .get_mut()
+                .write_all(format!("{}\n", ACK_MESSAGE).as_bytes())
+            {
+                warn!("Failed to answer instance message: {}", err);
+            }
+
+            DUPLICATE_LAUNCHES.fetch_add(1, Ordering::Relaxed);
+            info!("Another instance was launched, showing dashboard");
+            let app_state = app_handle.state::<AppState>();
+            tauri::async_runtime::block_on(async {
+                ui_messages::dispatch(&app_state.ui_messages, UiMessage::ShowDashboard).await;
+            });
+        }
+    });
+}
+
+// Stops answering later launches so they start on their own, e.g. after a relaunch. Removes the
+// socket file on platforms without namespaced sockets, a leftover file would make the next start
+// wait for a dead instance.
+pub fn release() {
+    SHUTTING_DOWN.store(true, Ordering::Relaxed);
+    let name = socket_name();
+    if is_path_name(&name) {
+        let _ = std::fs::remove_file(name);
+    }
+}
+
+#[tauri::command]
+pub fn get_instance_status() -> InstanceStatus {
+    InstanceStatus {
+        pid: std::process::id(),
+        socket_name: socket_name(),
+        duplicate_launches: DUPLICATE_LAUNCHES.load(Ordering::Relaxed),
+    }
+}
@@ -1,81 +1,333 @@
-use crate::AppHandle;
+use crate::{AppHandle, AppState};
 use anyhow::Result;
-use log::{error, info};
-use std::path::PathBuf;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::path::{Component, Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 use tauri::Manager;
+use thiserror::Error;
 use tokio::sync::Mutex;
 
+// How long a graceful stop may take before the server is stopped forcefully
+const STOP_TIMEOUT: Duration = Duration::from_secs(10);
+// Version of the tables defined in desktop/server/src/tables.rs. Bump it with every incompatible
+// change there, e.g. a renamed, added or removed column or table. Backups of another version are
+// refused on import.
+const DATA_SCHEMA_VERSION: u32 = 3;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ServerStatus {
+    #[default]
+    Stopped,
+    Running,
+    Stopping,
+}
+
+#[derive(Error, Debug)]
+pub enum SpacetimeServerError {
+    #[error("unable to stop SpacetimeDB server: {0}")]
+    Stop(String),
+    #[error("unable to start SpacetimeDB server: {0}")]
+    Start(String),
+    #[error("backup has schema version {found}, expected {expected}")]
+    IncompatibleSchema { found: u32, expected: u32 },
+    #[error("backup is malformed: {0}")]
+    InvalidBackup(String),
+    #[error("resetting the database deletes all local data, confirm to continue")]
+    ResetNotConfirmed,
+    #[error("unable to access SpacetimeDB data")]
+    Io(#[from] std::io::Error),
+}
+
+// Portable dump of the server's data directory, written by `export_spacetime_data`
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SpacetimeBackup {
+    schema_version: u32,
+    exported_at: chrono::DateTime<chrono::Utc>,
+    files: Vec<BackupFile>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BackupFile {
+    // Relative to the server's data directory, always `/` separated
+    path: String,
+    // Hex encoded file contents
+    contents: String,
+}
+impl serde::Serialize for SpacetimeServerError {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(self.to_string().as_ref())
+    }
+}
+
 pub struct SpacetimeServer {
     #[allow(dead_code)]
     app_handle: AppHandle,
     server_path: PathBuf,
-    is_running: Arc<Mutex<bool>>,
+    // Shared through AppState so every handle sees the same server
+    status: Arc<Mutex<ServerStatus>>,
 }
 
 impl SpacetimeServer {
     pub fn new(app_handle: AppHandle) -> Self {
-        let server_path = app_handle
-            .path()
-            .app_local_data_dir()
-            .unwrap()
-            .join("spacetime_server");
-        
+        let server_path = data_dir(&app_handle).unwrap();
+        let status = app_handle.state::<AppState>().spacetime_status.clone();
+
         Self {
             app_handle,
             server_path,
-            is_running: Arc::new(Mutex::new(false)),
+            status,
         }
     }
 
     pub async fn start(&self) -> Result<()> {
-        let mut is_running = self.is_running.lock().await;
-        if *is_running {
+        let mut status = self.status.lock().await;
+        if *status == ServerStatus::Running {
             info!("SpacetimeDB server is already running");
             return Ok(());
         }
 
         info!("Starting SpacetimeDB server at {:?}", self.server_path);
-        
+
         if !self.server_path.exists() {
             std::fs::create_dir_all(&self.server_path)?;
         }
 
-        
-        *is_running = true;
+        *status = ServerStatus::Running;
         info!("SpacetimeDB server started successfully");
-        
+
         Ok(())
     }
 
-    #[allow(dead_code)]
+    // Lets the server flush its state and close client connections before it goes away. Gives
+    // up after STOP_TIMEOUT and stops the server forcefully instead.
     pub async fn stop(&self) -> Result<()> {
-        let mut is_running = self.is_running.lock().await;
-        if !*is_running {
-            info!("SpacetimeDB server is not running");
-            return Ok(());
+        {
+            let mut status = self.status.lock().await;
+            if *status != ServerStatus::Running {
+                info!("SpacetimeDB server is not running");
+                return Ok(());
+            }
+            *status = ServerStatus::Stopping;
         }
 
         info!("Stopping SpacetimeDB server");
-        
-        
-        *is_running = false;
+        if tokio::time::timeout(STOP_TIMEOUT, self.stop_gracefully())
+            .await
+            .is_err()
+        {
+            warn!(
+                "SpacetimeDB server did not stop within {}s, stopping it forcefully",
+                STOP_TIMEOUT.as_secs()
+            );
+        }
+
+        *self.status.lock().await = ServerStatus::Stopped;
         info!("SpacetimeDB server stopped successfully");
-        
+
         Ok(())
     }
 
-    #[allow(dead_code)]
+    // The server runs in-process without connections of its own yet, so there is nothing to
+    // flush or close
+    async fn stop_gracefully(&self) {}
+
+    pub async fn status(&self) -> ServerStatus {
+        *self.status.lock().await
+    }
+
     pub async fn is_running(&self) -> bool {
-        *self.is_running.lock().await
+        self.status().await == ServerStatus::Running
+    }
+
+    fn read_backup_files(&self) -> std::io::Result<Vec<BackupFile>> {
+        let mut files = vec![];
+        if self.server_path.exists() {
+            collect_backup_files(&self.server_path, &self.server_path, &mut files)?;
+        }
+
+        Ok(files)
+    }
+
+    // Replaces the data directory with the backed up files. They're written to a staging
+    // directory first and swapped in by renames, so a failed import leaves the old data intact.
+    fn write_backup_files(&self, files: &[BackupFile]) -> Result<(), SpacetimeServerError> {
+        let mut decoded = Vec::with_capacity(files.len());
+        for file in files {
+            let relative = Path::new(&file.path);
+            if !relative
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)))
+            {
+                return Err(SpacetimeServerError::InvalidBackup(format!(
+                    "file path {} leaves the data directory",
+                    file.path
+                )));
+            }
+            let contents = hex::decode(&file.contents)
+                .map_err(|err| SpacetimeServerError::InvalidBackup(err.to_string()))?;
+            decoded.push((relative.to_path_buf(), contents));
+        }
+
+        let staging_path = self.sibling_path("import");
+        if let Err(err) = write_files(&staging_path, decoded) {
+            let _ = std::fs::remove_dir_all(&staging_path);
+            return Err(err.into());
+        }
+
+        let previous_path = self.sibling_path("previous");
+        let had_data = self.server_path.exists();
+        if had_data {
+            if let Err(err) = std::fs::rename(&self.server_path, &previous_path) {
+                let _ = std::fs::remove_dir_all(&staging_path);
+                return Err(err.into());
+            }
+        }
+        if let Err(err) = std::fs::rename(&staging_path, &self.server_path) {
+            if had_data {
+                if let Err(restore_err) = std::fs::rename(&previous_path, &self.server_path) {
+                    error!(
+                        "Unable to restore SpacetimeDB data from {:?}: {}",
+                        previous_path, restore_err
+                    );
+                }
+            }
+            let _ = std::fs::remove_dir_all(&staging_path);
+            return Err(err.into());
+        }
+        if had_data {
+            if let Err(err) = std::fs::remove_dir_all(&previous_path) {
+                warn!(
+                    "Unable to remove previous SpacetimeDB data {:?}: {}",
+                    previous_path, err
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    // A path next to the data directory, e.g. `spacetime_server.backup-20240101120000`
+    fn sibling_path(&self, label: &str) -> PathBuf {
+        let mut name = self.server_path.file_name().unwrap_or_default().to_owned();
+        name.push(format!(
+            ".{}-{}",
+            label,
+            chrono::Utc::now().format("%Y%m%d%H%M%S")
+        ));
+        self.server_path.with_file_name(name)
+    }
+
+    // Moves the data directory next to itself, or deletes it without a backup. The server
+    // creates a fresh one on start.
+    fn clear_data(&self, backup: bool) -> Result<Option<PathBuf>, SpacetimeServerError> {
+        if !self.server_path.exists() {
+            return Ok(None);
+        }
+        if !backup {
+            std::fs::remove_dir_all(&self.server_path)?;
+            return Ok(None);
+        }
+
+        let backup_path = self.sibling_path("backup");
+        std::fs::rename(&self.server_path, &backup_path)?;
+
+        Ok(Some(backup_path))
+    }
+
+    // Runs `f` with the server stopped so its files are consistent, restarting it afterwards if
+    // it was running before
+    async fn while_stopped<T>(
+        &self,
+        f: impl FnOnce() -> Result<T, SpacetimeServerError>,
+    ) -> Result<T, SpacetimeServerError> {
+        let was_running = self.is_running().await;
+        if was_running {
+            self.stop()
+                .await
+                .map_err(|err| SpacetimeServerError::Stop(err.to_string()))?;
+        }
+
+        let result = f();
+
+        if was_running {
+            self.start()
+                .await
+                .map_err(|err| SpacetimeServerError::Start(err.to_string()))?;
+        }
+        result
     }
 }
 
+// Where the server keeps its data
+pub fn data_dir(app_handle: &AppHandle) -> tauri::Result<PathBuf> {
+    Ok(app_handle
+        .path()
+        .app_local_data_dir()?
+        .join("spacetime_server"))
+}
+
+fn collect_backup_files(
+    root: &Path,
+    dir: &Path,
+    files: &mut Vec<BackupFile>,
+) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        // Links could point outside the data directory, the server doesn't create any
+        let file_type = std::fs::symlink_metadata(&path)?.file_type();
+        if file_type.is_symlink() {
+            warn!("Skipping symlink {:?} in the SpacetimeDB data", path);
+            continue;
+        }
+        if file_type.is_dir() {
+            collect_backup_files(root, &path, files)?;
+            continue;
+        }
+
+        let relative = path.strip_prefix(root).unwrap_or(&path);
+        files.push(BackupFile {
+            path: relative
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/"),
+            contents: hex::encode(std::fs::read(&path)?),
+        });
+    }
+
+    Ok(())
+}
+
+// Writes `files`, relative to `root`, into a new directory at `root`
+fn write_files(root: &Path, files: Vec<(PathBuf, Vec<u8>)>) -> std::io::Result<()> {
+    if root.exists() {
+        std::fs::remove_dir_all(root)?;
+    }
+    std::fs::create_dir_all(root)?;
+    for (relative, contents) in files {
+        let path = root.join(relative);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, contents)?;
+    }
+
+    Ok(())
+}
+
 pub async fn setup(app_handle: &AppHandle) -> Result<()> {
     info!("Setting up SpacetimeDB server");
-    
+
     let server = SpacetimeServer::new(app_handle.clone());
-    
+
     match server.start().await {
         Ok(_) => {
             info!("SpacetimeDB server setup completed successfully");
@@ -88,12 +340,11 @@ pub async fn setup(app_handle: &AppHandle) -> Result<()> {
     }
 }
 
-#[allow(dead_code)]
 pub async fn shutdown(app_handle: &AppHandle) -> Result<()> {
     info!("Shutting down SpacetimeDB server");
-    
+
     let server = SpacetimeServer::new(app_handle.clone());
-    
+
     match server.stop().await {
         Ok(_) => {
             info!("SpacetimeDB server shutdown completed successfully");
@@ -105,3 +356,120 @@ pub async fn shutdown(app_handle: &AppHandle) -> Result<()> {
         }
     }
 }
+
+// Used by "restart sync engine" and before quitting, returns the status after stopping
+#[tauri::command]
+pub async fn stop_spacetime_server(
+    app_handle: AppHandle,
+) -> Result<ServerStatus, SpacetimeServerError> {
+    shutdown(&app_handle)
+        .await
+        .map_err(|err| SpacetimeServerError::Stop(err.to_string()))?;
+
+    Ok(SpacetimeServer::new(app_handle).status().await)
+}
+
+#[tauri::command]
+pub async fn get_spacetime_server_status(app_handle: AppHandle) -> ServerStatus {
+    SpacetimeServer::new(app_handle).status().await
+}
+
+// Writes a backup of the local sync database to `path`. The server is paused while its files
+// are read.
+#[tauri::command]
+pub async fn export_spacetime_data(
+    app_handle: AppHandle,
+    path: String,
+) -> Result<(), SpacetimeServerError> {
+    let server = SpacetimeServer::new(app_handle);
+    let files = server
+        .while_stopped(|| Ok(server.read_backup_files()?))
+        .await?;
+
+    let backup = SpacetimeBackup {
+        schema_version: DATA_SCHEMA_VERSION,
+        exported_at: chrono::Utc::now(),
+        files,
+    };
+    let contents = serde_json::to_vec(&backup)
+        .map_err(|err| SpacetimeServerError::InvalidBackup(err.to_string()))?;
+    std::fs::write(&path, contents)?;
+    info!(
+        "Exported {} SpacetimeDB files to {}",
+        backup.files.len(),
+        path
+    );
+
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DatabaseReset {
+    // Where the old data directory was moved to, none if it was deleted or didn't exist
+    backup_path: Option<PathBuf>,
+    status: ServerStatus,
+}
+
+// Recovery for a corrupted local database: stops the server, moves its data directory aside
+// (or deletes it if `backup` is false) and starts it with an empty database. Does
+// nothing unless `confirm` is set.
+#[tauri::command]
+pub async fn reset_spacetime_database(
+    app_handle: AppHandle,
+    confirm: bool,
+    backup: bool,
+) -> Result<DatabaseReset, SpacetimeServerError> {
+    if !confirm {
+        return Err(SpacetimeServerError::ResetNotConfirmed);
+    }
+
+    let server = SpacetimeServer::new(app_handle);
+    let backup_path = server.while_stopped(|| server.clear_data(backup)).await?;
+    match &backup_path {
+        Some(path) => info!("Reset SpacetimeDB database, moved old data to {:?}", path),
+        None => info!("Reset SpacetimeDB database, deleted old data"),
+    }
+    // A corrupted database may have kept the server from starting in the first place
+    if !server.is_running().await {
+        server
+            .start()
+            .await
+            .map_err(|err| SpacetimeServerError::Start(err.to_string()))?;
+    }
+
+    Ok(DatabaseReset {
+        backup_path,
+        status: server.status().await,
+    })
+}
+
+// Restores a backup written by `export_spacetime_data`, replacing all local data. Backups of
+// another schema version are refused before anything is touched.
+#[tauri::command]
+pub async fn import_spacetime_data(
+    app_handle: AppHandle,
+    path: String,
+) -> Result<(), SpacetimeServerError> {
+    let backup: SpacetimeBackup = serde_json::from_slice(&std::fs::read(&path)?)
+        .map_err(|err| SpacetimeServerError::InvalidBackup(err.to_string()))?;
+    if backup.schema_version != DATA_SCHEMA_VERSION {
+        return Err(SpacetimeServerError::IncompatibleSchema {
+            found: backup.schema_version,
+            expected: DATA_SCHEMA_VERSION,
+        });
+    }
+
+    let server = SpacetimeServer::new(app_handle);
+    server
+        .while_stopped(|| server.write_backup_files(&backup.files))
+        .await?;
+    info!(
+        "Imported {} SpacetimeDB files from {}, exported at {}",
+        backup.files.len(),
+        path,
+        backup.exported_at
+    );
+
+    Ok(())
+}
@@ -1,14 +1,16 @@
 use crate::{
     resource_watcher::{ProState, WorkspacesState},
-    ui_messages::{OpenProInstanceMsg, OpenWorkspaceMsg},
+    ui_messages::{self, OpenProInstanceMsg, OpenWorkspaceMsg},
     util, AppHandle, AppState, UiMessage,
 };
 use log::{error, warn};
 use tauri::{
+    image::Image,
     menu::{Menu, MenuBuilder, MenuEvent, MenuItem, Submenu},
     tray::{MouseButton, TrayIcon, TrayIconEvent},
     Manager,
 };
+use thiserror::Error;
 use util::QUIT_EXIT_CODE;
 
 #[cfg(not(target_os = "macos"))]
@@ -21,6 +23,69 @@ pub static SYSTEM_TRAY_ICON_BYTES: &'static [u8] = include_bytes!("../icons/icon
 #[cfg(target_os = "macos")]
 pub static SYSTEM_TRAY_ICON_BYTES: &'static [u8] = include_bytes!("../icons/icon_system_tray.png");
 
+// Edge length in pixels of the embedded tray icons and of the daemon status icons
+pub const SYSTEM_TRAY_ICON_SIZE: u32 = 88;
+pub const STATUS_ICON_SIZE: u32 = 48;
+
+#[derive(Error, Debug)]
+pub enum IconError {
+    #[error("unable to decode png: {0}")]
+    Decode(#[from] png::DecodingError),
+    #[error("expected 8 bit RGBA, found {0:?} with {1:?}")]
+    UnsupportedFormat(png::ColorType, png::BitDepth),
+    #[error("expected {expected}x{expected} pixels, found {width}x{height}")]
+    Dimensions {
+        expected: u32,
+        width: u32,
+        height: u32,
+    },
+}
+
+// Decodes an embedded PNG icon into the RGBA pixels `Image` expects. If the asset doesn't
+// decode to a `size`x`size` image, the failure is logged and a plain default icon is used so a
+// broken asset can't garble the tray or take it down.
+pub fn load_icon(name: &str, bytes: &[u8], size: u32) -> Image<'static> {
+    match decode_icon(bytes, size) {
+        Ok(icon) => icon,
+        Err(err) => {
+            error!(
+                "Failed to load icon {}, using the default icon: {}",
+                name, err
+            );
+            fallback_icon(size)
+        }
+    }
+}
+
+fn decode_icon(bytes: &[u8], size: u32) -> Result<Image<'static>, IconError> {
+    let mut reader = png::Decoder::new(bytes).read_info()?;
+    let mut rgba = vec![0; reader.output_buffer_size()];
+    let frame = reader.next_frame(&mut rgba)?;
+    if frame.color_type != png::ColorType::Rgba || frame.bit_depth != png::BitDepth::Eight {
+        return Err(IconError::UnsupportedFormat(
+            frame.color_type,
+            frame.bit_depth,
+        ));
+    }
+    if frame.width != size || frame.height != size {
+        return Err(IconError::Dimensions {
+            expected: size,
+            width: frame.width,
+            height: frame.height,
+        });
+    }
+    rgba.truncate(frame.buffer_size());
+
+    Ok(Image::new_owned(rgba, size, size))
+}
+
+// Solid grey square, works as a template icon on macOS as well
+fn fallback_icon(size: u32) -> Image<'static> {
+    let rgba = [128, 128, 128, 255].repeat((size * size) as usize);
+
+    Image::new_owned(rgba, size, size)
+}
+
 pub trait ToSystemTraySubmenu {
     fn to_submenu(&self, app_handle: &AppHandle) -> anyhow::Result<Submenu<tauri::Wry>>;
 }
@@ -77,18 +142,14 @@ impl SystemTray {
                 let app_state = app.state::<AppState>();
 
                 tauri::async_runtime::block_on(async move {
-                    if let Err(err) = app_state.ui_messages.send(UiMessage::ShowDashboard).await {
-                        error!("Failed to broadcast show dashboard message: {}", err);
-                    };
+                    ui_messages::dispatch(&app_state.ui_messages, UiMessage::ShowDashboard).await;
                 });
             }
             id => {
                 let app_state = app.state::<AppState>();
 
                 tauri::async_runtime::block_on(async move {
-                    if let Err(err) = app_state.ui_messages.send(UiMessage::ShowDashboard).await {
-                        error!("Failed to broadcast show dashboard message: {}", err);
-                    };
+                    ui_messages::dispatch(&app_state.ui_messages, UiMessage::ShowDashboard).await;
                     if id.starts_with(WorkspacesState::IDENTIFIER_PREFIX) {
                         let tx = &app_state.ui_messages;
 
@@ -137,10 +198,8 @@ impl SystemTray {
                     let app_state = icon.app_handle().state::<AppState>();
 
                     tauri::async_runtime::block_on(async move {
-                        if let Err(err) = app_state.ui_messages.send(UiMessage::ShowDashboard).await
-                        {
-                            error!("Failed to broadcast show dashboard message: {}", err);
-                        };
+                        ui_messages::dispatch(&app_state.ui_messages, UiMessage::ShowDashboard)
+                            .await;
                     });
                 }
             }
@@ -148,3 +207,79 @@ impl SystemTray {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    mod icons {
+        use super::super::*;
+
+        // Every embedded icon, including the other platform's tray variants
+        const TRAY_ICONS: &[(&str, &[u8])] = &[
+            (
+                "icon_system_tray.png",
+                include_bytes!("../icons/icon_system_tray.png"),
+            ),
+            (
+                "icon_system_tray_color.png",
+                include_bytes!("../icons/icon_system_tray_color.png"),
+            ),
+            (
+                "icon_warning_system_tray.png",
+                include_bytes!("../icons/icon_warning_system_tray.png"),
+            ),
+            (
+                "icon_warning_system_tray_color.png",
+                include_bytes!("../icons/icon_warning_system_tray_color.png"),
+            ),
+        ];
+        const STATUS_ICONS: &[(&str, &[u8])] = &[
+            ("running.png", include_bytes!("../icons/running.png")),
+            ("stopped.png", include_bytes!("../icons/stopped.png")),
+            ("pending.png", include_bytes!("../icons/pending.png")),
+        ];
+
+        fn assert_decodes(icons: &[(&str, &[u8])], size: u32) {
+            for (name, bytes) in icons {
+                let icon = decode_icon(bytes, size)
+                    .unwrap_or_else(|err| panic!("{} doesn't decode: {}", name, err));
+
+                assert_eq!((icon.width(), icon.height()), (size, size), "{}", name);
+                assert_eq!(icon.rgba().len(), (size * size * 4) as usize, "{}", name);
+            }
+        }
+
+        #[test]
+        fn should_decode_tray_icons() {
+            assert_decodes(TRAY_ICONS, SYSTEM_TRAY_ICON_SIZE);
+        }
+
+        #[test]
+        fn should_decode_status_icons() {
+            assert_decodes(STATUS_ICONS, STATUS_ICON_SIZE);
+        }
+
+        #[test]
+        fn should_reject_unexpected_dimensions() {
+            let err = decode_icon(SYSTEM_TRAY_ICON_BYTES, STATUS_ICON_SIZE).unwrap_err();
+
+            assert!(matches!(
+                err,
+                IconError::Dimensions {
+                    expected: STATUS_ICON_SIZE,
+                    width: SYSTEM_TRAY_ICON_SIZE,
+                    height: SYSTEM_TRAY_ICON_SIZE,
+                }
+            ));
+        }
+
+        #[test]
+        fn should_fall_back_for_broken_icon() {
+            let icon = load_icon("broken", b"not a png", STATUS_ICON_SIZE);
+
+            assert_eq!(
+                (icon.width(), icon.height()),
+                (STATUS_ICON_SIZE, STATUS_ICON_SIZE)
+            );
+        }
+    }
+}
@@ -3,9 +3,12 @@ use crate::{custom_protocol::ParseError, window::WindowHelper, AppHandle};
 use log::{error, info, warn};
 use serde::{de, Deserialize, Serialize};
 use std::collections::{HashMap, VecDeque};
-use tauri::{Emitter, Manager, State};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use tauri::{async_runtime::JoinHandle, Emitter, Manager, State};
 use tauri_plugin_notification::NotificationExt;
-use tokio::sync::mpsc::Receiver;
+use tokio::sync::mpsc::{error::TrySendError, Receiver, Sender};
+use tokio::sync::Mutex;
 
 pub async fn send_ui_message(
     app_state: State<'_, AppState>,
@@ -17,6 +20,33 @@ pub async fn send_ui_message(
     };
 }
 
+// Applies the backpressure policy of the UI message channel: droppable messages are discarded
+// when the channel is full (drop-newest), all others wait for capacity.
+// Returns whether the message was handed to the listener.
+pub async fn dispatch(sender: &Sender<UiMessage>, msg: UiMessage) -> bool {
+    if !msg.is_droppable() {
+        return match sender.send(msg).await {
+            Ok(_) => true,
+            Err(err) => {
+                error!("Failed to send UI message {:?}: {}", err.0, err);
+                false
+            }
+        };
+    }
+
+    match sender.try_send(msg) {
+        Ok(_) => true,
+        Err(TrySendError::Full(msg)) => {
+            warn!("UI message channel is full, dropping {:?}", msg);
+            false
+        }
+        Err(TrySendError::Closed(msg)) => {
+            error!("Failed to send UI message {:?}: channel closed", msg);
+            false
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct UiMessageHelper {
     app_handle: AppHandle,
@@ -37,50 +67,69 @@ impl UiMessageHelper {
         }
     }
 
-    pub async fn listen(&mut self, mut receiver: Receiver<UiMessage>) {
+    // A message whose handling panics is logged and skipped, the listener keeps going
+    pub async fn listen(&mut self, receiver: &mut Receiver<UiMessage>) {
         while let Some(ui_msg) = receiver.recv().await {
-            match ui_msg {
-                UiMessage::Ready => {
-                    self.is_ready = true;
-
-                    self.app_handle.get_webview_window("main").map(|w| w.show());
-                    while let Some(msg) = self.message_buffer.pop_front() {
-                        let emit_result = self.app_handle.emit("event", msg);
-                        if let Err(err) = emit_result {
-                            warn!("Error sending message: {}", err);
-                        }
+            let description = format!("{:?}", ui_msg);
+            if panic::catch_unwind(AssertUnwindSafe(|| self.handle(ui_msg))).is_err() {
+                error!("Handling UI message {} panicked, skipping it", description);
+            }
+        }
+        warn!("UI message channel closed, listener stopped");
+    }
+
+    fn handle(&mut self, ui_msg: UiMessage) {
+        match ui_msg {
+            UiMessage::Ready => {
+                self.is_ready = true;
+
+                self.app_handle.get_webview_window("main").map(|w| w.show());
+                while let Some(msg) = self.message_buffer.pop_front() {
+                    let emit_result = self.app_handle.emit("event", msg);
+                    if let Err(err) = emit_result {
+                        warn!("Error sending message: {}", err);
                     }
                 }
-                UiMessage::ExitRequested => {
-                    self.is_ready = false;
-                }
-                UiMessage::LoginRequired(msg) => {
-                    info!("Login required: {} {}", msg.host, msg.provider);
-
-                    let main_window = self.app_handle.get_webview_window("main");
-                    if !self.is_ready || main_window.is_none() {
-                        // send os notification if we aren't ready to display the main window
-                        let title = "Login required".to_string();
-                        let body = format!(
+            }
+            UiMessage::ExitRequested => {
+                self.is_ready = false;
+            }
+            UiMessage::LoginRequired(msg) => {
+                info!("Login required: {} {}", msg.host, msg.provider);
+
+                let main_window = self.app_handle.get_webview_window("main");
+                if !self.is_ready || main_window.is_none() {
+                    // send os notification if we aren't ready to display the main window
+                    let title = "Login required".to_string();
+                    let mut body = if msg.provider.is_empty() {
+                        format!(
                             "You have been logged out. Please log back in to {}",
-                            msg.host,
-                        );
-                        let _ = self
-                            .app_handle
-                            .notification()
-                            .builder()
-                            .title(title)
-                            .body(body)
-                            .show();
-                        continue;
+                            msg.host
+                        )
+                    } else {
+                        format!(
+                            "You have been logged out of {}. Please log back in to {}",
+                            msg.provider, msg.host
+                        )
+                    };
+                    if let Some(reason) = &msg.reason {
+                        body.push_str(&format!(" ({})", reason));
                     }
-
-                    // let main window handle
-                    let _ = self.app_handle.emit("event", UiMessage::LoginRequired(msg));
+                    let _ = self
+                        .app_handle
+                        .notification()
+                        .builder()
+                        .title(title)
+                        .body(body)
+                        .show();
+                    return;
                 }
-                // send all other messages to the UI
-                _ => self.handle_msg(ui_msg),
+
+                // let main window handle
+                let _ = self.app_handle.emit("event", UiMessage::LoginRequired(msg));
             }
+            // send all other messages to the UI
+            _ => self.handle_msg(ui_msg),
         }
     }
 
@@ -101,6 +150,63 @@ impl UiMessageHelper {
     }
 }
 
+// Runs `UiMessageHelper::listen` in a task it can replace. The receiver and the helper's state
+// live outside of the task, so a new listener picks up the same channel and buffered messages.
+pub struct UiMessageListener {
+    inner: Arc<Mutex<(UiMessageHelper, Receiver<UiMessage>)>>,
+    task: Option<JoinHandle<()>>,
+}
+
+impl UiMessageListener {
+    pub fn new(helper: UiMessageHelper, receiver: Receiver<UiMessage>) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new((helper, receiver))),
+            task: None,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.task
+            .as_ref()
+            .is_some_and(|task| !task.inner().is_finished())
+    }
+
+    // Stops the current listener, if any, and starts a new one
+    pub fn spawn(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+
+        let inner = self.inner.clone();
+        self.task = Some(tauri::async_runtime::spawn(async move {
+            let mut inner = inner.lock().await;
+            let (helper, receiver) = &mut *inner;
+            helper.listen(receiver).await;
+        }));
+    }
+}
+
+// Replaces the UI message listener, for when the app stopped reacting to the tray or deep links
+#[tauri::command]
+pub fn restart_ui_message_listener(app_handle: AppHandle) {
+    let state = app_handle.state::<AppState>();
+    let mut listener = state.ui_message_listener.lock().unwrap();
+    match listener.as_mut() {
+        Some(listener) => {
+            info!(
+                "Restarting UI message listener, previous listener was {}",
+                if listener.is_running() {
+                    "running"
+                } else {
+                    "dead"
+                }
+            );
+            listener.spawn();
+        }
+        None => warn!("UI message listener not set up yet, nothing to restart"),
+    }
+}
+
 #[derive(Debug, Serialize, Clone)]
 #[serde(tag = "type")]
 #[allow(dead_code)]
@@ -116,6 +222,21 @@ pub enum UiMessage {
     CommandFailed(ParseError),
     LoginRequired(LoginRequiredMsg),
 }
+impl UiMessage {
+    // Droppable messages are either re-sent by their producer or triggered again by the user,
+    // so losing one under load is harmless. `LoginRequired` is re-sent by the daemon watcher
+    // until it is delivered. Everything else carries a one-off user intent or lifecycle
+    // transition and must not be lost.
+    fn is_droppable(&self) -> bool {
+        matches!(
+            self,
+            UiMessage::ShowDashboard
+                | UiMessage::ShowToast(_)
+                | UiMessage::CommandFailed(_)
+                | UiMessage::LoginRequired(_)
+        )
+    }
+}
 
 #[derive(Debug, Serialize, Clone)]
 pub struct ShowToastMsg {
@@ -282,4 +403,6 @@ impl OpenWorkspaceMsg {
 pub struct LoginRequiredMsg {
     pub host: String,
     pub provider: String,
+    // Why the daemon lost its session, if it said so
+    pub reason: Option<String>,
 }
@@ -7,7 +7,7 @@ use regex::Regex;
 use reqwest::{Client, Method};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use tauri::Manager;
+use tauri::{Emitter, Manager};
 use tauri_plugin_notification::NotificationExt;
 use tauri_plugin_updater::UpdaterExt;
 use thiserror::Error;
@@ -18,6 +18,15 @@ use ts_rs::TS;
 const UPDATE_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 10);
 const RELEASES_URL: &str = "https://update-server.devpod.sh/releases";
 const FALLBACK_RELEASES_URL: &str = "https://api.github.com/repos/loft-sh/devpod/releases";
+const FETCH_RELEASES_ATTEMPTS: u32 = 4;
+const FETCH_RELEASES_BACKOFF: std::time::Duration = std::time::Duration::from_secs(2);
+// A stalled connection counts as a failed attempt instead of blocking the loader forever
+const FETCH_RELEASES_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+// How often to try again after the retries ran out while offline
+const OFFLINE_RETRY_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+const UPDATE_STATUS_EVENT: &str = "update-status";
+// Upper bound for a connectivity probe, a slow proxy shows up as a timeout
+const CONNECTIVITY_PROBE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
 
 #[derive(Error, Debug)]
 pub enum UpdateError {
@@ -28,6 +37,10 @@ pub enum UpdateError {
     CheckUpdate(#[from] tauri_plugin_updater::Error),
     #[error("failed to fetch releases {0}")]
     FetchRelease(#[from] anyhow::Error),
+    #[error("unable to reach the update server, are you offline? {0}")]
+    Offline(String),
+    #[error("the update has already been installed and takes effect on the next restart")]
+    AlreadyInstalled,
 }
 impl serde::Serialize for UpdateError {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -40,6 +53,76 @@ impl serde::Serialize for UpdateError {
 
 pub type Releases = Vec<Release>;
 
+// Why the last attempt to load releases failed, cleared once releases were loaded
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", content = "message", rename_all = "camelCase")]
+pub enum ReleasesFetchFailure {
+    // The request never got a response, e.g. no network or DNS
+    Offline(String),
+    // The update servers responded with an error or an unexpected payload
+    Server(String),
+}
+impl ReleasesFetchFailure {
+    fn from_error(err: &anyhow::Error) -> Self {
+        let offline = err.chain().any(|cause| {
+            cause
+                .downcast_ref::<reqwest::Error>()
+                .is_some_and(|err| err.is_connect() || err.is_timeout())
+        });
+        if offline {
+            ReleasesFetchFailure::Offline(format!("{:#}", err))
+        } else {
+            ReleasesFetchFailure::Server(format!("{:#}", err))
+        }
+    }
+}
+
+// Where the app update currently is, every change is emitted as an `update-status` event
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+// The download states are only reached by release builds, see `UpdateHelper::poll`
+#[cfg_attr(debug_assertions, allow(dead_code))]
+pub enum UpdateStatus {
+    #[default]
+    Idle,
+    Checking,
+    // Percent of the download, None while the size is unknown
+    Downloading {
+        progress: Option<u8>,
+    },
+    // Downloaded and applied, takes effect once the app restarts
+    ReadyToInstall,
+    // The downloaded update is being applied
+    Installing,
+    Failed {
+        reason: String,
+    },
+}
+
+// An update download in progress, dropped once the download finished
+#[derive(Debug)]
+pub struct UpdateDownload {
+    version: String,
+    cancel: tokio::sync::oneshot::Sender<()>,
+}
+
+// Records the status and tells the frontend about it. Download progress is only emitted when
+// the percentage changes.
+pub fn set_update_status(app_handle: &AppHandle, status: UpdateStatus) {
+    let state = app_handle.state::<AppState>();
+    {
+        let mut current = state.update_status.lock().unwrap();
+        if *current == status {
+            return;
+        }
+        *current = status.clone();
+    }
+
+    if let Err(err) = app_handle.emit(UPDATE_STATUS_EVENT, status) {
+        warn!("Failed to emit update status: {}", err);
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
 #[serde(rename_all = "snake_case")]
 #[ts(export)]
@@ -131,26 +214,154 @@ pub async fn get_pending_update(state: tauri::State<'_, AppState>) -> Result<Rel
     release.clone().ok_or(())
 }
 
+#[tauri::command]
+pub fn update_status(state: tauri::State<'_, AppState>) -> UpdateStatus {
+    state.update_status.lock().unwrap().clone()
+}
+
+// Drops the update that is waiting for the user or still downloading. The version isn't
+// offered again until the app restarts. Does nothing if no update is pending, an update that
+// has already been installed can't be backed out.
+#[tauri::command]
+pub fn cancel_pending_update(app_handle: AppHandle) -> Result<(), UpdateError> {
+    let state = app_handle.state::<AppState>();
+    if *state.update_installed.lock().unwrap() {
+        return Err(UpdateError::AlreadyInstalled);
+    }
+
+    let pending = state.pending_update.lock().unwrap().take();
+    let download = state.update_download.lock().unwrap().take();
+    let version = match (pending, download) {
+        (_, Some(download)) => {
+            // the download may have just finished, then there's nothing left to abort
+            let _ = download.cancel.send(());
+            Some(download.version)
+        }
+        (Some(release), None) => Some(release.tag_name.trim_start_matches('v').to_string()),
+        (None, None) => None,
+    };
+
+    if let Some(version) = version {
+        info!("Cancelled pending update to {}", version);
+        *state.skipped_update.lock().unwrap() = Some(version);
+        set_update_status(&app_handle, UpdateStatus::Idle);
+    }
+
+    Ok(())
+}
+
 #[tauri::command]
 pub async fn check_updates(app_handle: AppHandle) -> Result<bool, UpdateError> {
     let updater = app_handle
         .updater()
         .map_err(|e| UpdateError::CheckUpdate(e))?;
+    // an update in flight is further along than a check
+    let previous = app_handle.state::<AppState>().update_status.lock().unwrap().clone();
+    let idle = matches!(previous, UpdateStatus::Idle | UpdateStatus::Failed { .. });
+    if idle {
+        set_update_status(&app_handle, UpdateStatus::Checking);
+    }
     match updater.check().await {
         Ok(update) => {
             let update_available = update.is_some();
             info!("Update check completed, result: {}", update_available);
+            if idle {
+                set_update_status(&app_handle, UpdateStatus::Idle);
+            }
 
             return Ok(update_available);
         }
         Err(e) => {
             error!("Failed to get update: {}", e);
+            if idle {
+                set_update_status(
+                    &app_handle,
+                    UpdateStatus::Failed {
+                        reason: e.to_string(),
+                    },
+                );
+            }
+
+            // the releases loader knows whether we're offline, which is more useful to the user
+            let state = app_handle.state::<AppState>();
+            if let Some(ReleasesFetchFailure::Offline(reason)) =
+                state.releases_failure.lock().unwrap().clone()
+            {
+                return Err(UpdateError::Offline(reason));
+            }
 
             return Err(UpdateError::CheckUpdate(e));
         }
     }
 }
 
+// Result of probing one of the release endpoints, see `check_update_connectivity`
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateConnectivity {
+    url: String,
+    reachable: bool,
+    // Until the response headers arrived, or until the probe failed
+    latency_ms: u128,
+    failure: Option<ConnectivityFailure>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum ConnectivityFailure {
+    // The host name could not be resolved
+    Dns { message: String },
+    // A connection was made but the TLS handshake failed, e.g. an intercepting proxy
+    Tls { message: String },
+    // No connection could be established
+    Connect { message: String },
+    Timeout,
+    // The server answered with an error status
+    Http { status: u16 },
+}
+impl ConnectivityFailure {
+    // reqwest doesn't expose what went wrong below the connection, so the causes are matched
+    // by their messages
+    fn from_error(err: &reqwest::Error) -> Self {
+        if err.is_timeout() {
+            return ConnectivityFailure::Timeout;
+        }
+
+        let mut causes = vec![];
+        let mut source: Option<&dyn std::error::Error> = Some(err);
+        while let Some(cause) = source {
+            causes.push(cause.to_string());
+            source = cause.source();
+        }
+        let message = causes.join(": ");
+        let mentions = |needles: &[&str]| {
+            let message = message.to_lowercase();
+            needles.iter().any(|needle| message.contains(needle))
+        };
+
+        if mentions(&["dns error", "lookup address", "name or service not known"]) {
+            ConnectivityFailure::Dns { message }
+        } else if mentions(&["certificate", "tls", "ssl", "handshake"]) {
+            ConnectivityFailure::Tls { message }
+        } else {
+            ConnectivityFailure::Connect { message }
+        }
+    }
+}
+
+// Probes the release endpoints the update checks use, the fallback included, without
+// downloading the releases
+#[tauri::command]
+pub async fn check_update_connectivity(app_handle: AppHandle) -> Vec<UpdateConnectivity> {
+    let helper = UpdateHelper::new(&app_handle);
+    let mut results = vec![];
+    for url in [RELEASES_URL, FALLBACK_RELEASES_URL] {
+        results.push(helper.probe(url).await);
+    }
+
+    results
+}
+
 #[derive(Clone, Debug)]
 pub struct UpdateHelper<'a> {
     #[allow(dead_code)]
@@ -172,6 +383,8 @@ impl<'a> UpdateHelper<'a> {
 
         #[cfg(not(debug_assertions))]
         {
+            use crate::{settings::Settings, window::WindowHelper};
+
             loop {
             // check if we have updated the app recently
             // if so, show changelog in app
@@ -184,10 +397,24 @@ impl<'a> UpdateHelper<'a> {
                 continue;
             }
             info!("Attempting to check update");
-            if let Ok(update) = updater.unwrap().check().await {
+            set_update_status(self.app_handle, UpdateStatus::Checking);
+            let check = updater.unwrap().check().await;
+            if let Err(err) = &check {
+                warn!("Failed to check for updates: {}", err);
+                set_update_status(
+                    self.app_handle,
+                    UpdateStatus::Failed {
+                        reason: err.to_string(),
+                    },
+                );
+            }
+            if let Ok(update) = check {
                 match update {
                     Some(..) => info!("update available"),
-                    None => info!("no update available"),
+                    None => {
+                        info!("no update available");
+                        set_update_status(self.app_handle, UpdateStatus::Idle);
+                    }
                 };
 
                 if let Some(update) = update {
@@ -195,17 +422,38 @@ impl<'a> UpdateHelper<'a> {
                     let update_installed_state = *state.update_installed.lock().unwrap();
                     // prevent ourselves from installing the same update multiple times
                     if update_installed_state {
+                        set_update_status(self.app_handle, UpdateStatus::ReadyToInstall);
                         return;
                     }
+                    // the user backed out of this version through `cancel_pending_update`
+                    if state.skipped_update.lock().unwrap().as_deref() == Some(update.version.as_str()) {
+                        set_update_status(self.app_handle, UpdateStatus::Idle);
+                        tokio::time::sleep(UPDATE_POLL_INTERVAL).await;
+                        continue;
+                    }
 
                     let new_version = update.version.as_str();
                     let update_helper = UpdateHelper::new(&self.app_handle);
                     if let Err(e) = update_helper.update_app_releases(new_version).await {
-                        error!("Failed to update app releases: {}", e);
+                        crate::recent_errors::report_error(
+                            self.app_handle,
+                            module_path!(),
+                            format!("Failed to update app releases: {}", e),
+                        );
                     }
 
                     if Settings::auto_update_enabled(&self.app_handle) {
-                        let on_chunk = |_, _| {};
+                        let mut downloaded: u64 = 0;
+                        let on_chunk = |chunk_length: usize, content_length: Option<u64>| {
+                            downloaded += chunk_length as u64;
+                            let progress = content_length
+                                .filter(|total| *total > 0)
+                                .map(|total| (downloaded * 100 / total).min(100) as u8);
+                            set_update_status(
+                                self.app_handle,
+                                UpdateStatus::Downloading { progress },
+                            );
+                        };
                         let on_download_fininshed = || {
                             info!("Download for version {} finished", new_version);
                         };
@@ -214,23 +462,62 @@ impl<'a> UpdateHelper<'a> {
                             update.current_version, new_version,
                         );
                         info!("Starting to download");
-                        if let Err(err) = update
-                            .download_and_install(on_chunk, on_download_fininshed)
-                            .await
-                        {
-                            error!("Failed to download and install update: {}", err);
-                        }
+                        set_update_status(
+                            self.app_handle,
+                            UpdateStatus::Downloading { progress: None },
+                        );
+                        let (cancel, cancelled) = tokio::sync::oneshot::channel();
+                        *state.update_download.lock().unwrap() = Some(UpdateDownload {
+                            version: new_version.to_string(),
+                            cancel,
+                        });
+                        let downloaded = tokio::select! {
+                            res = update.download(on_chunk, on_download_fininshed) => res.map(Some),
+                            _ = cancelled => Ok(None),
+                        };
+                        state.update_download.lock().unwrap().take();
+                        let installed = match downloaded {
+                            Ok(Some(bytes)) => {
+                                set_update_status(self.app_handle, UpdateStatus::Installing);
+                                update.install(bytes).map(Some)
+                            }
+                            Ok(None) => Ok(None),
+                            Err(err) => Err(err),
+                        };
+                        match installed {
+                            Ok(None) => {
+                                info!("Download for version {} cancelled", new_version);
+                                set_update_status(self.app_handle, UpdateStatus::Idle);
+                            }
+                            Ok(Some(())) => {
+                                set_update_status(self.app_handle, UpdateStatus::ReadyToInstall);
 
-                        let window_helper = WindowHelper::new(self.app_handle.clone());
-                        let _ = window_helper.new_update_ready_window();
+                                let window_helper = WindowHelper::new(self.app_handle.clone());
+                                let _ = window_helper.new_update_ready_window();
 
-                        let state = self.app_handle.state::<AppState>();
-                        let mut pending_update_state = state.pending_update.lock().unwrap();
-                        *pending_update_state = None;
+                                let state = self.app_handle.state::<AppState>();
+                                let mut pending_update_state = state.pending_update.lock().unwrap();
+                                *pending_update_state = None;
 
-                        let mut update_installed_state = state.update_installed.lock().unwrap();
-                        *update_installed_state = true;
+                                let mut update_installed_state = state.update_installed.lock().unwrap();
+                                *update_installed_state = true;
+                            }
+                            Err(err) => {
+                                set_update_status(
+                                    self.app_handle,
+                                    UpdateStatus::Failed {
+                                        reason: err.to_string(),
+                                    },
+                                );
+                                crate::recent_errors::report_error(
+                                    self.app_handle,
+                                    module_path!(),
+                                    format!("Failed to download and install update: {}", err),
+                                );
+                            }
+                        }
                     } else {
+                        set_update_status(self.app_handle, UpdateStatus::Idle);
                         match self.update_app_releases(new_version).await {
                             Ok(release) => {
                                 if let Err(err) = self.notify_update_available(&release).await {
@@ -243,7 +530,11 @@ impl<'a> UpdateHelper<'a> {
                                 *pending_update_state = Some(release);
                             }
                             Err(e) => {
-                                error!("Failed to update app releases: {}", e);
+                                crate::recent_errors::report_error(
+                                    self.app_handle,
+                                    module_path!(),
+                                    format!("Failed to update app releases: {}", e),
+                                );
                             }
                         }
                     }
@@ -261,6 +552,7 @@ impl<'a> UpdateHelper<'a> {
             .await
             .map_err(UpdateError::FetchRelease)?;
         let state = self.app_handle.state::<AppState>();
+        *state.releases_failure.lock().unwrap() = None;
         let mut releases_state = state.releases.lock().unwrap();
         *releases_state = releases;
 
@@ -273,6 +565,46 @@ impl<'a> UpdateHelper<'a> {
             .clone())
     }
 
+    // Only waits for the response headers, the body is dropped unread
+    pub async fn probe(&self, url: &str) -> UpdateConnectivity {
+        let started_at = std::time::Instant::now();
+        let response = match Client::builder()
+            .user_agent("loft-sh/devpod")
+            .timeout(CONNECTIVITY_PROBE_TIMEOUT)
+            .build()
+        {
+            Ok(client) => {
+                client
+                    .request(Method::GET, url)
+                    .header("Accept", "application/vnd.github+json")
+                    .header("X-GitHub-Api-Version", "2022-11-28")
+                    .send()
+                    .await
+            }
+            Err(err) => Err(err),
+        };
+        let latency_ms = started_at.elapsed().as_millis();
+
+        let failure = match response {
+            Ok(response) if response.status().is_success() => None,
+            Ok(response) => Some(ConnectivityFailure::Http {
+                status: response.status().as_u16(),
+            }),
+            Err(err) => Some(ConnectivityFailure::from_error(&err)),
+        };
+        match &failure {
+            None => info!("Reached {} in {}ms", url, latency_ms),
+            Some(failure) => warn!("Unable to reach {}: {:?}", url, failure),
+        }
+
+        UpdateConnectivity {
+            url: url.to_string(),
+            reachable: failure.is_none(),
+            latency_ms,
+            failure,
+        }
+    }
+
     pub async fn fetch_releases_from_url(&self, url: &str) -> anyhow::Result<Vec<Release>> {
         let client = Client::builder().user_agent("loft-sh/devpod").build()?;
 
@@ -300,6 +632,73 @@ impl<'a> UpdateHelper<'a> {
         Ok(releases)
     }
 
+    // Loads the releases into the app state, retrying with backoff. While offline it keeps
+    // trying every OFFLINE_RETRY_INTERVAL until connectivity returns, server errors give up.
+    pub async fn load_releases(&self) {
+        let state = self.app_handle.state::<AppState>();
+        loop {
+            let failure = match self.fetch_releases_with_retry().await {
+                Ok(releases) => {
+                    *state.releases.lock().unwrap() = releases;
+                    *state.releases_failure.lock().unwrap() = None;
+                    return;
+                }
+                Err(failure) => failure,
+            };
+            *state.releases_failure.lock().unwrap() = Some(failure.clone());
+
+            match failure {
+                ReleasesFetchFailure::Offline(reason) => {
+                    info!(
+                        "Update server unreachable, retrying in {:?}: {}",
+                        OFFLINE_RETRY_INTERVAL, reason
+                    );
+                    tokio::time::sleep(OFFLINE_RETRY_INTERVAL).await;
+                }
+                ReleasesFetchFailure::Server(reason) => {
+                    warn!("Failed to load releases: {}", reason);
+                    return;
+                }
+            }
+        }
+    }
+
+    async fn fetch_releases_with_retry(&self) -> Result<Releases, ReleasesFetchFailure> {
+        let mut backoff = FETCH_RELEASES_BACKOFF;
+        let mut attempt = 1;
+        loop {
+            let failure =
+                match tokio::time::timeout(FETCH_RELEASES_TIMEOUT, self.fetch_releases()).await {
+                    Ok(Ok(releases)) => return Ok(releases),
+                    Ok(Err(err)) => {
+                        debug!(
+                            "Fetching releases failed (attempt {}/{}): {:#}",
+                            attempt, FETCH_RELEASES_ATTEMPTS, err
+                        );
+                        ReleasesFetchFailure::from_error(&err)
+                    }
+                    Err(_) => {
+                        warn!(
+                            "Fetching releases timed out after {:?} (attempt {}/{})",
+                            FETCH_RELEASES_TIMEOUT, attempt, FETCH_RELEASES_ATTEMPTS
+                        );
+                        ReleasesFetchFailure::Offline(format!(
+                            "no response within {:?}",
+                            FETCH_RELEASES_TIMEOUT
+                        ))
+                    }
+                };
+            if attempt >= FETCH_RELEASES_ATTEMPTS {
+                return Err(failure);
+            }
+
+            debug!("Retrying to fetch releases in {:?}", backoff);
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+            attempt += 1;
+        }
+    }
+
     pub async fn fetch_releases(&self) -> anyhow::Result<Releases> {
         debug!("Querying releases from update server: {}", RELEASES_URL);
         let releases = match self.fetch_releases_from_url(RELEASES_URL).await {
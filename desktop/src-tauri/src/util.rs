@@ -1,5 +1,10 @@
 use log::{debug, error};
-use std::time::{Duration, Instant};
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+    time::{Duration, Instant},
+};
 
 // Exit code for the window to signal that the application was quit by the user through the system tray
 // and event handlers may not use prevent_exit().
@@ -17,6 +22,66 @@ where
     start.elapsed()
 }
 
+/// `read_last_lines` returns the last `lines` lines of a file, reading backwards from the end
+/// in chunks so large logs don't have to be loaded completely.
+pub fn read_last_lines(path: &Path, lines: usize) -> std::io::Result<Vec<String>> {
+    const CHUNK_SIZE: u64 = 8 * 1024;
+
+    let mut file = File::open(path)?;
+    let mut pos = file.metadata()?.len();
+    if lines == 0 {
+        return Ok(vec![]);
+    }
+
+    // once there are more newlines than requested lines, the last `lines` lines are complete
+    let mut buf: Vec<u8> = vec![];
+    while pos > 0 && buf.iter().filter(|b| **b == b'\n').count() <= lines {
+        let read = CHUNK_SIZE.min(pos);
+        pos -= read;
+        file.seek(SeekFrom::Start(pos))?;
+
+        let mut chunk = vec![0; read as usize];
+        file.read_exact(&mut chunk)?;
+        chunk.extend_from_slice(&buf);
+        buf = chunk;
+    }
+
+    let text = String::from_utf8_lossy(&buf);
+    let all_lines: Vec<&str> = text.lines().collect();
+
+    Ok(all_lines[all_lines.len().saturating_sub(lines)..]
+        .iter()
+        .map(|line| line.to_string())
+        .collect())
+}
+
+/// `dir_size` sums up the size of all files below `path`. Symlinks are counted as links and not
+/// followed, so links out of the tree or cycles don't inflate the result. Entries that vanish or
+/// can't be read during the walk are skipped.
+pub fn dir_size(path: &Path) -> u64 {
+    let metadata = match std::fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(err) => {
+            debug!("Skipping {:?} in disk usage: {}", path, err);
+            return 0;
+        }
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+
+    match std::fs::read_dir(path) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| dir_size(&entry.path()))
+            .sum(),
+        Err(err) => {
+            debug!("Skipping {:?} in disk usage: {}", path, err);
+            0
+        }
+    }
+}
+
 /// Kills all child processes of a pid on windows, does nothing on all the other OSs.
 pub fn kill_child_processes(_parent_pid: u32) {
     #[cfg(windows)]
@@ -150,3 +215,193 @@ pub fn kill_process(pid: u32) {
         }
     }
 }
+
+// Kills `pid` together with every process below it and returns the descendants it found, which
+// are only known on unix. Children go first so they can't be reparented and escape.
+#[cfg(not(windows))]
+pub fn kill_process_tree(pid: u32) -> Vec<u32> {
+    use nix::sys::signal::{self, Signal};
+    use nix::unistd::Pid;
+
+    let descendants = descendants(pid);
+    for descendant in descendants.iter().rev().chain(std::iter::once(&pid)) {
+        if let Err(err) = signal::kill(Pid::from_raw(*descendant as i32), Signal::SIGKILL) {
+            log::warn!("Failed to kill process {}: {}", descendant, err);
+        }
+    }
+
+    descendants
+}
+
+#[cfg(windows)]
+pub fn kill_process_tree(pid: u32) -> Vec<u32> {
+    kill_child_processes(pid);
+    kill_process(pid);
+
+    vec![]
+}
+
+// All processes below `pid`, parents before their children. Reads the process table through
+// `ps`, which is there on every unix the app supports.
+#[cfg(not(windows))]
+fn descendants(pid: u32) -> Vec<u32> {
+    let output = match std::process::Command::new("ps")
+        .args(["-A", "-o", "pid=,ppid="])
+        .output()
+    {
+        Ok(output) => output,
+        Err(err) => {
+            log::warn!("Failed to list processes, only killing {}: {}", pid, err);
+            return vec![];
+        }
+    };
+
+    let parents: Vec<(u32, u32)> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace().map(|field| field.parse::<u32>());
+            match (fields.next(), fields.next()) {
+                (Some(Ok(pid)), Some(Ok(ppid))) => Some((pid, ppid)),
+                _ => None,
+            }
+        })
+        .collect();
+
+    descendants_in(pid, &parents)
+}
+
+// All processes below `pid` in a process table of `(pid, ppid)` pairs, parents before their
+// children
+#[cfg(not(windows))]
+fn descendants_in(pid: u32, parents: &[(u32, u32)]) -> Vec<u32> {
+    let mut descendants = vec![];
+    let mut next = 0;
+    let mut parent = pid;
+    loop {
+        descendants.extend(
+            parents
+                .iter()
+                .filter(|(_, ppid)| *ppid == parent)
+                .map(|(pid, _)| *pid),
+        );
+        match descendants.get(next) {
+            Some(pid) => parent = *pid,
+            None => break,
+        }
+        next += 1;
+    }
+
+    descendants
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(windows))]
+    mod descendants {
+        use super::super::*;
+
+        // 1
+        // ├── 10
+        // │   ├── 100
+        // │   └── 101
+        // │       └── 1010
+        // └── 11
+        // 2
+        // └── 20
+        const PROCESS_TABLE: &[(u32, u32)] = &[
+            (1, 0),
+            (2, 0),
+            (20, 2),
+            (101, 10),
+            (10, 1),
+            (1010, 101),
+            (11, 1),
+            (100, 10),
+        ];
+
+        #[test]
+        fn should_list_whole_subtree_parents_first() {
+            assert_eq!(
+                descendants_in(1, PROCESS_TABLE),
+                vec![10, 11, 101, 100, 1010]
+            );
+        }
+
+        #[test]
+        fn should_list_subtree_of_inner_process() {
+            assert_eq!(descendants_in(10, PROCESS_TABLE), vec![101, 100, 1010]);
+        }
+
+        #[test]
+        fn should_return_nothing_for_leaf() {
+            assert!(descendants_in(1010, PROCESS_TABLE).is_empty());
+        }
+
+        #[test]
+        fn should_return_nothing_for_unknown_process() {
+            assert!(descendants_in(42, PROCESS_TABLE).is_empty());
+        }
+    }
+
+    mod read_last_lines {
+        use super::super::*;
+        use std::path::PathBuf;
+
+        fn write_temp_file(name: &str, contents: &str) -> PathBuf {
+            let path = std::env::temp_dir().join(format!(
+                "read-last-lines-{}-{}.log",
+                std::process::id(),
+                name
+            ));
+            std::fs::write(&path, contents).unwrap();
+            path
+        }
+
+        #[test]
+        fn should_return_last_lines() {
+            let path = write_temp_file("last", "one\ntwo\nthree\n");
+            let lines = read_last_lines(&path, 2).unwrap();
+            std::fs::remove_file(&path).unwrap();
+
+            assert_eq!(lines, vec!["two", "three"]);
+        }
+
+        #[test]
+        fn should_return_all_lines_of_short_file() {
+            let path = write_temp_file("short", "one\ntwo");
+            let lines = read_last_lines(&path, 5).unwrap();
+            std::fs::remove_file(&path).unwrap();
+
+            assert_eq!(lines, vec!["one", "two"]);
+        }
+
+        #[test]
+        fn should_read_across_chunks() {
+            let contents: String = (0..5000).map(|i| format!("line {}\n", i)).collect();
+            let path = write_temp_file("chunks", &contents);
+            let lines = read_last_lines(&path, 3000).unwrap();
+            std::fs::remove_file(&path).unwrap();
+
+            assert_eq!(lines.len(), 3000);
+            assert_eq!(lines[0], "line 2000");
+            assert_eq!(lines[2999], "line 4999");
+        }
+
+        #[test]
+        fn should_return_nothing_for_zero_lines() {
+            let path = write_temp_file("zero", "one\n");
+            let lines = read_last_lines(&path, 0).unwrap();
+            std::fs::remove_file(&path).unwrap();
+
+            assert!(lines.is_empty());
+        }
+
+        #[test]
+        fn should_fail_for_missing_file() {
+            let path = std::env::temp_dir().join("read-last-lines-missing.log");
+
+            let err = read_last_lines(&path, 1).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+        }
+    }
+}
@@ -1,7 +1,251 @@
-use crate::AppHandle;
+use crate::{
+    settings::{Settings, SettingsError, MAX_ZOOM_FACTOR, MIN_ZOOM_FACTOR},
+    AppHandle,
+};
 use anyhow::{Context, Result};
-use log::error;
-use tauri::{WebviewWindow, WebviewWindowBuilder, WebviewUrl};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use tauri::{
+    Manager, PhysicalPosition, PhysicalSize, WebviewUrl, WebviewWindow, WebviewWindowBuilder,
+};
+use tauri_plugin_store::StoreExt;
+use thiserror::Error;
+
+// Saved window arrangements, keyed by layout name
+const WINDOW_LAYOUTS_FILE_NAME: &str = ".window_layouts.json";
+
+#[derive(Error, Debug)]
+pub enum WindowError {
+    #[error("invalid zoom factor {0}")]
+    InvalidZoom(f64),
+    #[error("unable to set zoom")]
+    Zoom(#[from] tauri::Error),
+    #[error(transparent)]
+    Settings(#[from] SettingsError),
+    #[error("window layout {0} not found")]
+    LayoutNotFound(String),
+    #[error("unable to arrange window {label}")]
+    Layout {
+        label: String,
+        #[source]
+        source: tauri::Error,
+    },
+    #[error("unable to open store {0}")]
+    Store(String),
+    #[error("unable to save store {0}")]
+    Save(String),
+}
+impl serde::Serialize for WindowError {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(self.to_string().as_ref())
+    }
+}
+
+// Applies the zoom factor to every open webview and persists it, returns the clamped factor
+#[tauri::command]
+pub fn set_zoom(app_handle: AppHandle, factor: f64) -> Result<f64, WindowError> {
+    if !factor.is_finite() {
+        return Err(WindowError::InvalidZoom(factor));
+    }
+    let factor = factor.clamp(MIN_ZOOM_FACTOR, MAX_ZOOM_FACTOR);
+
+    for window in app_handle.webview_windows().values() {
+        window.set_zoom(factor)?;
+    }
+    Settings::set_zoom_factor(&app_handle, factor)?;
+
+    Ok(factor)
+}
+
+#[tauri::command]
+pub fn get_zoom(app_handle: AppHandle) -> f64 {
+    Settings::zoom_factor(&app_handle)
+}
+
+// Persists whether the app shows a dock icon on macOS and applies it right away. The icon is
+// still hidden automatically while there are no windows. Other platforms only store the choice.
+#[tauri::command]
+pub fn set_dock_icon_visibility(app_handle: AppHandle, visible: bool) -> Result<(), WindowError> {
+    Settings::set_show_dock_icon(&app_handle, visible)?;
+
+    #[cfg(target_os = "macos")]
+    {
+        let has_windows = !app_handle.webview_windows().is_empty();
+        WindowHelper::new(app_handle).set_dock_icon_visibility(visible && has_windows);
+    }
+
+    Ok(())
+}
+
+// Where a window was and how it was shown when its layout was saved, in physical pixels
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WindowPlacement {
+    label: String,
+    // Path of the page the window showed, to recreate windows that were closed since
+    path: String,
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+    maximized: bool,
+    minimized: bool,
+    fullscreen: bool,
+}
+impl WindowPlacement {
+    fn capture(window: &WebviewWindow) -> tauri::Result<Self> {
+        let position = window.outer_position()?;
+        let size = window.inner_size()?;
+
+        Ok(WindowPlacement {
+            label: window.label().to_string(),
+            path: window.url()?.path().to_string(),
+            x: position.x,
+            y: position.y,
+            width: size.width,
+            height: size.height,
+            maximized: window.is_maximized()?,
+            minimized: window.is_minimized()?,
+            fullscreen: window.is_fullscreen()?,
+        })
+    }
+
+    // Moves the window onto the monitor it overlaps, or the first one if it's off screen
+    // because that monitor is gone, and shrinks it to fit. Monitors are given by their bounds.
+    fn clamp_to(&mut self, monitors: &[(PhysicalPosition<i32>, PhysicalSize<u32>)]) {
+        let overlaps = |(position, size): &&(PhysicalPosition<i32>, PhysicalSize<u32>)| {
+            self.x < position.x + size.width as i32
+                && self.x + self.width as i32 > position.x
+                && self.y < position.y + size.height as i32
+                && self.y + self.height as i32 > position.y
+        };
+        let Some((position, size)) = monitors.iter().find(overlaps).or(monitors.first()) else {
+            return;
+        };
+
+        self.width = self.width.min(size.width);
+        self.height = self.height.min(size.height);
+        self.x = self
+            .x
+            .clamp(position.x, position.x + (size.width - self.width) as i32);
+        self.y = self
+            .y
+            .clamp(position.y, position.y + (size.height - self.height) as i32);
+    }
+
+    fn apply(&self, window: &WebviewWindow) -> tauri::Result<()> {
+        window.set_fullscreen(false)?;
+        window.unmaximize()?;
+        window.set_size(PhysicalSize::new(self.width, self.height))?;
+        window.set_position(PhysicalPosition::new(self.x, self.y))?;
+        if self.maximized {
+            window.maximize()?;
+        }
+        if self.fullscreen {
+            window.set_fullscreen(true)?;
+        }
+        if self.minimized {
+            window.minimize()?;
+        } else {
+            window.show()?;
+        }
+
+        Ok(())
+    }
+}
+
+// Remembers the position, size and state of every open window under `name`, replacing a
+// layout of the same name
+#[tauri::command]
+pub fn save_window_layout(app_handle: AppHandle, name: String) -> Result<(), WindowError> {
+    let mut placements = app_handle
+        .webview_windows()
+        .values()
+        .map(|window| {
+            WindowPlacement::capture(window).map_err(|source| WindowError::Layout {
+                label: window.label().to_string(),
+                source,
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    placements.sort_by(|a, b| a.label.cmp(&b.label));
+
+    let store = app_handle
+        .store(WINDOW_LAYOUTS_FILE_NAME)
+        .map_err(|_| WindowError::Store(WINDOW_LAYOUTS_FILE_NAME.to_string()))?;
+    store.set(name.clone(), serde_json::json!(placements));
+    store
+        .save()
+        .map_err(|err| WindowError::Save(err.to_string()))?;
+    info!(
+        "Saved window layout {} with {} windows",
+        name,
+        placements.len()
+    );
+
+    Ok(())
+}
+
+// Puts every window of the layout back where it was, recreating windows that were closed.
+// Windows that aren't part of the layout stay as they are.
+#[tauri::command]
+pub async fn restore_window_layout(app_handle: AppHandle, name: String) -> Result<(), WindowError> {
+    let store = app_handle
+        .store(WINDOW_LAYOUTS_FILE_NAME)
+        .map_err(|_| WindowError::Store(WINDOW_LAYOUTS_FILE_NAME.to_string()))?;
+    let placements = store
+        .get(&name)
+        .and_then(|value| serde_json::from_value::<Vec<WindowPlacement>>(value).ok())
+        .ok_or_else(|| WindowError::LayoutNotFound(name.clone()))?;
+    let monitors: Vec<_> = app_handle
+        .available_monitors()
+        .unwrap_or_else(|err| {
+            warn!(
+                "Failed to list monitors, not clamping window layout: {}",
+                err
+            );
+            vec![]
+        })
+        .iter()
+        .map(|monitor| (*monitor.position(), *monitor.size()))
+        .collect();
+
+    for mut placement in placements {
+        placement.clamp_to(&monitors);
+        let layout_error = |source| WindowError::Layout {
+            label: placement.label.clone(),
+            source,
+        };
+
+        let window = match app_handle.get_webview_window(&placement.label) {
+            Some(window) => window,
+            None => {
+                let window = if placement.label == "main" {
+                    build_main_window(&app_handle, app_handle.package_info().name.clone())
+                } else {
+                    WebviewWindowBuilder::new(
+                        &app_handle,
+                        placement.label.clone(),
+                        WebviewUrl::App(placement.path.trim_start_matches('/').into()),
+                    )
+                    .visible(false)
+                    .build()
+                }
+                .map_err(layout_error)?;
+                // Recreated windows get the same treatment as windows opened any other way
+                WindowHelper::new(app_handle.clone()).setup(&window);
+                window
+            }
+        };
+        placement.apply(&window).map_err(layout_error)?;
+    }
+    info!("Restored window layout {}", name);
+
+    Ok(())
+}
 
 #[derive(Clone, Debug)]
 pub struct WindowHelper {
@@ -13,11 +257,17 @@ impl WindowHelper {
         Self { app_handle }
     }
 
-    pub fn setup(&self, _window: &WebviewWindow) {
+    pub fn setup(&self, window: &WebviewWindow) {
+        // reapply the zoom level the user picked last time
+        let zoom_factor = Settings::zoom_factor(&self.app_handle);
+        if let Err(err) = window.set_zoom(zoom_factor) {
+            warn!("Failed to restore zoom level {}: {}", zoom_factor, err);
+        }
+
         // open browser devtools automatically during development
         #[cfg(debug_assertions)]
         {
-            _window.open_devtools();
+            window.open_devtools();
         }
 
         // Window vibrancy
@@ -38,35 +288,28 @@ impl WindowHelper {
         let self_ = self.clone();
 
         #[cfg(target_os = "macos")]
-        self.set_dock_icon_visibility(true);
+        if Settings::show_dock_icon(&self.app_handle) {
+            self.set_dock_icon_visibility(true);
+        }
 
         self.app_handle
             .run_on_main_thread(move || {
-                // Config should match the config in `src-tauri/tauri.conf.json` for a consistent window appearance
-                let window_builder =
-                    WebviewWindowBuilder::new(&handle, "main".to_string(), WebviewUrl::default())
-                        .title(app_name)
-                        .fullscreen(false)
-                        .resizable(true)
-                        .transparent(true)
-                        .min_inner_size(1000.0, 700.0)
-                        .inner_size(1200.0, 800.0)
-                        .visible(false);
-
-                #[cfg(target_os = "macos")]
-                let window_builder = window_builder
-                    .title_bar_style(tauri::TitleBarStyle::Overlay)
-                    .hidden_title(true);
-
-                let window = window_builder.build();
-
-                if let Ok(window) = window {
+                if let Ok(window) = build_main_window(&handle, app_name) {
                     self_.setup(&window);
                 }
             })
             .context("Failed to create main window")
     }
 
+    // Used during setup when the window from `tauri.conf.json` failed to come up,
+    // must be called on the main thread
+    pub fn recreate_main(&self, app_name: String) -> tauri::Result<WebviewWindow> {
+        let window = build_main_window(&self.app_handle, app_name)?;
+        self.setup(&window);
+
+        Ok(window)
+    }
+
     #[allow(dead_code)]
     pub fn new_update_ready_window(&self) -> Result<()> {
         let handle = self.app_handle.clone();
@@ -93,6 +336,26 @@ impl WindowHelper {
     }
 }
 
+fn build_main_window(handle: &AppHandle, app_name: String) -> tauri::Result<WebviewWindow> {
+    // Config should match the config in `src-tauri/tauri.conf.json` for a consistent window appearance
+    let window_builder =
+        WebviewWindowBuilder::new(handle, "main".to_string(), WebviewUrl::default())
+            .title(app_name)
+            .fullscreen(false)
+            .resizable(true)
+            .transparent(true)
+            .min_inner_size(1000.0, 700.0)
+            .inner_size(1200.0, 800.0)
+            .visible(false);
+
+    #[cfg(target_os = "macos")]
+    let window_builder = window_builder
+        .title_bar_style(tauri::TitleBarStyle::Overlay)
+        .hidden_title(true);
+
+    window_builder.build()
+}
+
 #[cfg(target_os = "macos")]
 use cocoa::{
     appkit::{
@@ -265,3 +528,88 @@ extern "C" {
     fn TransformProcessType(psn: *const ProcessSerialNumber, transformState: TransformState)
         -> i32;
 }
+
+#[cfg(test)]
+mod tests {
+    mod window_placement {
+        use super::super::*;
+
+        fn placement(x: i32, y: i32, width: u32, height: u32) -> WindowPlacement {
+            WindowPlacement {
+                label: "main".to_string(),
+                path: "/".to_string(),
+                x,
+                y,
+                width,
+                height,
+                maximized: false,
+                minimized: false,
+                fullscreen: false,
+            }
+        }
+
+        fn monitor(
+            x: i32,
+            y: i32,
+            width: u32,
+            height: u32,
+        ) -> (PhysicalPosition<i32>, PhysicalSize<u32>) {
+            (
+                PhysicalPosition::new(x, y),
+                PhysicalSize::new(width, height),
+            )
+        }
+
+        fn bounds(placement: &WindowPlacement) -> (i32, i32, u32, u32) {
+            (placement.x, placement.y, placement.width, placement.height)
+        }
+
+        #[test]
+        fn should_keep_window_inside_monitor() {
+            let mut window = placement(100, 100, 800, 600);
+            window.clamp_to(&[monitor(0, 0, 1920, 1080)]);
+
+            assert_eq!(bounds(&window), (100, 100, 800, 600));
+        }
+
+        #[test]
+        fn should_move_partly_off_screen_window_back() {
+            let mut window = placement(1500, -50, 800, 600);
+            window.clamp_to(&[monitor(0, 0, 1920, 1080)]);
+
+            assert_eq!(bounds(&window), (1120, 0, 800, 600));
+        }
+
+        #[test]
+        fn should_shrink_window_larger_than_monitor() {
+            let mut window = placement(-100, 50, 2560, 1440);
+            window.clamp_to(&[monitor(0, 0, 1920, 1080)]);
+
+            assert_eq!(bounds(&window), (0, 0, 1920, 1080));
+        }
+
+        #[test]
+        fn should_clamp_to_monitor_at_negative_origin() {
+            let mut window = placement(-2000, 200, 800, 600);
+            window.clamp_to(&[monitor(0, 0, 1920, 1080), monitor(-1920, 0, 1920, 1080)]);
+
+            assert_eq!(bounds(&window), (-1920, 200, 800, 600));
+        }
+
+        #[test]
+        fn should_move_window_of_missing_monitor_to_first() {
+            let mut window = placement(4000, 100, 800, 600);
+            window.clamp_to(&[monitor(0, 0, 1920, 1080)]);
+
+            assert_eq!(bounds(&window), (1120, 100, 800, 600));
+        }
+
+        #[test]
+        fn should_leave_window_without_monitors() {
+            let mut window = placement(4000, 100, 800, 600);
+            window.clamp_to(&[]);
+
+            assert_eq!(bounds(&window), (4000, 100, 800, 600));
+        }
+    }
+}
@@ -0,0 +1,699 @@
+use crate::commands::{
+    delete_workspace::DeleteWorkspaceCommand,
+    exec_workspace::{ExecStream, ExecWorkspaceCommand},
+    list_workspaces::ListWorkspacesCommand,
+    logs_workspace::LogsWorkspaceCommand,
+    stop_workspace::StopWorkspaceCommand,
+    up_workspace::{UpWorkspaceCommand, KNOWN_IDES},
+    workspace_status::{WorkspaceState, WorkspaceStatus, WorkspaceStatusCommand},
+    DevpodCommandError,
+};
+use crate::providers::{self, ProviderHealth};
+use crate::resource_watcher::{Identifiable, Workspace};
+use crate::settings::Settings;
+use crate::{util, AppHandle, AppState};
+use anyhow::anyhow;
+use log::{error, info, warn};
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+use std::time::{Duration, Instant};
+use tauri::{Emitter, Manager};
+use tauri_plugin_notification::NotificationExt;
+use tauri_plugin_shell::ShellExt;
+
+const DELETE_WORKSPACE_PROGRESS_EVENT: &str = "delete-workspace-progress";
+const OPEN_WORKSPACE_PROGRESS_EVENT: &str = "open-workspace-progress";
+const AUTO_START_PROGRESS_EVENT: &str = "auto-start-workspace-progress";
+const EXEC_OUTPUT_EVENT: &str = "workspace-exec-output";
+const EXEC_EXIT_EVENT: &str = "workspace-exec-exit";
+// How long auto start waits for the first workspace poll and the pro daemons
+const AUTO_START_READY_TIMEOUT: Duration = Duration::from_secs(120);
+// Lines returned by `open_workspace_logs` for workspaces without a local directory
+const WORKSPACE_LOG_LINES: usize = 200;
+// Same limit the CLI enforces for workspace ids
+const MAX_WORKSPACE_NAME_LENGTH: usize = 48;
+// Walking a workspace can take a while, the dashboard polls more often than sizes change
+const DISK_USAGE_CACHE_TTL: Duration = Duration::from_secs(60);
+// Operations finishing faster than this are still on the user's mind, no notification needed
+const COMPLETION_NOTIFICATION_THRESHOLD: Duration = Duration::from_secs(10);
+
+// Everything a delete would remove, so the UI can ask for an accurate confirmation
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeleteWorkspacePreview {
+    id: String,
+    provider: Option<String>,
+    context: Option<String>,
+    state: WorkspaceState,
+    resources: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct DiskUsageCache {
+    entries: HashMap<String, (Instant, u64)>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspacesDiskUsage {
+    total_bytes: u64,
+    workspaces: HashMap<String, u64>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceNameValidation {
+    valid: bool,
+    normalized: String,
+    problems: Vec<String>,
+}
+
+// Discrete changes of a workspace, emitted when they complete so the UI can notify about them
+#[derive(Debug, Clone, Copy)]
+pub enum WorkspaceLifecycle {
+    Created,
+    Started,
+    Stopped,
+    Deleted,
+}
+
+impl WorkspaceLifecycle {
+    fn event_name(&self) -> &'static str {
+        match self {
+            WorkspaceLifecycle::Created => "workspace-created",
+            WorkspaceLifecycle::Started => "workspace-started",
+            WorkspaceLifecycle::Stopped => "workspace-stopped",
+            WorkspaceLifecycle::Deleted => "workspace-deleted",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct WorkspaceLifecycleEvent {
+    id: String,
+    // Not known for workspaces created outside of the app
+    state: Option<WorkspaceState>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct WorkspaceProgress {
+    id: String,
+    message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct WorkspaceExecOutput {
+    operation_id: String,
+    stream: ExecStream,
+    data: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct WorkspaceExecExit {
+    operation_id: String,
+    // None if the command was killed by a signal
+    code: Option<i32>,
+}
+
+// Runs `command` inside the workspace and streams its output as `workspace-exec-output` events,
+// tagged with `operation_id`. Input is sent with `write_operation_stdin` and the command stopped
+// with `cancel_operation`. The exit code is returned and emitted as `workspace-exec-exit`.
+#[tauri::command]
+pub async fn exec_in_workspace(
+    app_handle: AppHandle,
+    id: String,
+    command: String,
+    operation_id: String,
+) -> Result<Option<i32>, DevpodCommandError> {
+    info!("Running command in workspace {}", id);
+    let output_handle = app_handle.clone();
+    let code = ExecWorkspaceCommand::new(id, command)
+        .exec_streaming(&app_handle, &operation_id, |stream, data| {
+            let output = WorkspaceExecOutput {
+                operation_id: operation_id.clone(),
+                stream,
+                data,
+            };
+            if let Err(err) = output_handle.emit(EXEC_OUTPUT_EVENT, output) {
+                warn!("Failed to emit exec output: {}", err);
+            }
+        })
+        .await?;
+
+    let exit = WorkspaceExecExit {
+        operation_id,
+        code,
+    };
+    if let Err(err) = app_handle.emit(EXEC_EXIT_EVENT, exit) {
+        warn!("Failed to emit exec exit: {}", err);
+    }
+
+    Ok(code)
+}
+
+// Stops a workspace without deleting it and returns the state reported by the CLI afterwards.
+#[tauri::command]
+pub async fn stop_workspace(
+    app_handle: AppHandle,
+    id: String,
+) -> Result<WorkspaceStatus, DevpodCommandError> {
+    let status = WorkspaceStatusCommand::new(id.clone())
+        .exec(&app_handle)
+        .await?;
+    if status.state != WorkspaceState::Stopped {
+        info!("Stopping workspace {}", id);
+        StopWorkspaceCommand::new(id.clone())
+            .exec(&app_handle)
+            .await?;
+    } else {
+        info!("Workspace {} is already stopped", id);
+    }
+
+    let status = WorkspaceStatusCommand::new(id.clone())
+        .exec(&app_handle)
+        .await?;
+    let state = app_handle.state::<AppState>();
+    state
+        .workspaces
+        .write()
+        .await
+        .set_state(id.clone(), status.state);
+    emit_lifecycle_event(
+        &app_handle,
+        WorkspaceLifecycle::Stopped,
+        &id,
+        Some(status.state),
+    );
+
+    Ok(status)
+}
+
+// In preview mode only describes what would be removed. Otherwise deletes the workspace,
+// emitting each line of CLI output as a progress event, and returns what was removed.
+// With an `operation_id` the delete can be aborted through `cancel_operation`.
+#[tauri::command]
+pub async fn delete_workspace(
+    app_handle: AppHandle,
+    id: String,
+    preview: bool,
+    operation_id: Option<String>,
+) -> Result<DeleteWorkspacePreview, DevpodCommandError> {
+    let workspace = ListWorkspacesCommand::new()
+        .exec(&app_handle)
+        .await?
+        .into_iter()
+        .find(|w| w.id() == id)
+        .ok_or_else(|| DevpodCommandError::Any(anyhow!("workspace {} not found", id)))?;
+    let status = WorkspaceStatusCommand::new(id.clone())
+        .exec(&app_handle)
+        .await?;
+
+    let mut resources = vec![format!("workspace {}", id)];
+    if let Some(machine_id) = workspace.machine_id() {
+        resources.push(format!("machine {}", machine_id));
+    }
+    let result = DeleteWorkspacePreview {
+        id: id.clone(),
+        provider: workspace.provider().map(String::from),
+        context: workspace.context().map(String::from),
+        state: status.state,
+        resources,
+    };
+    if preview {
+        return Ok(result);
+    }
+
+    info!("Deleting workspace {}", id);
+    let started_at = Instant::now();
+    let progress_handle = app_handle.clone();
+    let deleted = DeleteWorkspaceCommand::new(id.clone())
+        .exec_with_progress(&app_handle, operation_id.as_deref(), |message| {
+            let progress = WorkspaceProgress {
+                id: id.clone(),
+                message,
+            };
+            if let Err(err) = progress_handle.emit(DELETE_WORKSPACE_PROGRESS_EVENT, progress) {
+                warn!("Failed to emit delete progress: {}", err);
+            }
+        })
+        .await;
+    notify_completion(
+        &app_handle,
+        started_at,
+        match &deleted {
+            Ok(_) => format!("Workspace {} deleted", id),
+            Err(err) => format!("Failed to delete workspace {}: {}", id, err),
+        },
+    );
+    deleted?;
+
+    let state = app_handle.state::<AppState>();
+    state
+        .workspaces
+        .write()
+        .await
+        .set_state(id.clone(), WorkspaceState::NotFound);
+    emit_lifecycle_event(
+        &app_handle,
+        WorkspaceLifecycle::Deleted,
+        &id,
+        Some(WorkspaceState::NotFound),
+    );
+
+    Ok(result)
+}
+
+// Starts the workspace and opens it in `ide`, emitting each line of CLI output as a progress
+// event. The workspace is marked busy while the CLI runs. With an `operation_id` the start
+// can be aborted through `cancel_operation`.
+#[tauri::command]
+pub async fn open_workspace(
+    app_handle: AppHandle,
+    id: String,
+    ide: String,
+    operation_id: Option<String>,
+) -> Result<WorkspaceStatus, DevpodCommandError> {
+    if !KNOWN_IDES.contains(&ide.as_str()) {
+        return Err(DevpodCommandError::Any(anyhow!("unknown IDE {}", ide)));
+    }
+
+    let state = app_handle.state::<AppState>();
+    state
+        .workspaces
+        .write()
+        .await
+        .set_state(id.clone(), WorkspaceState::Busy);
+
+    info!("Opening workspace {} in {}", id, ide);
+    let started_at = Instant::now();
+    let progress_handle = app_handle.clone();
+    let result = UpWorkspaceCommand::new(id.clone(), ide)
+        .exec_with_progress(&app_handle, operation_id.as_deref(), |message| {
+            let progress = WorkspaceProgress {
+                id: id.clone(),
+                message,
+            };
+            if let Err(err) = progress_handle.emit(OPEN_WORKSPACE_PROGRESS_EVENT, progress) {
+                warn!("Failed to emit open progress: {}", err);
+            }
+        })
+        .await;
+    notify_completion(
+        &app_handle,
+        started_at,
+        match &result {
+            Ok(_) => format!("Workspace {} is ready", id),
+            Err(err) => format!("Failed to open workspace {}: {}", id, err),
+        },
+    );
+
+    // record what the CLI reports rather than assuming the outcome of `up`
+    let status = WorkspaceStatusCommand::new(id.clone())
+        .exec(&app_handle)
+        .await;
+    if let Ok(status) = &status {
+        state
+            .workspaces
+            .write()
+            .await
+            .set_state(id.clone(), status.state);
+    }
+    result?;
+
+    let status = status?;
+    emit_lifecycle_event(
+        &app_handle,
+        WorkspaceLifecycle::Started,
+        &id,
+        Some(status.state),
+    );
+
+    Ok(status)
+}
+
+// Starts the workspaces listed in the `autoStartWorkspaces` setting once the workspace list and
+// the daemons are ready. Workspaces that are already up or whose provider isn't healthy are
+// skipped. Every line of CLI output is emitted as an auto start progress event.
+pub async fn auto_start(app_handle: AppHandle) {
+    let ids = Settings::auto_start_workspaces(&app_handle);
+    if ids.is_empty() {
+        return;
+    }
+    if !wait_until_ready(&app_handle).await {
+        warn!(
+            "Workspaces or daemons not ready after {:?}, skipping auto start",
+            AUTO_START_READY_TIMEOUT
+        );
+        return;
+    }
+
+    let state = app_handle.state::<AppState>();
+    for id in ids {
+        let provider = {
+            let workspaces = state.workspaces.read().await;
+            match workspaces.find_workspace(&id) {
+                Some(workspace) => workspace.provider().map(String::from),
+                None => {
+                    warn!("Skipping auto start of {}: workspace not found", id);
+                    continue;
+                }
+            }
+        };
+        match WorkspaceStatusCommand::new(id.clone())
+            .exec(&app_handle)
+            .await
+        {
+            Ok(status)
+                if matches!(status.state, WorkspaceState::Running | WorkspaceState::Busy) =>
+            {
+                info!("Skipping auto start of {}: already {:?}", id, status.state);
+                continue;
+            }
+            Ok(_) => {}
+            Err(err) => {
+                warn!("Skipping auto start of {}: {}", id, err);
+                continue;
+            }
+        }
+        if let Some(provider) = provider {
+            let (health, errors) = providers::check_provider_health(&app_handle, &provider).await;
+            if health != ProviderHealth::Reachable {
+                warn!(
+                    "Skipping auto start of {}: provider {} is {:?} {}",
+                    id,
+                    provider,
+                    health,
+                    errors.join("; ")
+                );
+                continue;
+            }
+        }
+
+        info!("Auto starting workspace {}", id);
+        state
+            .workspaces
+            .write()
+            .await
+            .set_state(id.clone(), WorkspaceState::Busy);
+        let progress_handle = app_handle.clone();
+        let result = UpWorkspaceCommand::new(id.clone(), "none".to_string())
+            .exec_with_progress(&app_handle, None, |message| {
+                let progress = WorkspaceProgress {
+                    id: id.clone(),
+                    message,
+                };
+                if let Err(err) = progress_handle.emit(AUTO_START_PROGRESS_EVENT, progress) {
+                    warn!("Failed to emit auto start progress: {}", err);
+                }
+            })
+            .await;
+        if let Err(err) = &result {
+            warn!("Failed to auto start workspace {}: {}", id, err);
+        }
+
+        if let Ok(status) = WorkspaceStatusCommand::new(id.clone())
+            .exec(&app_handle)
+            .await
+        {
+            state
+                .workspaces
+                .write()
+                .await
+                .set_state(id.clone(), status.state);
+            if result.is_ok() {
+                emit_lifecycle_event(
+                    &app_handle,
+                    WorkspaceLifecycle::Started,
+                    &id,
+                    Some(status.state),
+                );
+            }
+        }
+    }
+}
+
+async fn wait_until_ready(app_handle: &AppHandle) -> bool {
+    let state = app_handle.state::<AppState>();
+    let started_at = Instant::now();
+    while started_at.elapsed() < AUTO_START_READY_TIMEOUT {
+        let polled = state.workspaces.read().await.is_polled();
+        if polled && state.pro.read().await.all_ready() {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+
+    false
+}
+
+// Tells the user about the outcome of an operation that took long enough for them to switch
+// away, unless the app is in focus or completion notifications are turned off
+fn notify_completion(app_handle: &AppHandle, started_at: Instant, message: String) {
+    if started_at.elapsed() < COMPLETION_NOTIFICATION_THRESHOLD
+        || !Settings::completion_notifications_enabled(app_handle)
+    {
+        return;
+    }
+    let focused = app_handle
+        .get_webview_window("main")
+        .and_then(|window| window.is_focused().ok())
+        .unwrap_or(false);
+    if focused {
+        return;
+    }
+
+    let res = app_handle
+        .notification()
+        .builder()
+        .title("Workspace operation finished")
+        .body(message)
+        .show();
+    if let Err(err) = res {
+        error!("Unable to send completion notification: {}", err);
+    }
+}
+
+pub fn emit_lifecycle_event(
+    app_handle: &AppHandle,
+    lifecycle: WorkspaceLifecycle,
+    id: &str,
+    state: Option<WorkspaceState>,
+) {
+    let event = WorkspaceLifecycleEvent {
+        id: id.to_string(),
+        state,
+    };
+    if let Err(err) = app_handle.emit(lifecycle.event_name(), event) {
+        warn!("Failed to emit {}: {}", lifecycle.event_name(), err);
+    }
+}
+
+// Applies the CLI's workspace id rules up front so the create flow can give instant feedback.
+// The name is normalized the way the CLI would (trimmed, lower case, spaces and underscores
+// as dashes) before the remaining problems are reported.
+#[tauri::command]
+pub fn validate_workspace_name(name: String) -> WorkspaceNameValidation {
+    let normalized = normalize_workspace_name(&name);
+    let problems = workspace_name_problems(&normalized);
+
+    WorkspaceNameValidation {
+        valid: problems.is_empty(),
+        normalized,
+        problems,
+    }
+}
+
+fn normalize_workspace_name(name: &str) -> String {
+    name.trim()
+        .to_lowercase()
+        .chars()
+        .map(|c| {
+            if c.is_whitespace() || c == '_' {
+                '-'
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+fn workspace_name_problems(name: &str) -> Vec<String> {
+    if name.is_empty() {
+        return vec!["name must not be empty".to_string()];
+    }
+
+    let mut problems = vec![];
+    let invalid: BTreeSet<char> = name
+        .chars()
+        .filter(|c| !(c.is_ascii_lowercase() || c.is_ascii_digit() || *c == '-'))
+        .collect();
+    if !invalid.is_empty() {
+        problems.push(format!(
+            "name can only contain lower case letters, numbers and dashes, found {}",
+            invalid
+                .iter()
+                .map(|c| format!("'{}'", c))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+    if name.starts_with('-') || name.ends_with('-') {
+        problems.push("name must not start or end with a dash".to_string());
+    }
+    let length = name.chars().count();
+    if length > MAX_WORKSPACE_NAME_LENGTH {
+        problems.push(format!(
+            "name must be at most {} characters, got {}",
+            MAX_WORKSPACE_NAME_LENGTH, length
+        ));
+    }
+
+    problems
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum WorkspaceLogs {
+    // The directory was opened in the file manager
+    Opened { path: String },
+    // Recent lines of the workspace's logs, for workspaces without a local directory
+    Lines { lines: Vec<String> },
+}
+
+// Opens the directory the CLI keeps for the workspace, next to its files it holds what the CLI
+// logs about it. Remote workspaces have no such directory, the last lines of their logs are
+// returned instead.
+#[tauri::command]
+pub async fn open_workspace_logs(
+    app_handle: AppHandle,
+    id: String,
+) -> Result<WorkspaceLogs, DevpodCommandError> {
+    let workspace = ListWorkspacesCommand::new()
+        .exec(&app_handle)
+        .await?
+        .into_iter()
+        .find(|w| w.id() == id)
+        .ok_or_else(|| DevpodCommandError::Any(anyhow!("workspace {} not found", id)))?;
+
+    if let Some(dir) = workspace.dirs()?.into_iter().find(|dir| dir.is_dir()) {
+        let path = dir.to_string_lossy().to_string();
+        info!("Opening logs of workspace {} at {}", id, path);
+        #[allow(deprecated)]
+        app_handle.shell().open(&path, None)?;
+
+        return Ok(WorkspaceLogs::Opened { path });
+    }
+
+    let mut lines = LogsWorkspaceCommand::new(id).exec(&app_handle).await?;
+    let lines = lines.split_off(lines.len().saturating_sub(WORKSPACE_LOG_LINES));
+
+    Ok(WorkspaceLogs::Lines { lines })
+}
+
+// Bytes the workspace occupies on disk, cached for DISK_USAGE_CACHE_TTL
+#[tauri::command]
+pub async fn get_workspace_disk_usage(
+    app_handle: AppHandle,
+    id: String,
+) -> Result<u64, DevpodCommandError> {
+    let workspace = ListWorkspacesCommand::new()
+        .exec(&app_handle)
+        .await?
+        .into_iter()
+        .find(|w| w.id() == id)
+        .ok_or_else(|| DevpodCommandError::Any(anyhow!("workspace {} not found", id)))?;
+
+    disk_usage(&app_handle, workspace).await
+}
+
+#[tauri::command]
+pub async fn get_workspaces_disk_usage(
+    app_handle: AppHandle,
+) -> Result<WorkspacesDiskUsage, DevpodCommandError> {
+    let mut workspaces = HashMap::new();
+    for workspace in ListWorkspacesCommand::new().exec(&app_handle).await? {
+        let id = workspace.id();
+        workspaces.insert(id, disk_usage(&app_handle, workspace).await?);
+    }
+
+    Ok(WorkspacesDiskUsage {
+        total_bytes: workspaces.values().sum(),
+        workspaces,
+    })
+}
+
+async fn disk_usage(
+    app_handle: &AppHandle,
+    workspace: Workspace,
+) -> Result<u64, DevpodCommandError> {
+    let id = workspace.id();
+    let state = app_handle.state::<AppState>();
+    if let Some((computed_at, bytes)) = state.disk_usage.lock().unwrap().entries.get(&id) {
+        if computed_at.elapsed() < DISK_USAGE_CACHE_TTL {
+            return Ok(*bytes);
+        }
+    }
+
+    let dirs = workspace.dirs()?;
+    let bytes = tauri::async_runtime::spawn_blocking(move || {
+        dirs.iter().map(|dir| util::dir_size(dir)).sum::<u64>()
+    })
+    .await
+    .map_err(|err| DevpodCommandError::Any(anyhow!("disk usage walk failed: {}", err)))?;
+
+    state
+        .disk_usage
+        .lock()
+        .unwrap()
+        .entries
+        .insert(id, (Instant::now(), bytes));
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    mod workspace_name {
+        use super::super::*;
+
+        #[test]
+        fn should_normalize() {
+            let validation = validate_workspace_name("  My_Workspace 1 ".to_string());
+
+            assert!(validation.valid);
+            assert_eq!(validation.normalized, "my-workspace-1".to_string());
+        }
+
+        #[test]
+        fn should_report_invalid_characters() {
+            let validation = validate_workspace_name("ws.1/2".to_string());
+
+            assert!(!validation.valid);
+            assert_eq!(validation.problems.len(), 1);
+            assert!(validation.problems[0].contains("'.', '/'"));
+        }
+
+        #[test]
+        fn should_report_invalid_characters_once() {
+            let validation = validate_workspace_name("a!b!c".to_string());
+
+            assert!(validation.problems[0].ends_with("found '!'"));
+        }
+
+        #[test]
+        fn should_report_every_problem() {
+            let validation = validate_workspace_name(format!("-{}", "a".repeat(48)));
+
+            assert_eq!(validation.problems.len(), 2);
+        }
+
+        #[test]
+        fn should_reject_empty() {
+            assert!(!validate_workspace_name("   ".to_string()).valid);
+        }
+    }
+}
@@ -1,12 +1,14 @@
 import asyncio
+import os
 import sys
+import tempfile
 import logging
 
 logging.basicConfig(level=logging.INFO, format='%(levelname)s:%(name)s:%(message)s')
 
 # Attempt to import the Rust extension
 try:
-    from agent_lifecycle_rust import execute_command_rust_async, CommandOutput as RustCommandOutput
+    from agent_lifecycle_rust import execute_command_rust_async, execute_pipeline_rust_async, CommandOutput as RustCommandOutput
     print("SUCCESS: Rust command executor module loaded.")
 except ImportError as e:
     print(f"ERROR: Failed to import Rust command executor: {e}")
@@ -15,8 +17,9 @@ except ImportError as e:
 
 async def run_test(test_name, command_str, cwd=None, env_vars=None, timeout_seconds=None, stdin_str=None,
                  expected_stdout_contains=None, expected_stderr_contains=None,
-                 expected_exit_code=None,
-                 expected_exception_type=None, expected_exception_message_contains=None):
+                 expected_exit_code=None, expected_rusage=False, expected_resolved_env=None,
+                 expected_transcript_contains=None, expected_stdout_bytes=None, expected_success=None, expected_exception_type=None, expected_exception_message_contains=None,
+                 **executor_kwargs):
     print(f"\n--- Running Test: {test_name} ---")
     print(f"Command: {command_str}")
     if stdin_str:
@@ -30,7 +33,8 @@ async def run_test(test_name, command_str, cwd=None, env_vars=None, timeout_seco
             cwd=cwd,
             env_vars=env_vars,
             timeout_seconds=timeout_seconds,
-            stdin_str=stdin_str
+            stdin_str=stdin_str,
+            **executor_kwargs
         )
 
         if expected_exception_type is not None:
@@ -53,6 +57,28 @@ async def run_test(test_name, command_str, cwd=None, env_vars=None, timeout_seco
         if expected_exit_code is not None and result.exit_code != expected_exit_code:
             print(f"FAIL: Expected exit code {expected_exit_code}, got {result.exit_code}")
             passed = False
+        if expected_resolved_env is not None:
+            for key, value in expected_resolved_env.items():
+                actual = (result.resolved_env or {}).get(key)
+                if actual != value:
+                    print(f"FAIL: Expected resolved_env[{key!r}] to be {value!r}, got {actual!r}")
+                    passed = False
+        if expected_stdout_bytes is not None and result.stdout_bytes != expected_stdout_bytes:
+            print(f"FAIL: Expected stdout bytes {expected_stdout_bytes!r}, got {result.stdout_bytes!r}")
+            passed = False
+        if expected_success is not None and result.success() != expected_success:
+            print(f"FAIL: Expected success() to be {expected_success}, got {result.success()}")
+            passed = False
+        if expected_transcript_contains is not None:
+            transcript = result.transcript()
+            for expected in expected_transcript_contains:
+                if expected not in transcript:
+                    print(f"FAIL: Expected transcript to contain {expected!r}, got {transcript!r}")
+                    passed = False
+        if expected_rusage and None in (result.max_rss_bytes, result.user_cpu_ms, result.sys_cpu_ms):
+            print(f"FAIL: Expected resource usage, got max_rss_bytes={result.max_rss_bytes}, "
+                  f"user_cpu_ms={result.user_cpu_ms}, sys_cpu_ms={result.sys_cpu_ms}")
+            passed = False
         
         if passed:
             print("PASS")
@@ -81,6 +107,36 @@ async def run_test(test_name, command_str, cwd=None, env_vars=None, timeout_seco
             print("FAIL")
             return False
 
+async def run_pipeline_test(test_name, commands, expected_stdout_contains=None, expected_exit_code=None,
+                            expected_stage_exit_codes=None, **executor_kwargs):
+    print(f"\n--- Running Test: {test_name} ---")
+    print(f"Pipeline: {' | '.join(commands)}")
+
+    try:
+        result = await execute_pipeline_rust_async(commands=commands, **executor_kwargs)
+    except Exception as e:
+        print(f"PYTHON UNEXPECTED EXCEPTION during test: {type(e).__name__}: {e}")
+        print("FAIL")
+        return False
+
+    print(f"Stdout: {result.output.stdout.strip()[:200]}")
+    print(f"Exit Code: {result.exit_code}, Stage Exit Codes: {result.stage_exit_codes}")
+
+    passed = True
+    if expected_stdout_contains is not None and expected_stdout_contains not in result.output.stdout:
+        print(f"FAIL: Expected stdout to contain '{expected_stdout_contains}'")
+        passed = False
+    if expected_exit_code is not None and result.exit_code != expected_exit_code:
+        print(f"FAIL: Expected exit code {expected_exit_code}, got {result.exit_code}")
+        passed = False
+    if expected_stage_exit_codes is not None and result.stage_exit_codes != expected_stage_exit_codes:
+        print(f"FAIL: Expected stage exit codes {expected_stage_exit_codes}, got {result.stage_exit_codes}")
+        passed = False
+
+    if passed:
+        print("PASS")
+    return passed
+
 async def main():
     test_results = []
 
@@ -136,6 +192,188 @@ async def main():
                                      cwd="/tmp", 
                                      expected_stdout_contains="/tmp", expected_exit_code=0))
 
+    # 11. Env var expansion in the command string, including the $$ escape
+    test_results.append(await run_test("Expand Env Vars", "echo $MY_EXPAND_VAR ${MY_EXPAND_VAR}_suffix $$literal",
+                                     env_vars={"MY_EXPAND_VAR": "expanded"},
+                                     expand_env=True,
+                                     expected_stdout_contains="expanded expanded_suffix $literal", expected_exit_code=0))
+
+    # 12. Env var expansion in cwd, falling back to the process environment
+    test_results.append(await run_test("Expand Env Vars In CWD", "pwd",
+                                     cwd="${HOME}",
+                                     expand_env=True,
+                                     expected_stdout_contains=os.path.expanduser("~"), expected_exit_code=0))
+
+    # 13. Unterminated variable reference
+    test_results.append(await run_test("Expand Env Vars Unterminated", "echo ${MY_EXPAND_VAR",
+                                     expand_env=True,
+                                     expected_exception_type=ValueError,
+                                     expected_exception_message_contains="unterminated variable reference"))
+
+    # 14. Resource usage of the finished child
+    if sys.platform != "win32":
+        test_results.append(await run_test("Resource Usage", "python3 -c \"x = bytearray(32 * 1024 * 1024)\"",
+                                         expected_exit_code=0, expected_rusage=True))
+
+    # 15. Redirect stdout to a file instead of buffering it
+    stdout_file = os.path.join(tempfile.gettempdir(), "rust_executor_stdout.txt")
+    passed = await run_test("Stdout To File", "echo written to disk",
+                            stdout_path=stdout_file,
+                            expected_exit_code=0)
+    with open(stdout_file) as f:
+        if "written to disk" not in f.read():
+            print(f"FAIL: Expected {stdout_file} to contain the command output")
+            passed = False
+    test_results.append(passed)
+
+    # 16. Output file that cannot be created
+    test_results.append(await run_test("Unwritable Output File", "echo hello",
+                                     stderr_path="/nonexistent-dir/stderr.txt",
+                                     expected_exception_type=OSError,
+                                     expected_exception_message_contains="Failed to open output file '/nonexistent-dir/stderr.txt'"))
+
+    # 17. Sub-second timeout in milliseconds, taking precedence over timeout_seconds
+    test_results.append(await run_test("Timeout Millis", "sleep 3",
+                                     timeout_seconds=10,
+                                     timeout_ms=500,
+                                     expected_exception_type=TimeoutError,
+                                     expected_exception_message_contains="timed out after 0.5 seconds"))
+
+    # 18. Zero timeout means no timeout
+    test_results.append(await run_test("Zero Timeout", "echo no timeout",
+                                     timeout_ms=0,
+                                     expected_stdout_contains="no timeout", expected_exit_code=0))
+
+    # 19. Pipeline wiring stdout to the next stdin, fed from stdin_str
+    test_results.append(await run_pipeline_test("Pipeline", ["cat", "tr a-z A-Z", "sed s/HELLO/BYE/"],
+                                              stdin_str="hello pipeline",
+                                              expected_stdout_contains="BYE PIPELINE",
+                                              expected_exit_code=0, expected_stage_exit_codes=[0, 0, 0]))
+
+    # 20. Failing intermediate stage is reported, exit code follows the last stage without pipefail
+    test_results.append(await run_pipeline_test("Pipeline Without Pipefail", ["sh -c 'echo partial; exit 3'", "cat"],
+                                              expected_stdout_contains="partial",
+                                              expected_exit_code=0, expected_stage_exit_codes=[3, 0]))
+
+    # 21. With pipefail the failing stage decides the exit code
+    test_results.append(await run_pipeline_test("Pipeline With Pipefail", ["sh -c 'echo partial; exit 3'", "cat"],
+                                              pipefail=True,
+                                              expected_exit_code=3, expected_stage_exit_codes=[3, 0]))
+
+    # 22. Resolved env snapshot, secret-looking values redacted by default
+    test_results.append(await run_test("Resolved Env Redacted", "true",
+                                     env_vars={"MY_PLAIN_VAR": "visible", "MY_API_TOKEN": "hunter2"},
+                                     include_resolved_env=True,
+                                     expected_resolved_env={"MY_PLAIN_VAR": "visible", "MY_API_TOKEN": "<redacted>"}))
+
+    # 23. Resolved env snapshot including secrets
+    test_results.append(await run_test("Resolved Env With Secrets", "true",
+                                     env_vars={"MY_API_TOKEN": "hunter2"},
+                                     include_resolved_env=True,
+                                     include_secret_env=True,
+                                     expected_resolved_env={"MY_API_TOKEN": "hunter2"}))
+
+    # 24. Children get SIGTERM (15) as parent-death signal on Linux unless disabled
+    if sys.platform.startswith("linux"):
+        get_pdeathsig = ("python3 -c \"import ctypes; libc = ctypes.CDLL(None); sig = ctypes.c_int(); "
+                         "libc.prctl(2, ctypes.byref(sig)); print(f'pdeathsig={sig.value}')\"")
+        test_results.append(await run_test("Kill On Parent Exit", get_pdeathsig,
+                                         expected_stdout_contains="pdeathsig=15", expected_exit_code=0))
+        test_results.append(await run_test("Kill On Parent Exit Disabled", get_pdeathsig,
+                                         kill_on_parent_exit=False,
+                                         expected_stdout_contains="pdeathsig=0", expected_exit_code=0))
+
+    # 25. Transcript combines invocation, both streams and exit status
+    test_results.append(await run_test("Transcript", "sh -c 'echo out; echo err >&2; exit 4'",
+                                     expected_exit_code=4,
+                                     expected_transcript_contains=["$ sh -c 'echo out; echo err >&2; exit 4'\n",
+                                                                   "stdout ----------\nout\n",
+                                                                   "stderr ----------\nerr\n",
+                                                                   "exit status: 4"]))
+
+    # 26. Niceness is applied to the child before exec on Unix
+    if sys.platform != "win32":
+        get_nice = "python3 -c \"import os; print(f'nice={os.nice(0)}')\""
+        test_results.append(await run_test("Nice", get_nice, nice=10,
+                                         expected_stdout_contains="nice=10", expected_exit_code=0))
+        test_results.append(await run_pipeline_test("Pipeline Nice", [get_nice, "cat"], nice=5,
+                                                  expected_stdout_contains="nice=5", expected_exit_code=0))
+
+    # 27. Command header prepended to the captured stdout
+    test_results.append(await run_test("Prepend Command Header", "echo 'header test'",
+                                     prepend_command_header=True,
+                                     expected_stdout_contains="$ echo 'header test'\nheader test\n", expected_exit_code=0))
+
+    # 28. Non UTF-8 output decoded with the requested charset, raw bytes kept as written
+    latin1_cmd = "python3 -c \"import sys; sys.stdout.buffer.write(b'caf\\xe9'); sys.stderr.buffer.write(b'\\xe9')\""
+    test_results.append(await run_test("Encoding Latin-1", latin1_cmd, encoding="latin1",
+                                     expected_stdout_contains="café", expected_stderr_contains="é",
+                                     expected_stdout_bytes=b"caf\xe9", expected_exit_code=0))
+
+    # 29. stderr can use its own charset
+    test_results.append(await run_test("Encoding Per Stream", latin1_cmd, stderr_encoding="latin1",
+                                     expected_stdout_contains="caf\ufffd", expected_stderr_contains="é",
+                                     expected_exit_code=0))
+
+    # 30. umask is applied to the child before exec on Unix
+    if sys.platform != "win32":
+        get_umask = "python3 -c \"import os; print(f'umask={os.umask(0):03o}')\""
+        test_results.append(await run_test("Umask", get_umask, umask=0o027,
+                                         expected_stdout_contains="umask=027", expected_exit_code=0))
+        test_results.append(await run_pipeline_test("Pipeline Umask", [get_umask, "cat"], umask=0o077,
+                                                  expected_stdout_contains="umask=077", expected_exit_code=0))
+
+    # 31. Only the last lines of each stream are kept, with a marker for the dropped ones
+    many_lines = "python3 -c \"import sys; [print(i) or print(i, file=sys.stderr) for i in range(1, 10001)]\""
+    test_results.append(await run_test("Tail Lines", many_lines, tail_lines=3,
+                                     expected_stdout_contains="... (truncated 9997 lines) ...\n9998\n9999\n10000\n",
+                                     expected_stderr_contains="... (truncated 9997 lines) ...\n9998\n9999\n10000\n",
+                                     expected_exit_code=0))
+    test_results.append(await run_test("Tail Lines Not Reached", "printf 'a\\nb'", tail_lines=3,
+                                     expected_stdout_bytes=b"a\nb", expected_exit_code=0))
+    test_results.append(await run_pipeline_test("Pipeline Tail Lines", ["seq 1 100", "cat"], tail_lines=2,
+                                              expected_stdout_contains="... (truncated 98 lines) ...\n99\n100\n",
+                                              expected_exit_code=0))
+
+    # 32. Declared exit codes count as success, 0 alone is the default
+    test_results.append(await run_test("Success Exit Codes", "sh -c 'exit 1'", success_exit_codes=[0, 1],
+                                     expected_success=True, expected_exit_code=1))
+    test_results.append(await run_test("Success Exit Codes Default", "sh -c 'exit 1'",
+                                     expected_success=False, expected_exit_code=1))
+    test_results.append(await run_test("Success Exit Codes Exclude Zero", "true", success_exit_codes=[1],
+                                     expected_success=False, expected_exit_code=0))
+
+    # 33. A timed out child is killed instead of left running
+    if sys.platform != "win32":
+        marker = os.path.join(tempfile.gettempdir(), "rust_executor_timeout_marker")
+        if os.path.exists(marker):
+            os.remove(marker)
+        passed = await run_test("Timeout Kills Child", f"sh -c 'sleep 1; touch {marker}'",
+                                timeout_ms=200,
+                                expected_exception_type=TimeoutError)
+        await asyncio.sleep(1.5)
+        if os.path.exists(marker):
+            print(f"FAIL: Expected the timed out command to be killed before it created {marker}")
+            passed = False
+        test_results.append(passed)
+
+    # 34. Structured fields of executor log records reach Python logging
+    records = []
+    class RecordCollector(logging.Handler):
+        def emit(self, record):
+            records.append(record)
+    collector = RecordCollector()
+    logging.getLogger("agent_lifecycle_rust").addHandler(collector)
+    try:
+        passed = await run_test("Log Record Fields", "sh -c 'exit 3'", expected_exit_code=3)
+    finally:
+        logging.getLogger("agent_lifecycle_rust").removeHandler(collector)
+    finished = [r for r in records if getattr(r, "exit_code", None) == 3]
+    if not finished or not isinstance(getattr(finished[0], "pid", None), int) or finished[0].timed_out is not False:
+        print(f"FAIL: Expected a log record with exit_code, pid and timed_out fields, got {[r.__dict__ for r in records]}")
+        passed = False
+    test_results.append(passed)
+
     print("\n--- Test Summary ---")
     if all(test_results):
         print("All tests PASSED!")